@@ -0,0 +1,85 @@
+//! Runs the analysis thread's per-hop pipeline (ring buffer -> sliding window FFT -> beat tracking
+//! and spectral features -> triple buffer) and verifies that steady-state frames perform no heap
+//! allocations, reporting the time per frame.
+//!
+//! Run with `cargo bench --bench analysis_alloc`.
+
+use ringbuf::{
+    HeapRb,
+    traits::{Producer, Split},
+};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use audio_visualizer::analysis::{Spectrum, Window};
+use audio_visualizer::audio::AnalysisPipeline;
+use audio_visualizer::triple_buffer::triple_buffer;
+
+/// Global allocator that counts every allocation made by the process
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const SAMPLE_RATE: usize = 48_000;
+const FRAMES: usize = 2_000;
+
+fn main() {
+    let mut failed = false;
+
+    for fft_size in [512, 1024, 2048, 4096] {
+        let hop_size = fft_size / 2;
+
+        // Everything the pipeline needs is allocated here, before measuring
+        let (mut producer, mut consumer) = HeapRb::<f32>::new(SAMPLE_RATE).split();
+        let (mut input, mut output) = triple_buffer(Spectrum::new(fft_size));
+        let mut pipeline = AnalysisPipeline::new(fft_size, SAMPLE_RATE as u32);
+        let tone: Vec<f32> = (0..hop_size)
+            .map(|i| (2.0 * std::f32::consts::PI * 1000.0 * i as f32 / SAMPLE_RATE as f32).sin())
+            .collect();
+
+        let allocations_before = ALLOCATIONS.load(Ordering::Relaxed);
+        let start = Instant::now();
+
+        for _ in 0..FRAMES {
+            producer.push_slice(&tone);
+            pipeline.configure(fft_size, Window::default());
+            pipeline.analyse_pending(&mut consumer, &mut input);
+            if output.update() {
+                std::hint::black_box(output.output_buffer());
+            }
+        }
+
+        let elapsed = start.elapsed();
+        let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations_before;
+        println!(
+            "fft_size {fft_size:>5}: {:>8.2} us/frame, {allocations} allocations in {FRAMES} frames",
+            elapsed.as_secs_f64() * 1e6 / FRAMES as f64
+        );
+        failed |= allocations > 0;
+    }
+
+    if failed {
+        eprintln!("analysis pipeline allocated on the real-time path");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,72 @@
+//! Compares the real-input (R2C) analysis path against a full complex FFT of the same real
+//! signal, which is what the analysis did before switching to `realfft`.
+//!
+//! Run with `cargo bench --bench fft`.
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use rustfft::{FftPlanner, num_complex::Complex};
+use std::hint::black_box;
+
+use audio_visualizer::analysis::{Analyzer, Spectrum, hann_window};
+
+const SAMPLE_RATE: f32 = 48_000.0;
+const FFT_SIZES: [usize; 5] = [512, 1024, 2048, 4096, 8192];
+
+/// Test signal: two tones plus a little broadband content
+fn test_signal(len: usize) -> Vec<f32> {
+    (0..len)
+        .map(|i| {
+            let t = i as f32 / SAMPLE_RATE;
+            (2.0 * std::f32::consts::PI * 440.0 * t).sin()
+                + 0.5 * (2.0 * std::f32::consts::PI * 3_000.0 * t).sin()
+                + 0.01 * ((i * 7919) % 97) as f32 / 97.0
+        })
+        .collect()
+}
+
+fn bench_fft(c: &mut Criterion) {
+    let mut group = c.benchmark_group("fft");
+
+    for fft_size in FFT_SIZES {
+        let signal = test_signal(fft_size);
+
+        // Previous path: widen real samples to complex, transform, keep the lower half
+        let fft = FftPlanner::new().plan_fft_forward(fft_size);
+        let window = hann_window(fft_size);
+        let mut buffer = vec![Complex::new(0.0, 0.0); fft_size];
+        let mut scratch = vec![Complex::new(0.0, 0.0); fft.get_inplace_scratch_len()];
+        let mut magnitudes = vec![0.0f32; fft_size / 2 + 1];
+        group.bench_with_input(
+            BenchmarkId::new("complex", fft_size),
+            &signal,
+            |b, signal| {
+                b.iter(|| {
+                    for ((value, sample), weight) in buffer.iter_mut().zip(signal).zip(&window) {
+                        *value = Complex::new(sample * weight, 0.0);
+                    }
+                    fft.process_with_scratch(&mut buffer, &mut scratch);
+                    for (magnitude, value) in magnitudes.iter_mut().zip(&buffer) {
+                        *magnitude = value.norm_sqr().sqrt();
+                    }
+                    black_box(&magnitudes);
+                })
+            },
+        );
+
+        // Current path: real-to-complex transform of the same window
+        let mut analyzer = Analyzer::new(fft_size, fft_size);
+        analyzer.advance(|samples| samples.copy_from_slice(&signal));
+        let mut spectrum = Spectrum::new(fft_size);
+        group.bench_function(BenchmarkId::new("real", fft_size), |b| {
+            b.iter(|| {
+                analyzer.compute(&mut spectrum);
+                black_box(&spectrum);
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_fft);
+criterion_main!(benches);
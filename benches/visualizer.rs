@@ -0,0 +1,110 @@
+//! Benchmarks for the per-frame hot paths: windowed FFT analysis, mapping FFT bins to bars and
+//! rendering a full frame.
+//!
+//! Run with `cargo bench --bench visualizer`.
+
+use audio_visualizer::analysis::{Analyzer, Spectrum};
+use audio_visualizer::app::App;
+use audio_visualizer::bars::spectrum_to_bars;
+use audio_visualizer::config::Config;
+use audio_visualizer::event_log::EventLog;
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use ratatui::{Terminal, backend::TestBackend};
+use std::hint::black_box;
+
+const SAMPLE_RATE: f32 = 48_000.0;
+const FFT_SIZES: [usize; 4] = [512, 1024, 4096, 16384];
+const BAR_COUNTS: [usize; 5] = [8, 16, 32, 64, 128];
+
+/// Test signal: a few tones spread over the spectrum plus a little broadband content
+fn test_signal(len: usize) -> Vec<f32> {
+    (0..len)
+        .map(|i| {
+            let t = i as f32 / SAMPLE_RATE;
+            [110.0, 440.0, 3_000.0, 12_000.0]
+                .iter()
+                .map(|freq| (2.0 * std::f32::consts::PI * freq * t).sin())
+                .sum::<f32>()
+                + 0.01 * ((i * 7919) % 97) as f32 / 97.0
+        })
+        .collect()
+}
+
+/// Magnitudes of the test signal for frames of `fft_size` samples
+fn test_magnitudes(fft_size: usize) -> Vec<f32> {
+    let signal = test_signal(fft_size);
+    let mut analyzer = Analyzer::new(fft_size, fft_size);
+    analyzer.advance(|samples| samples.copy_from_slice(&signal));
+    let mut spectrum = Spectrum::new(fft_size);
+    analyzer.compute(&mut spectrum);
+    spectrum.magnitudes
+}
+
+/// One analysis frame: slide the window by a hop, then window and transform it
+fn bench_analysis(c: &mut Criterion) {
+    let mut group = c.benchmark_group("analysis");
+
+    for fft_size in FFT_SIZES {
+        let hop_size = fft_size / 2;
+        let signal = test_signal(hop_size);
+        let mut analyzer = Analyzer::new(fft_size, hop_size);
+        let mut spectrum = Spectrum::new(fft_size);
+        group.bench_function(BenchmarkId::from_parameter(fft_size), |b| {
+            b.iter(|| {
+                analyzer.advance(|hop| hop.copy_from_slice(&signal));
+                analyzer.compute(&mut spectrum);
+                black_box(&spectrum);
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_bar_mapping(c: &mut Criterion) {
+    let mut group = c.benchmark_group("spectrum_to_bars");
+
+    for fft_size in FFT_SIZES {
+        let magnitudes = test_magnitudes(fft_size);
+        for bar_count in BAR_COUNTS {
+            group.bench_with_input(
+                BenchmarkId::new(format!("fft{fft_size}"), bar_count),
+                &bar_count,
+                |b, &bar_count| b.iter(|| spectrum_to_bars(black_box(&magnitudes), bar_count, 1.0)),
+            );
+        }
+    }
+
+    group.finish();
+}
+
+fn bench_render(c: &mut Criterion) {
+    let mut group = c.benchmark_group("render");
+    let magnitudes = test_magnitudes(Config::default().fft_size);
+
+    for (width, height) in [(80, 24), (200, 60)] {
+        for bar_count in [32, 128] {
+            let config = Config {
+                bar_count,
+                ..Config::default()
+            };
+            let mut app = App::without_audio(config, cpal::default_host(), EventLog::new());
+            app.set_spectrum(&magnitudes);
+            let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+
+            group.bench_function(
+                BenchmarkId::new(format!("{width}x{height}"), bar_count),
+                |b| {
+                    b.iter(|| {
+                        terminal.draw(|frame| app.render(frame)).unwrap();
+                    })
+                },
+            );
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_analysis, bench_bar_mapping, bench_render);
+criterion_main!(benches);
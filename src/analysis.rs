@@ -0,0 +1,353 @@
+use realfft::num_complex::Complex;
+use realfft::{RealFftPlanner, RealToComplex};
+use std::sync::Arc;
+
+use crate::bands::{Band, band_levels};
+use crate::features::SpectralFeatures;
+use crate::rhythm::Rhythm;
+
+/// Spectrum of one analysis frame
+#[derive(Debug, Clone, Default)]
+pub struct Spectrum {
+    /// Complex value of each FFT bin from DC up to and including Nyquist
+    pub bins: Vec<Complex<f32>>,
+    /// Magnitude of each bin in `bins`
+    pub magnitudes: Vec<f32>,
+    /// Number of samples the frame was computed from
+    pub fft_size: usize,
+    /// Sequence number of the frame since the analysis started
+    pub frame: u64,
+    /// Onsets, beats and tempo detected up to and including this frame
+    pub rhythm: Rhythm,
+    /// Shape descriptors of this frame's spectrum
+    pub features: SpectralFeatures,
+    /// Window the frame was weighted with before the FFT
+    pub window: Window,
+}
+
+impl Spectrum {
+    /// Create an empty spectrum for frames of `fft_size` samples
+    pub fn new(fft_size: usize) -> Self {
+        let mut spectrum = Self::default();
+        spectrum.resize(fft_size);
+        spectrum
+    }
+
+    /// Resize the buffers for frames of `fft_size` samples (allocates only if the size changed)
+    pub fn resize(&mut self, fft_size: usize) {
+        let bins = fft_size / 2 + 1;
+        self.bins.resize(bins, Complex::new(0.0, 0.0));
+        self.magnitudes.resize(bins, 0.0);
+        self.fft_size = fft_size;
+    }
+
+    /// Overwrite this spectrum with `other`, reusing the buffers (allocates only if they must grow)
+    pub fn copy_from(&mut self, other: &Spectrum) {
+        self.resize(other.fft_size);
+        self.bins.copy_from_slice(&other.bins);
+        self.magnitudes.copy_from_slice(&other.magnitudes);
+        self.frame = other.frame;
+        self.rhythm = other.rhythm;
+        self.features = other.features;
+        self.window = other.window;
+    }
+
+    /// Level of each of `bands` in dB relative to a full-scale sine; see [`band_levels`]
+    pub fn band_levels(&self, sample_rate: u32, bands: &[Band]) -> Vec<f32> {
+        band_levels(
+            &self.magnitudes,
+            sample_rate,
+            self.fft_size,
+            self.window,
+            bands,
+        )
+    }
+}
+
+/// Window applied to each frame before the FFT
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Window {
+    /// Narrow main lobe and fast-falling side lobes; suits the display
+    #[default]
+    Hann,
+    /// Flat main lobe so peaks read their true amplitude wherever they fall between bins, at the
+    /// cost of a main lobe several bins wide
+    FlatTop,
+}
+
+impl Window {
+    /// All windows, indexed by [`Window::index`]
+    pub const ALL: [Window; 2] = [Window::Hann, Window::FlatTop];
+
+    /// Position of the window in [`Window::ALL`]
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Coefficients of the sum of cosines the window is made of
+    fn cosine_terms(self) -> &'static [f32] {
+        match self {
+            Window::Hann => &[0.5, 0.5],
+            // Flat-top window as used by common audio analyzers (e.g. Matlab's `flattopwin`)
+            Window::FlatTop => &[
+                0.215_578_95,
+                0.416_631_58,
+                0.277_263_16,
+                0.083_578_95,
+                0.006_947_37,
+            ],
+        }
+    }
+
+    /// The window's `size` weights
+    pub fn coefficients(self, size: usize) -> Vec<f32> {
+        let terms = self.cosine_terms();
+        let denominator = size.saturating_sub(1).max(1) as f32;
+        (0..size)
+            .map(|i| {
+                let phase = 2.0 * std::f32::consts::PI * i as f32 / denominator;
+                terms
+                    .iter()
+                    .enumerate()
+                    .map(|(k, a)| {
+                        let sign = if k % 2 == 0 { 1.0 } else { -1.0 };
+                        sign * a * (k as f32 * phase).cos()
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+
+    /// Mean weight; scales the magnitude of a sine's peak bin
+    pub fn coherent_gain(self) -> f32 {
+        self.cosine_terms()[0]
+    }
+
+    /// Mean squared weight; scales the total energy of a signal
+    pub fn power_gain(self) -> f32 {
+        let terms = self.cosine_terms();
+        terms[0] * terms[0] + terms[1..].iter().map(|a| a * a / 2.0).sum::<f32>()
+    }
+
+    /// Bins either side of a sine's peak that hold practically all of its energy
+    pub fn main_lobe_bins(self) -> usize {
+        match self {
+            Window::Hann => 2,
+            Window::FlatTop => 5,
+        }
+    }
+
+    /// Display name
+    pub fn name(self) -> &'static str {
+        match self {
+            Window::Hann => "Hann",
+            Window::FlatTop => "flat-top",
+        }
+    }
+}
+
+/// Windowed real-input FFT analysis over a sliding window of samples.
+///
+/// All buffers are allocated up front (and again only when the FFT size changes), so advancing
+/// the window and computing a spectrum never allocates and can run on a real-time thread.
+pub struct Analyzer {
+    /// Caches plans, so switching back to a previously used size reuses its plan
+    planner: RealFftPlanner<f32>,
+    fft: Arc<dyn RealToComplex<f32>>,
+    hop_size: usize,
+    window_kind: Window,
+    window: Vec<f32>,
+    samples: Vec<f32>,
+    /// FFT input; overwritten by the transform, so the windowed samples are copied here
+    input: Vec<f32>,
+    scratch: Vec<Complex<f32>>,
+}
+
+impl Analyzer {
+    /// Create an analyzer for frames of `fft_size` samples, advancing `hop_size` samples per frame
+    pub fn new(fft_size: usize, hop_size: usize) -> Self {
+        let mut planner = RealFftPlanner::new();
+        let fft = planner.plan_fft_forward(fft_size);
+        let mut analyzer = Self {
+            planner,
+            fft,
+            hop_size: 0,
+            window_kind: Window::Hann,
+            window: Vec::new(),
+            samples: Vec::new(),
+            input: Vec::new(),
+            scratch: Vec::new(),
+        };
+        analyzer.set_fft_size(fft_size, hop_size);
+        analyzer
+    }
+
+    /// Number of samples per frame
+    pub fn fft_size(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Number of new samples per frame
+    pub fn hop_size(&self) -> usize {
+        self.hop_size
+    }
+
+    /// Window applied to each frame
+    pub fn window(&self) -> Window {
+        self.window_kind
+    }
+
+    /// Change the window applied to each frame (allocates only if the window changed)
+    pub fn set_window(&mut self, window: Window) {
+        if window != self.window_kind {
+            self.window_kind = window;
+            self.window = window.coefficients(self.fft_size());
+        }
+    }
+
+    /// Change the frame and hop size, keeping the newest samples of the current window
+    pub fn set_fft_size(&mut self, fft_size: usize, hop_size: usize) {
+        assert!(fft_size >= 2 && hop_size > 0 && hop_size <= fft_size);
+        self.hop_size = hop_size;
+        if fft_size == self.samples.len() {
+            return;
+        }
+
+        self.fft = self.planner.plan_fft_forward(fft_size);
+        self.window = self.window_kind.coefficients(fft_size);
+        self.input = self.fft.make_input_vec();
+        self.scratch = self.fft.make_scratch_vec();
+
+        // Keep the most recent history aligned to the end of the new window
+        let mut samples = vec![0.0; fft_size];
+        let keep = fft_size.min(self.samples.len());
+        samples[fft_size - keep..].copy_from_slice(&self.samples[self.samples.len() - keep..]);
+        self.samples = samples;
+    }
+
+    /// Slide the window forward by one hop, letting `fill` write the `hop_size` newest samples
+    pub fn advance(&mut self, fill: impl FnOnce(&mut [f32])) {
+        let fft_size = self.fft_size();
+        self.samples.copy_within(self.hop_size.., 0);
+        fill(&mut self.samples[fft_size - self.hop_size..]);
+    }
+
+    /// Compute the spectrum of the current window into `spectrum`
+    pub fn compute(&mut self, spectrum: &mut Spectrum) {
+        spectrum.resize(self.fft_size());
+        spectrum.window = self.window_kind;
+
+        for ((input, sample), weight) in self.input.iter_mut().zip(&self.samples).zip(&self.window)
+        {
+            *input = sample * weight;
+        }
+
+        // Buffer lengths always match the plan, so the transform cannot fail
+        let _ =
+            self.fft
+                .process_with_scratch(&mut self.input, &mut spectrum.bins, &mut self.scratch);
+
+        for (magnitude, bin) in spectrum.magnitudes.iter_mut().zip(&spectrum.bins) {
+            *magnitude = bin.norm_sqr().sqrt();
+        }
+    }
+}
+
+/// Hann window of `size` points
+pub fn hann_window(size: usize) -> Vec<f32> {
+    Window::Hann.coefficients(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `len` samples of a sine completing `cycles` periods every `period` samples
+    fn sine(len: usize, cycles: f32, period: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| (2.0 * std::f32::consts::PI * cycles * i as f32 / period as f32).sin())
+            .collect()
+    }
+
+    /// Spectrum of a single frame holding `samples`, with `window`
+    fn spectrum_of(samples: &[f32], window: Window) -> Spectrum {
+        let mut analyzer = Analyzer::new(samples.len(), samples.len());
+        analyzer.set_window(window);
+        analyzer.advance(|frame| frame.copy_from_slice(samples));
+        let mut spectrum = Spectrum::new(samples.len());
+        analyzer.compute(&mut spectrum);
+        spectrum
+    }
+
+    #[test]
+    fn real_fft_matches_a_direct_dft() {
+        let samples: Vec<f32> = (0..32).map(|i| ((i * 7919) % 23) as f32 / 23.0).collect();
+        let spectrum = spectrum_of(&samples, Window::Hann);
+        assert_eq!(spectrum.bins.len(), 17);
+
+        let window = hann_window(samples.len());
+        for (k, bin) in spectrum.bins.iter().enumerate() {
+            let expected: Complex<f32> = samples
+                .iter()
+                .zip(&window)
+                .enumerate()
+                .map(|(n, (sample, weight))| {
+                    let phase = -2.0 * std::f32::consts::PI * (k * n) as f32 / 32.0;
+                    Complex::from_polar(sample * weight, phase)
+                })
+                .sum();
+            assert!(
+                (bin - expected).norm() < 1e-4,
+                "bin {k}: {bin} != {expected}"
+            );
+            assert!((spectrum.magnitudes[k] - expected.norm()).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn sine_peaks_at_its_bin_scaled_by_the_coherent_gain() {
+        for window in Window::ALL {
+            let spectrum = spectrum_of(&sine(1024, 64.0, 1024), window);
+            let (peak, &magnitude) = spectrum
+                .magnitudes
+                .iter()
+                .enumerate()
+                .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                .unwrap();
+            assert_eq!(peak, 64);
+            let expected = 512.0 * window.coherent_gain();
+            assert!((magnitude - expected).abs() < expected * 0.01, "{window:?}");
+            assert_eq!(spectrum.window, window);
+        }
+    }
+
+    #[test]
+    fn frames_slide_by_the_hop() {
+        let mut analyzer = Analyzer::new(8, 2);
+        for hop in 0..4 {
+            analyzer.advance(|samples| samples.copy_from_slice(&[hop as f32; 2]));
+        }
+        assert_eq!(analyzer.samples, [0.0, 0.0, 1.0, 1.0, 2.0, 2.0, 3.0, 3.0]);
+
+        // Resizing keeps the newest samples at the end of the window
+        analyzer.set_fft_size(4, 4);
+        assert_eq!(analyzer.samples, [2.0, 2.0, 3.0, 3.0]);
+        analyzer.set_fft_size(6, 3);
+        assert_eq!(analyzer.samples, [0.0, 0.0, 2.0, 2.0, 3.0, 3.0]);
+        assert_eq!((analyzer.fft_size(), analyzer.hop_size()), (6, 3));
+    }
+
+    #[test]
+    fn window_gains_match_the_coefficients() {
+        for window in Window::ALL {
+            let coefficients = window.coefficients(4096);
+            let mean = coefficients.iter().sum::<f32>() / 4096.0;
+            let mean_square = coefficients.iter().map(|w| w * w).sum::<f32>() / 4096.0;
+            assert!((mean - window.coherent_gain()).abs() < 1e-3, "{window:?}");
+            assert!(
+                (mean_square - window.power_gain()).abs() < 1e-3,
+                "{window:?}"
+            );
+        }
+    }
+}
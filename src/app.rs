@@ -0,0 +1,3128 @@
+use color_eyre::Result;
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseEvent, MouseEventKind,
+};
+use ratatui::{
+    DefaultTerminal, Frame,
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Bar, BarChart, BarGroup, Block, Borders, Clear, Paragraph, Wrap},
+};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::time;
+
+use crate::audio::{AudioProcessor, StreamErrorCounts, StreamEvent, get_input_devices};
+use crate::averaging::{AveragingMode, SpectrumAverager};
+use crate::axis::{bin_frequency, db_ticks, frequency_ticks};
+use crate::bands::band_levels;
+use crate::bars::{
+    MAX_BAR_HEIGHT, bar_bins, bar_magnitude, bins_per_bar, magnitude_to_db, spectrum_to_bars,
+};
+use crate::chroma::{Chroma, PITCH_CLASSES, chroma};
+use crate::config::{self, Config, StreamErrorPolicy, ViewMode};
+use crate::devices::{Backoff, DeviceUpdate, DeviceWorker};
+use crate::event_log::{EventLog, Level, format_time};
+use crate::export::SpectrumExport;
+use crate::features::{FeaturesCsv, SpectralFeatures, spectral_features};
+use crate::history::SpectrumHistory;
+use crate::measure::{Distortion, measure_distortion};
+use crate::pitch::{NOTE_NAMES, Note, detect_pitch};
+use crate::recording::{Recorder, recording_path};
+use crate::reference::Reference;
+use crate::rhythm::Rhythm;
+use crate::snapshot::{snapshot_path, spectrum_plot};
+
+/// How often the input device list is re-enumerated to pick up hot-plugged devices
+const DEVICE_SCAN_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Longest wait between attempts to reconnect to a preferred device that fails to open
+const MAX_RECONNECT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long the most recent log entry stays visible as a toast
+const TOAST_DURATION: Duration = Duration::from_secs(4);
+
+/// Width of the dB labels column, including a space before the bars
+const DB_AXIS_WIDTH: u16 = 5;
+
+/// How long the cursor readout holds a peak level before following the signal down again
+const PEAK_HOLD_DURATION: Duration = Duration::from_secs(2);
+
+/// Height of the measurement panel: three rows of readouts inside a border
+const MEASUREMENT_HEIGHT: u16 = 5;
+
+/// Height of the band meters panel: a row of labels and a row of meters inside a border
+const BANDS_HEIGHT: u16 = 4;
+
+/// Level at which the band meters are empty; they are full at 0 dB
+const BAND_FLOOR_DB: f32 = -80.0;
+
+/// Height of the spectral features panel: two rows of readouts inside a border
+const FEATURES_HEIGHT: u16 = 4;
+
+/// Number of frames kept for the scrolling chroma heatmap
+const CHROMA_HISTORY: usize = 512;
+
+/// Characters used to shade heatmap cells from quiet to loud
+const HEATMAP_SHADES: [&str; 5] = [" ", "░", "▒", "▓", "█"];
+
+/// How long the beat indicator and beat pulse stay lit after a beat
+const BEAT_FLASH_DURATION: Duration = Duration::from_millis(150);
+
+/// Colour of the reference trace drawn over the bars
+const REFERENCE_COLOR: Color = Color::White;
+
+/// Minimum time between repeated warnings for frequent stream problems (xruns, overflows)
+const STREAM_WARNING_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Shortest time between stream restarts under [`StreamErrorPolicy::Restart`]; the interval
+/// doubles while backend errors keep coming
+const MIN_RESTART_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Longest time between stream restarts; a stream that ran this long without a restart starts
+/// again from the shortest interval
+const MAX_RESTART_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Why a device is being opened in the background, which decides what is done with the result
+enum OpenPurpose {
+    /// Return to the preferred device after it went away
+    Reconnect,
+    /// Keep audio flowing from the default device while the preferred one is missing
+    Fallback,
+    /// The user switched to another device; `previous` is reopened if it fails
+    Switch {
+        previous: Option<(String, cpal::Device)>,
+    },
+    /// Reopen the device used before a failed switch
+    Restore,
+    /// The user switched to another host, starting on its default device
+    HostSwitch,
+    /// Rebuild the stream after a backend error
+    Restart,
+}
+
+/// The main application which holds the state and logic of the application.
+pub struct App {
+    /// Is the application running?
+    running: bool,
+    /// Audio host backend devices are opened on
+    host: cpal::Host,
+    /// Audio processor for capturing and analyzing audio
+    audio_processor: Option<AudioProcessor>,
+    /// Current configuration
+    config: Config,
+    /// Latest FFT data for visualization
+    fft_data: Vec<f32>,
+    /// Available audio input devices
+    available_devices: Vec<(String, cpal::Device)>,
+    /// Current device index
+    current_device_index: usize,
+    /// Name of the device the user selected, reconnected to whenever it is present
+    preferred_device: Option<String>,
+    /// Last time the device list was refreshed
+    last_device_scan: Instant,
+    /// Background thread enumerating devices and opening streams, once the app has started
+    devices: Option<DeviceWorker>,
+    /// Whether a device scan requested from `devices` has not been answered yet
+    scan_pending: bool,
+    /// Number and purpose of the open request to `devices` not answered yet; the results of
+    /// earlier requests are discarded
+    pending_open: Option<(u64, OpenPurpose)>,
+    /// Number given to the next open request
+    next_open_id: u64,
+    /// Delay before retrying the preferred device after it failed to reopen
+    reconnect_backoff: Backoff,
+    /// Last render time for FPS limiting
+    last_render: Instant,
+    /// Show help overlay
+    show_help: bool,
+    /// Event log shown as toasts and in the log overlay
+    log: EventLog,
+    /// Show log overlay
+    show_log: bool,
+    /// Number of entries the log overlay is scrolled back from the newest one
+    log_scroll: usize,
+    /// Totals of the problems reported by the audio stream
+    stream_errors: StreamErrorCounts,
+    /// Last time a rate-limited stream warning was logged
+    last_stream_warning: Option<Instant>,
+    /// Delay before the stream may be restarted again after a backend error
+    restart_backoff: Backoff,
+    /// When the stream was last restarted after a backend error
+    last_restart: Option<Instant>,
+    /// Bar highlighted by the cursor, if shown
+    cursor: Option<usize>,
+    /// Highest level in dB of the cursor bar and when it was reached
+    cursor_peak: Option<(f32, Instant)>,
+    /// Distortion of the test tone in the displayed spectrum, while measuring
+    distortion: Option<Distortion>,
+    /// Where the bars were last drawn and the width of each, for mapping mouse positions
+    chart_layout: Option<(Rect, u16)>,
+    /// Recent chroma frames for the heatmap view, oldest first
+    chroma_history: VecDeque<Chroma>,
+    /// Onsets, beats and tempo of the latest spectrum
+    rhythm: Rhythm,
+    /// When the most recent beat was seen
+    last_beat: Option<Instant>,
+    /// Spectral features of the latest spectrum
+    features: SpectralFeatures,
+    /// Export of the features of every new spectrum, if requested
+    features_csv: Option<FeaturesCsv>,
+    /// Export of every new spectrum, if requested
+    export: Option<SpectrumExport>,
+    /// Average or hold of the spectra shown as bars
+    averager: SpectrumAverager,
+    /// When the previous spectrum arrived, for timing the exponential average
+    last_spectrum: Instant,
+    /// Frozen spectrum drawn over the live bars, if any
+    reference: Option<Reference>,
+    /// Where the reference trace is saved across runs, if anywhere
+    reference_path: Option<PathBuf>,
+    /// Recent spectra, which keep arriving while the display is paused
+    history: SpectrumHistory,
+    /// Sequence number of the history frame shown while the display is paused
+    paused: Option<u64>,
+    /// Recorder for the captured audio, matching the format of the current input
+    recorder: Option<Recorder>,
+}
+
+impl App {
+    /// Construct a new instance of [`App`], opening the first input device of `host`.
+    pub fn new(config: Config, host: cpal::Host, log: EventLog) -> Result<Self> {
+        let mut app = Self::without_audio(config, host, log);
+        app.available_devices = get_input_devices(&app.host).unwrap_or_default();
+
+        // Try to initialize audio processor with default device
+        app.audio_processor = if let Some((_, device)) = app.available_devices.first().cloned() {
+            match app.open_processor(Some(device)) {
+                Ok(processor) => Some(processor),
+                Err(e) => {
+                    app.log.warn(format!(
+                        "Failed to initialize audio with selected device: {e}. Trying default device."
+                    ));
+                    app.open_processor(None).ok()
+                }
+            }
+        } else {
+            match app.open_processor(None) {
+                Ok(processor) => Some(processor),
+                Err(e) => {
+                    app.log.warn(format!(
+                        "Failed to initialize audio: {e}. Visualizer will run without audio input."
+                    ));
+                    None
+                }
+            }
+        };
+
+        // Follow the device that actually opened, which may be the fallback
+        if let Some(name) = app
+            .audio_processor
+            .as_ref()
+            .map(|processor| processor.device_name().to_string())
+        {
+            app.current_device_index = app.device_index(&name).unwrap_or(0);
+            app.preferred_device = Some(name);
+        }
+        app.devices =
+            Some(DeviceWorker::new(app.host.id()).map_err(|e| color_eyre::eyre::eyre!("{e}"))?);
+        app.reference_path = Reference::default_path();
+        app.reference = app
+            .reference_path
+            .as_deref()
+            .and_then(|path| Reference::load_from(path).ok());
+
+        Ok(app)
+    }
+
+    /// Construct an [`App`] that has not opened any audio device, e.g. for rendering spectra
+    /// supplied through [`App::set_spectrum`].
+    pub fn without_audio(config: Config, host: cpal::Host, log: EventLog) -> Self {
+        Self {
+            running: false,
+            host,
+            audio_processor: None,
+            fft_data: vec![0.0; config.fft_size / 2 + 1], // Initialize with zeros
+            averager: SpectrumAverager::new(config.averaging, config.averaging_time),
+            history: SpectrumHistory::new(config.history),
+            config,
+            available_devices: Vec::new(),
+            current_device_index: 0,
+            preferred_device: None,
+            last_device_scan: Instant::now(),
+            devices: None,
+            scan_pending: false,
+            pending_open: None,
+            next_open_id: 0,
+            reconnect_backoff: Backoff::new(DEVICE_SCAN_INTERVAL, MAX_RECONNECT_INTERVAL),
+            last_render: Instant::now(),
+            show_help: false,
+            log,
+            show_log: false,
+            log_scroll: 0,
+            stream_errors: StreamErrorCounts::default(),
+            last_stream_warning: None,
+            restart_backoff: Backoff::new(MIN_RESTART_INTERVAL, MAX_RESTART_INTERVAL),
+            last_restart: None,
+            cursor: None,
+            cursor_peak: None,
+            distortion: None,
+            chart_layout: None,
+            chroma_history: VecDeque::with_capacity(CHROMA_HISTORY),
+            rhythm: Rhythm::default(),
+            last_beat: None,
+            features: SpectralFeatures::default(),
+            features_csv: None,
+            export: None,
+            last_spectrum: Instant::now(),
+            reference: None,
+            reference_path: None,
+            paused: None,
+            recorder: None,
+        }
+    }
+
+    /// Add a new spectrum with the bin magnitudes in `magnitudes`, displaying it unless paused
+    pub fn set_spectrum(&mut self, magnitudes: &[f32]) {
+        // Spectra from the analysis thread come with their features; these are computed here
+        let fft_size = magnitudes.len().saturating_sub(1) * 2;
+        let features = SpectralFeatures {
+            flux: self.rhythm.flux,
+            ..spectral_features(magnitudes, self.sample_rate(), fft_size)
+        };
+        self.history.push(magnitudes, features, Instant::now());
+        self.on_spectrum_update();
+    }
+
+    /// Replace the displayed onset and tempo state, lighting the beat indicator if the beat count
+    /// went up
+    pub fn set_rhythm(&mut self, rhythm: Rhythm) {
+        // A restarted stream counts from zero again, which is not a beat
+        if rhythm.beats > self.rhythm.beats {
+            self.last_beat = Some(Instant::now());
+        }
+        self.rhythm = rhythm;
+    }
+
+    /// Write the spectral features of every frame analysed from now on to `csv`
+    pub fn set_features_csv(&mut self, csv: FeaturesCsv) {
+        self.features_csv = Some(csv);
+    }
+
+    /// Write every frame analysed from now on to `export`
+    pub fn set_export(&mut self, export: SpectrumExport) {
+        self.export = Some(export);
+    }
+
+    /// Whether a beat was seen recently enough to still be shown
+    fn on_beat(&self) -> bool {
+        self.last_beat
+            .is_some_and(|beat| beat.elapsed() < BEAT_FLASH_DURATION)
+    }
+
+    /// Border style of the main view, lit up on beats when the beat pulse is enabled
+    fn view_border_style(&self) -> Style {
+        if self.config.beat_pulse && self.on_beat() {
+            Style::default()
+                .fg(self.get_bar_color())
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        }
+    }
+
+    /// The spectrum shown as bars: the frame stepped to while paused, otherwise the average or
+    /// hold if enabled, otherwise the latest spectrum
+    fn displayed_spectrum(&self) -> &[f32] {
+        match self.averager.magnitudes() {
+            averaged if self.paused.is_none() && !averaged.is_empty() => averaged,
+            _ => &self.fft_data,
+        }
+    }
+
+    /// Freeze the display on the newest spectrum, or resume following the live one
+    fn toggle_pause(&mut self) {
+        if self.paused.take().is_some() {
+            self.show_frame(self.history.newest());
+        } else {
+            self.paused = Some(self.history.newest());
+        }
+    }
+
+    /// Pause and step `steps` frames through the history, back in time if negative
+    fn step_history(&mut self, steps: i64) {
+        let current = self.paused.unwrap_or_else(|| self.history.newest());
+        let sequence = self.history.step(current, steps);
+        self.paused = Some(sequence);
+        self.show_frame(sequence);
+    }
+
+    /// Display history frame `sequence`, if it is still kept
+    fn show_frame(&mut self, sequence: u64) {
+        let (Some(magnitudes), Some(features)) =
+            (self.history.get(sequence), self.history.features(sequence))
+        else {
+            return;
+        };
+        self.fft_data.clear();
+        self.fft_data.extend_from_slice(magnitudes);
+        self.features = features;
+        self.update_cursor_peak();
+        self.update_distortion();
+    }
+
+    /// Pass the newly captured audio to the recorder, replacing the recorder (and finishing any
+    /// recording) when the input's format changes
+    fn update_recording(&mut self) {
+        let Some(format) = self
+            .audio_processor
+            .as_ref()
+            .map(|processor| (processor.sample_rate(), processor.channels()))
+        else {
+            return;
+        };
+        if self
+            .recorder
+            .as_ref()
+            .is_none_or(|recorder| (recorder.sample_rate(), recorder.channels()) != format)
+        {
+            self.stop_recording();
+            self.recorder = Some(Recorder::new(format.0, format.1, self.config.pre_roll));
+        }
+
+        let mut result = Ok(());
+        let mut dropped = 0;
+        if let (Some(processor), Some(recorder)) = (&mut self.audio_processor, &mut self.recorder) {
+            dropped = processor.take_captured(|samples| {
+                if result.is_ok() {
+                    result = recorder.push(samples);
+                }
+            });
+        }
+        if let Err(e) = result {
+            self.log
+                .error(format!("Failed to write recording, stopping it: {e}"));
+            self.stop_recording();
+        }
+        self.report_dropped_capture(dropped);
+    }
+
+    /// Warn that the recording in progress has a gap where `samples` captured samples were
+    /// dropped, because the app did not collect them in time
+    fn report_dropped_capture(&mut self, samples: u64) {
+        let Some(recorder) = self
+            .recorder
+            .as_ref()
+            .filter(|recorder| recorder.is_recording())
+        else {
+            return;
+        };
+        if samples == 0 {
+            return;
+        }
+        let frames = samples / u64::from(recorder.channels().max(1));
+        self.log.warn(format!(
+            "Recording lost {:.2} s of audio while the app was busy",
+            frames as f32 / recorder.sample_rate().max(1) as f32
+        ));
+    }
+
+    /// Start recording to a new timestamped file, or finish the recording in progress
+    fn toggle_recording(&mut self) {
+        let Some(recorder) = &mut self.recorder else {
+            self.log.warn("No audio input to record.");
+            return;
+        };
+        if recorder.is_recording() {
+            self.stop_recording();
+            return;
+        }
+
+        let path = recording_path(&self.config.record_dir, SystemTime::now());
+        match recorder.start(&path) {
+            Ok(()) => self.log.info(format!(
+                "Recording to {} ({:.1} s of pre-roll)",
+                path.display(),
+                recorder.duration().as_secs_f32()
+            )),
+            Err(e) => self.log.error(format!("Failed to start recording: {e}")),
+        }
+    }
+
+    /// Finish the recording in progress, if any
+    fn stop_recording(&mut self) {
+        let Some(recorder) = &mut self.recorder else {
+            return;
+        };
+        let duration = recorder.duration();
+        match recorder.stop() {
+            Ok(Some(path)) => self.log.info(format!(
+                "Saved {:.1} s recording to {}",
+                duration.as_secs_f32(),
+                path.display()
+            )),
+            Ok(None) => {}
+            Err(e) => self.log.error(format!("Failed to save recording: {e}")),
+        }
+    }
+
+    /// Save the spectrum currently shown, with the reference trace, as an image
+    fn save_snapshot(&mut self) {
+        let format = self.config.snapshot_format;
+        let path = snapshot_path(&self.config.record_dir, SystemTime::now(), format);
+        let reference = self.reference_bars();
+        let plot = spectrum_plot(
+            self.displayed_spectrum(),
+            self.sample_rate(),
+            &self.config,
+            reference.as_deref(),
+        );
+        match plot.save_new(&path, format) {
+            Ok(()) => self
+                .log
+                .info(format!("Saved spectrum snapshot to {}", path.display())),
+            Err(e) => self.log.error(format!("Failed to save snapshot: {e}")),
+        }
+    }
+
+    /// Keep the spectrum currently shown as bars as the reference trace
+    fn freeze_reference(&mut self) {
+        self.reference = Some(Reference {
+            sample_rate: self.sample_rate(),
+            fft_size: self.fft_data.len().saturating_sub(1) * 2,
+            magnitudes: self.displayed_spectrum().to_vec(),
+        });
+    }
+
+    /// Write every frame analysed since the last call to the spectrum export and features CSV.
+    /// The display may skip frames when analysis outpaces the refresh rate; the exports go
+    /// through the processor's frame queue instead, so they see them all, paused or not.
+    fn export_frames(&mut self) {
+        let Some(processor) = &mut self.audio_processor else {
+            return;
+        };
+        if self.export.is_none() && self.features_csv.is_none() {
+            return;
+        }
+        // Does nothing once enabled, but a replaced processor starts without the queue
+        processor.queue_frames();
+
+        let sample_rate = processor.sample_rate();
+        let (mut exported, mut features_exported) = (Ok(()), Ok(()));
+        processor.drain_frames(|spectrum| {
+            if exported.is_ok()
+                && let Some(export) = &mut self.export
+            {
+                exported = export.write(
+                    spectrum,
+                    sample_rate,
+                    self.config.bar_count,
+                    self.config.sensitivity,
+                );
+            }
+            if features_exported.is_ok()
+                && let Some(csv) = &mut self.features_csv
+            {
+                features_exported = csv.write(spectrum, sample_rate);
+            }
+        });
+        if let Err(e) = exported {
+            self.log.error(format!(
+                "Failed to export spectra, stopping the export: {e}"
+            ));
+            self.export = None;
+        }
+        if let Err(e) = features_exported {
+            self.log.error(format!(
+                "Failed to export features, stopping the export: {e}"
+            ));
+            self.features_csv = None;
+        }
+
+        let dropped = processor.take_dropped_frames();
+        if dropped > 0 {
+            self.log.warn(format!(
+                "{dropped} analysed frames were missed by the export, which fell behind"
+            ));
+        }
+    }
+
+    /// Update the state derived from each new spectrum, the newest one in the history
+    fn on_spectrum_update(&mut self) {
+        let elapsed = self.last_spectrum.elapsed().as_secs_f32();
+        self.last_spectrum = Instant::now();
+        self.averager.update(self.history.latest(), elapsed);
+
+        // Averaging keeps following the audio while the display is paused
+        if self.paused.is_some() {
+            return;
+        }
+
+        self.fft_data.clear();
+        self.fft_data.extend_from_slice(self.history.latest());
+        self.features = self.history.latest_features();
+        self.update_cursor_peak();
+        self.update_distortion();
+
+        if self.config.view == ViewMode::ChromaHeatmap {
+            if self.chroma_history.len() == CHROMA_HISTORY {
+                self.chroma_history.pop_front();
+            }
+            let chroma = self.chroma();
+            self.chroma_history.push_back(chroma);
+        }
+    }
+
+    /// Run the application's main loop.
+    pub async fn run(mut self, mut terminal: DefaultTerminal) -> Result<()> {
+        self.running = true;
+        let mut interval = time::interval(Duration::from_millis(self.config.refresh_rate));
+
+        while self.running {
+            interval.tick().await;
+
+            // React to stream problems, then recover from unplugged devices and pick up newly
+            // connected ones
+            self.handle_stream_events();
+            self.update_devices();
+            self.update_recording();
+            self.export_frames();
+
+            // Update FFT data if audio processor is available
+            if let Some(ref mut processor) = self.audio_processor
+                && let Some(spectrum) = processor.latest_spectrum()
+            {
+                self.history
+                    .push(&spectrum.magnitudes, spectrum.features, Instant::now());
+                let rhythm = spectrum.rhythm;
+                self.set_rhythm(rhythm);
+                self.on_spectrum_update();
+            }
+
+            // Only render if enough time has passed for the configured refresh rate
+            if self.last_render.elapsed() >= Duration::from_millis(self.config.refresh_rate) {
+                terminal.draw(|frame| self.render(frame))?;
+                self.last_render = Instant::now();
+            }
+
+            // Handle events with timeout to avoid blocking
+            if crossterm::event::poll(Duration::from_millis(1))? {
+                self.handle_crossterm_events()?;
+            }
+        }
+
+        if let Some(recorder) = &mut self.recorder {
+            recorder
+                .stop()
+                .map_err(|e| color_eyre::eyre::eyre!("Failed to save recording: {e}"))?;
+        }
+        if let Some(export) = &mut self.export {
+            export
+                .flush()
+                .map_err(|e| color_eyre::eyre::eyre!("Failed to export spectra: {e}"))?;
+        }
+        if let Some(csv) = &mut self.features_csv {
+            csv.flush()
+                .map_err(|e| color_eyre::eyre::eyre!("Failed to export features: {e}"))?;
+        }
+        Ok(())
+    }
+
+    /// Renders the user interface.
+    pub fn render(&mut self, frame: &mut Frame) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3), // Title bar
+                Constraint::Min(0),    // Main visualization
+                Constraint::Length(3), // Status bar
+            ])
+            .split(frame.area());
+
+        // Render title
+        self.render_title(frame, chunks[0]);
+
+        // Render main visualization, with the enabled panels below it
+        let mut constraints = vec![Constraint::Min(0)];
+        if self.config.show_tuner {
+            constraints.push(Constraint::Length(5));
+        }
+        if self.config.measure {
+            constraints.push(Constraint::Length(MEASUREMENT_HEIGHT));
+        }
+        if self.config.show_bands {
+            constraints.push(Constraint::Length(BANDS_HEIGHT));
+        }
+        if self.config.show_features {
+            constraints.push(Constraint::Length(FEATURES_HEIGHT));
+        }
+        let areas = Layout::vertical(constraints).split(chunks[1]);
+        self.render_view(frame, areas[0]);
+        let mut panels = areas.iter().skip(1).copied();
+        if self.config.show_tuner
+            && let Some(area) = panels.next()
+        {
+            self.render_tuner(frame, area);
+        }
+        if self.config.measure
+            && let Some(area) = panels.next()
+        {
+            self.render_measurement(frame, area);
+        }
+        if self.config.show_bands
+            && let Some(area) = panels.next()
+        {
+            self.render_bands(frame, area);
+        }
+        if self.config.show_features
+            && let Some(area) = panels.next()
+        {
+            self.render_features(frame, area);
+        }
+
+        // Render status bar
+        self.render_status(frame, chunks[2]);
+
+        // Render the latest notification over the visualizer
+        self.render_toast(frame, chunks[1]);
+
+        // Render log overlay if requested
+        if self.show_log {
+            self.render_log_overlay(frame);
+        }
+
+        // Render help overlay if requested
+        if self.show_help {
+            self.render_help_overlay(frame);
+        }
+    }
+
+    /// Render the title bar
+    fn render_title(&self, frame: &mut Frame, area: Rect) {
+        let mut spans = vec![Span::styled(
+            "Audio Visualizer ",
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        )];
+        if let Some(recorder) = self.recorder.as_ref().filter(|r| r.is_recording()) {
+            spans.push(Span::styled(
+                format!("[REC {:.1} s] ", recorder.duration().as_secs_f32()),
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            ));
+        }
+        if let Some(sequence) = self.paused {
+            let paused = match self.history.age(sequence) {
+                Some(age) => format!("[PAUSED -{:.2} s] ", age.as_secs_f32()),
+                None => "[PAUSED] ".to_string(),
+            };
+            spans.push(Span::styled(
+                paused,
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            ));
+        }
+        spans.push(Span::styled(
+            "- Press 'h' for help",
+            Style::default().fg(Color::Gray),
+        ));
+        let title = Line::from(spans);
+
+        let title_widget = Paragraph::new(title)
+            .block(Block::default().borders(Borders::ALL))
+            .alignment(Alignment::Center);
+
+        frame.render_widget(title_widget, area);
+    }
+
+    /// Render the selected main view
+    fn render_view(&mut self, frame: &mut Frame, area: Rect) {
+        // Only the spectrum view has bars for the mouse to point at
+        self.chart_layout = None;
+        match self.config.view {
+            ViewMode::Spectrum => self.render_visualizer(frame, area),
+            ViewMode::ChromaBars => self.render_chroma_bars(frame, area),
+            ViewMode::ChromaHeatmap => self.render_chroma_heatmap(frame, area),
+        }
+    }
+
+    /// Render the current chroma as one bar per pitch class
+    fn render_chroma_bars(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(self.view_border_style())
+            .title(format!(
+                "Chromagram (A4 = {:.0} Hz) - {} scheme",
+                self.config.a4,
+                self.config.color_scheme.name()
+            ));
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let bars: Vec<Bar> = self
+            .chroma()
+            .iter()
+            .zip(NOTE_NAMES)
+            .map(|(energy, name)| {
+                Bar::default()
+                    .value((energy * MAX_BAR_HEIGHT as f32) as u64)
+                    .text_value(String::new())
+                    .label(Line::from(name))
+            })
+            .collect();
+        let bar_width = std::cmp::max(1, inner.width / PITCH_CLASSES as u16);
+        let bar_chart = BarChart::default()
+            .data(BarGroup::default().bars(&bars))
+            .bar_width(bar_width.saturating_sub(1).max(1))
+            .bar_gap(if bar_width > 1 { 1 } else { 0 })
+            .max(MAX_BAR_HEIGHT)
+            .bar_style(Style::default().fg(self.get_bar_color()))
+            .label_style(Style::default().fg(Color::Gray));
+        frame.render_widget(bar_chart, inner);
+    }
+
+    /// Render recent chroma frames as a heatmap scrolling to the left, C at the bottom
+    fn render_chroma_heatmap(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(self.view_border_style())
+            .title(format!(
+                "Chromagram history (A4 = {:.0} Hz) - {} scheme",
+                self.config.a4,
+                self.config.color_scheme.name()
+            ));
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        // Note names on the left, one row per pitch class when there is room
+        const LABEL_WIDTH: u16 = 3;
+        let rows = inner.height.min(PITCH_CLASSES as u16);
+        let columns = inner.width.saturating_sub(LABEL_WIDTH) as usize;
+        let bottom = inner.y + inner.height;
+        let style = Style::default().fg(self.get_bar_color());
+        let buffer = frame.buffer_mut();
+        for class in 0..rows {
+            let y = bottom - 1 - class;
+            buffer.set_string(
+                inner.x,
+                y,
+                NOTE_NAMES[class as usize],
+                Style::default().fg(Color::Gray),
+            );
+
+            // Newest frame in the rightmost column
+            let frames = self.chroma_history.iter().rev().take(columns);
+            for (offset, chroma) in frames.enumerate() {
+                let shade = (chroma[class as usize] * (HEATMAP_SHADES.len() - 1) as f32).round();
+                let x = inner.x + LABEL_WIDTH + (columns - 1 - offset) as u16;
+                buffer.set_string(x, y, HEATMAP_SHADES[shade as usize], style);
+            }
+        }
+    }
+
+    /// Render the fundamental, distortion and noise of a test tone
+    fn render_measurement(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::default().borders(Borders::ALL).title(format!(
+            " Measurement ({} window) ",
+            self.config.window().name()
+        ));
+        let width = block.inner(area).width as usize;
+        let text = match &self.distortion {
+            Some(distortion) => {
+                // As many harmonics as fit, lowest first
+                let mut harmonics = String::new();
+                for harmonic in &distortion.harmonics {
+                    let next = format!("H{} {:.1}  ", harmonic.number, harmonic.level);
+                    if harmonics.len() + next.len() + "dBc".len() > width {
+                        break;
+                    }
+                    harmonics.push_str(&next);
+                }
+                vec![
+                    Line::from(format!(
+                        "Fundamental: {:.1} Hz at {:.2} dBFS",
+                        distortion.frequency, distortion.level
+                    )),
+                    Line::from(format!(
+                        "THD: {:.3}% | THD+N: {:.3}% | SNR: {:.1} dB | SINAD: {:.1} dB",
+                        distortion.thd, distortion.thd_n, distortion.snr, distortion.sinad
+                    )),
+                    if distortion.resolved {
+                        Line::styled(
+                            format!("{harmonics}dBc"),
+                            Style::default().fg(Color::Yellow),
+                        )
+                    } else {
+                        Line::styled(
+                            "Harmonic lobes overlap; raise the FFT size to separate them",
+                            Style::default().fg(Color::Red),
+                        )
+                    },
+                ]
+            }
+            None => vec![Line::from("No test tone detected")],
+        };
+        let widget = Paragraph::new(text)
+            .block(block)
+            .style(Style::default().fg(Color::Gray))
+            .alignment(Alignment::Center);
+        frame.render_widget(widget, area);
+    }
+
+    /// Measure the distortion of the test tone in the displayed spectrum, once per new spectrum
+    /// rather than for every widget that shows it
+    fn update_distortion(&mut self) {
+        self.distortion = if self.config.measure {
+            let fft_size = self.fft_data.len().saturating_sub(1) * 2;
+            measure_distortion(
+                &self.fft_data,
+                self.sample_rate(),
+                fft_size,
+                self.config.window(),
+            )
+        } else {
+            None
+        };
+    }
+
+    /// Bars holding the measured fundamental (number 1) and its harmonics, as pairs of bar index
+    /// and harmonic number; a bar holding several keeps the lowest
+    fn harmonic_bars(&self) -> Vec<(usize, usize)> {
+        let Some(distortion) = &self.distortion else {
+            return Vec::new();
+        };
+        let fft_size = self.fft_data.len().saturating_sub(1) * 2;
+        let hz_per_bin = self.sample_rate() as f32 / fft_size as f32;
+        let per_bar = bins_per_bar(self.fft_data.len(), self.config.bar_count);
+
+        let mut bars: Vec<(usize, usize)> = Vec::new();
+        let peaks = std::iter::once((1, distortion.frequency)).chain(
+            distortion
+                .harmonics
+                .iter()
+                .map(|harmonic| (harmonic.number, harmonic.frequency)),
+        );
+        for (number, frequency) in peaks {
+            let bar = (frequency / hz_per_bin).round() as usize / per_bar;
+            if bar < self.config.bar_count && !bars.iter().any(|&(other, _)| other == bar) {
+                bars.push((bar, number));
+            }
+        }
+        bars
+    }
+
+    /// Render the level of each configured band as a label and a horizontal meter
+    fn render_bands(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::default().borders(Borders::ALL).title(" Bands (dB) ");
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let bands = &self.config.bands;
+        if bands.is_empty() || inner.height < 2 {
+            return;
+        }
+        let fft_size = self.fft_data.len().saturating_sub(1) * 2;
+        let levels = band_levels(
+            &self.fft_data,
+            self.sample_rate(),
+            fft_size,
+            self.config.window(),
+            bands,
+        );
+
+        // One column per band with a space between neighbours
+        let column_width = (inner.width / bands.len() as u16).max(1);
+        let meter_width = column_width.saturating_sub(1).max(1) as usize;
+        let meter_style = Style::default().fg(self.get_bar_color());
+        let buffer = frame.buffer_mut();
+        for (index, (band, level)) in bands.iter().zip(levels).enumerate() {
+            let x = inner.x + index as u16 * column_width;
+            if x >= inner.right() {
+                break;
+            }
+            let width = meter_width.min((inner.right() - x) as usize);
+
+            // Shorten the name rather than the level when the column is narrow
+            let level_text = format!("{level:.0}");
+            let name_width = width.saturating_sub(level_text.len() + 1);
+            let label = if name_width > 0 {
+                format!("{:<name_width$.name_width$} {level_text}", band.name)
+            } else {
+                level_text
+            };
+            buffer.set_stringn(x, inner.y, label, width, Style::default().fg(Color::Gray));
+
+            let fill = ((level - BAND_FLOOR_DB) / -BAND_FLOOR_DB).clamp(0.0, 1.0);
+            let filled = (fill * width as f32).round() as usize;
+            buffer.set_string(x, inner.y + 1, "█".repeat(filled), meter_style);
+            buffer.set_string(
+                x + filled as u16,
+                inner.y + 1,
+                "·".repeat(width - filled),
+                Style::default().fg(Color::DarkGray),
+            );
+        }
+    }
+
+    /// Render the spectral features of the current spectrum
+    fn render_features(&self, frame: &mut Frame, area: Rect) {
+        let features = &self.features;
+        let text = vec![
+            Line::from(format!(
+                "Centroid: {:.0} Hz | Bandwidth: {:.0} Hz | Rolloff: {:.0} Hz",
+                features.centroid, features.bandwidth, features.rolloff
+            )),
+            Line::from(format!(
+                "Flatness: {:.3} | Crest: {:.1} | Flux: {:.3}",
+                features.flatness, features.crest, features.flux
+            )),
+        ];
+        let widget = Paragraph::new(text)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(" Spectral features "),
+            )
+            .style(Style::default().fg(Color::Gray))
+            .alignment(Alignment::Center);
+        frame.render_widget(widget, area);
+    }
+
+    /// Chroma of the current spectrum
+    fn chroma(&self) -> Chroma {
+        chroma(
+            &self.fft_data,
+            self.sample_rate(),
+            self.fft_data.len().saturating_sub(1) * 2,
+            self.config.a4,
+        )
+    }
+
+    /// Render the main audio visualizer with its frequency axis and optional dB axis
+    fn render_visualizer(&mut self, frame: &mut Frame, area: Rect) {
+        let mut block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(self.view_border_style())
+            .title(format!(
+                "Frequency Spectrum ({}Hz) - {} bars - {} scheme{}{}",
+                self.sample_rate(),
+                self.config.bar_count,
+                self.config.color_scheme.name(),
+                if self.config.show_db_axis {
+                    " - dB"
+                } else {
+                    ""
+                },
+                match self.config.averaging {
+                    AveragingMode::Off => String::new(),
+                    mode => format!(" - {}", mode.name()),
+                }
+            ));
+        if let Some(readout) = self.cursor_readout() {
+            block = block.title_bottom(Line::styled(readout, Style::default().fg(Color::White)));
+        }
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        // Reserve the bottom row for frequency labels when there is room for the bars as well
+        let (mut chart_area, axis_area) = if inner.height >= 3 {
+            let [chart, axis] =
+                Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(inner);
+            (chart, Some(axis))
+        } else {
+            (inner, None)
+        };
+
+        if self.config.show_db_axis && chart_area.width > DB_AXIS_WIDTH * 2 {
+            let [labels, chart] =
+                Layout::horizontal([Constraint::Length(DB_AXIS_WIDTH), Constraint::Min(0)])
+                    .areas(chart_area);
+            self.render_db_axis(frame, labels);
+            chart_area = chart;
+        }
+
+        let bar_width = std::cmp::max(1, chart_area.width as usize / self.config.bar_count);
+        let harmonic_bars = self.harmonic_bars();
+        let bars = self.prepare_bar_data(&harmonic_bars);
+
+        // Create bar chart with color based on current scheme
+        let mut bar_chart = BarChart::default()
+            .data(BarGroup::default().bars(&bars))
+            .bar_width(bar_width as u16)
+            .bar_gap(0)
+            .bar_style(Style::default().fg(self.get_bar_color()))
+            .value_style(
+                Style::default()
+                    .fg(Color::White)
+                    .add_modifier(Modifier::BOLD),
+            );
+
+        // A dB scale only holds if bar heights are absolute rather than relative to the tallest,
+        // and the reference trace needs the same scale as the bars
+        let reference_bars = self.reference_bars();
+        let max = if self.config.show_db_axis {
+            Some(MAX_BAR_HEIGHT)
+        } else {
+            reference_bars.as_ref().map(|reference| {
+                let live = spectrum_to_bars(
+                    self.displayed_spectrum(),
+                    self.config.bar_count,
+                    self.config.sensitivity,
+                );
+                let live = live.into_iter().max().unwrap_or(0);
+                reference
+                    .iter()
+                    .copied()
+                    .max()
+                    .unwrap_or(0)
+                    .max(live)
+                    .max(1)
+            })
+        };
+        if let Some(max) = max {
+            bar_chart = bar_chart.max(max);
+        }
+        frame.render_widget(bar_chart, chart_area);
+        self.chart_layout = Some((chart_area, bar_width as u16));
+
+        // Trace the top of each reference bar over the live ones
+        if let (Some(reference), Some(max)) = (reference_bars, max) {
+            let rows = u64::from(chart_area.height);
+            for (bar, height) in reference.into_iter().enumerate() {
+                // Eighths of a row, as the bar chart measures its bars
+                let ticks = height.min(max) * rows * 8 / max;
+                let x = chart_area.x + (bar * bar_width) as u16;
+                if ticks == 0 || x >= chart_area.right() {
+                    continue;
+                }
+                let y = chart_area.bottom() - 1 - ((ticks - 1) / 8) as u16;
+                let width = bar_width.min((chart_area.right() - x) as usize);
+                frame.buffer_mut().set_string(
+                    x,
+                    y,
+                    "─".repeat(width),
+                    Style::default().fg(REFERENCE_COLOR),
+                );
+            }
+        }
+
+        // Number the fundamental and harmonics above their bars
+        for (bar, number) in harmonic_bars {
+            let x = chart_area.x + (bar * bar_width) as u16;
+            if x < chart_area.right() {
+                frame.buffer_mut().set_stringn(
+                    x,
+                    chart_area.y,
+                    number.to_string(),
+                    bar_width.min((chart_area.right() - x) as usize),
+                    Style::default().fg(harmonic_color(number)),
+                );
+            }
+        }
+
+        if let Some(axis_area) = axis_area {
+            let axis_area = Rect {
+                x: chart_area.x,
+                width: chart_area.width,
+                ..axis_area
+            };
+            self.render_frequency_axis(frame, axis_area, bar_width);
+        }
+    }
+
+    /// Heights of the reference trace's bars, scaled to the current FFT size and sensitivity
+    fn reference_bars(&self) -> Option<Vec<u64>> {
+        let reference = self.reference.as_ref()?;
+        let fft_size = self.fft_data.len().saturating_sub(1) * 2;
+        Some(spectrum_to_bars(
+            &reference.magnitudes_for(fft_size),
+            self.config.bar_count,
+            self.config.sensitivity,
+        ))
+    }
+
+    /// Render the detected pitch as a note with a cents deviation meter
+    fn render_tuner(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(format!(" Tuner (A4 = {:.0} Hz) ", self.config.a4));
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let fft_size = self.fft_data.len().saturating_sub(1) * 2;
+        let Some(frequency) = detect_pitch(&self.fft_data, self.sample_rate(), fft_size) else {
+            let idle = Paragraph::new("No pitch detected")
+                .style(Style::default().fg(Color::Gray))
+                .alignment(Alignment::Center);
+            frame.render_widget(idle, inner);
+            return;
+        };
+        let note = Note::from_frequency(frequency, self.config.a4);
+
+        let color = match note.cents.abs() {
+            cents if cents < 5.0 => Color::Green,
+            cents if cents < 15.0 => Color::Yellow,
+            _ => Color::Red,
+        };
+        let reading = Line::from(vec![
+            Span::styled(
+                format!("{}{}", note.name, note.octave),
+                Style::default().fg(color).add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(format!("  {:+.1} cents  ({frequency:.1} Hz)", note.cents)),
+        ]);
+
+        // Needle over a -50..+50 cents scale, centred on the in-tune mark
+        let width = inner.width as usize;
+        let centre = width / 2;
+        let needle =
+            ((note.cents + 50.0) / 100.0 * width.saturating_sub(1) as f32).round() as usize;
+        let meter: Line = (0..width)
+            .map(|column| match column {
+                _ if column == needle => Span::styled("●", Style::default().fg(color)),
+                _ if column == centre => Span::styled("┼", Style::default().fg(Color::Gray)),
+                _ => Span::styled("─", Style::default().fg(Color::Gray)),
+            })
+            .collect();
+        let scale = format!(
+            "{:<half$}0{:>rest$}",
+            "-50",
+            "+50",
+            half = centre,
+            rest = width.saturating_sub(centre + 1)
+        );
+
+        let tuner = Paragraph::new(vec![
+            reading.alignment(Alignment::Center),
+            meter,
+            Line::styled(scale, Style::default().fg(Color::Gray)),
+        ]);
+        frame.render_widget(tuner, inner);
+    }
+
+    /// Render frequency labels under the bars
+    fn render_frequency_axis(&self, frame: &mut Frame, area: Rect, bar_width: usize) {
+        let bin_count = self.fft_data.len();
+        let ticks = frequency_ticks(
+            bin_count,
+            self.config.bar_count,
+            bar_width,
+            area.width as usize,
+            self.sample_rate(),
+            bin_count.saturating_sub(1) * 2,
+        );
+
+        let mut spans = Vec::with_capacity(ticks.len() * 2);
+        let mut column = 0;
+        for tick in ticks {
+            spans.push(Span::raw(" ".repeat(tick.position - column)));
+            column = tick.position + tick.label.len();
+            spans.push(Span::raw(tick.label));
+        }
+
+        let axis = Paragraph::new(Line::from(spans)).style(Style::default().fg(Color::Gray));
+        frame.render_widget(axis, area);
+    }
+
+    /// Render dB labels to the left of the bars
+    fn render_db_axis(&self, frame: &mut Frame, area: Rect) {
+        let mut lines = vec![Line::default(); area.height as usize];
+        for tick in db_ticks(area.height as usize, self.config.sensitivity) {
+            lines[tick.position] = Line::from(format!(
+                "{:>width$} ",
+                tick.label,
+                width = DB_AXIS_WIDTH as usize - 1
+            ));
+        }
+
+        let axis = Paragraph::new(lines).style(Style::default().fg(Color::Gray));
+        frame.render_widget(axis, area);
+    }
+
+    /// Sample rate of the active stream, or a typical rate when there is none
+    fn sample_rate(&self) -> u32 {
+        self.audio_processor
+            .as_ref()
+            .map(|processor| processor.sample_rate())
+            .unwrap_or(44100)
+    }
+
+    /// Get the primary color for bars based on the color scheme
+    fn get_bar_color(&self) -> Color {
+        use config::ColorScheme;
+        match self.config.color_scheme {
+            ColorScheme::Rainbow => Color::Magenta, // Use magenta as base for rainbow
+            ColorScheme::Blue => Color::Blue,
+            ColorScheme::Green => Color::Green,
+            ColorScheme::Red => Color::Red,
+            ColorScheme::Purple => Color::Magenta,
+            ColorScheme::Cyan => Color::Cyan,
+            ColorScheme::Yellow => Color::Yellow,
+        }
+    }
+
+    /// Render the status bar
+    fn render_status(&self, frame: &mut Frame, area: Rect) {
+        let device_name = match (&self.audio_processor, &self.preferred_device) {
+            (Some(processor), Some(preferred)) if processor.device_name() != preferred => {
+                format!(
+                    "{} (fallback, waiting for {preferred})",
+                    processor.device_name()
+                )
+            }
+            (Some(processor), _) => processor.device_name().to_string(),
+            (None, Some(preferred)) => format!("No Device (waiting for {preferred})"),
+            (None, None) => "No Device".to_string(),
+        };
+
+        let stream_errors = self
+            .stream_errors
+            .summary()
+            .map(|summary| format!(" | Stream: {summary}"))
+            .unwrap_or_default();
+
+        let analysis = self
+            .audio_processor
+            .as_ref()
+            .map(|processor| {
+                let stats = processor.analysis_stats();
+                let dropped = if stats.dropped_frames > 0 {
+                    format!(", {} dropped", stats.dropped_frames)
+                } else {
+                    String::new()
+                };
+                format!(
+                    " | Analysis: {:.0}/s, backlog {}{}",
+                    processor.analysis_rate(),
+                    stats.backlog_samples,
+                    dropped
+                )
+            })
+            .unwrap_or_default();
+
+        let bpm = self
+            .rhythm
+            .bpm
+            .map_or_else(|| "--".to_string(), |bpm| format!("{bpm:.0}"));
+        let beat = if self.on_beat() { "●" } else { "○" };
+
+        let status_text = format!(
+            "Host: {} | Device: {} | Bars: {} | FFT: {} | FPS: {} | Sensitivity: {:.1} | BPM: {bpm} {beat}{}{} | Press 'q' to quit, 'h' for help",
+            self.host.id().name(),
+            device_name,
+            self.config.bar_count,
+            self.config.fft_size,
+            1000 / self.config.refresh_rate,
+            self.config.sensitivity,
+            analysis,
+            stream_errors
+        );
+
+        let status_widget = Paragraph::new(status_text)
+            .block(Block::default().borders(Borders::ALL))
+            .style(Style::default().fg(Color::Green))
+            .alignment(Alignment::Center);
+
+        frame.render_widget(status_widget, area);
+    }
+
+    /// Render help overlay
+    fn render_help_overlay(&self, frame: &mut Frame) {
+        let help_text = "\nKeyboard Controls:\n\n\
+            h - Toggle this help\n\
+            q, Esc, Ctrl+C - Quit\n\
+            c - Change color scheme\n\
+            + / = - Increase bars\n\
+            - / _ - Decrease bars\n\
+            r - Increase refresh rate\n\
+            R - Decrease refresh rate\n\
+            f - Increase FFT size\n\
+            F - Decrease FFT size\n\
+            s - Switch audio source\n\
+            b - Switch audio host backend\n\
+            l - Toggle event log\n\
+            d - Toggle dB axis\n\
+            Left / Right, mouse - Move cursor\n\
+            x - Hide cursor\n\
+            v - Cycle spectrum / chroma view\n\
+            t - Toggle tuner\n\
+            < / > - Lower / raise tuner A4\n\
+            k - Toggle beat pulse\n\
+            e - Toggle band meters\n\
+            i - Toggle spectral features\n\
+            m - Toggle THD / SNR measurement\n\
+            a - Cycle averaging / hold mode\n\
+            A - Restart averaging\n\
+            z - Freeze reference trace\n\
+            Space - Pause / resume\n\
+            Left / Right when paused - Scrub\n\
+            , / ., Shift+Left/Right - Scrub\n\
+            w - Start / stop recording\n\
+            p - Save spectrum snapshot\n\
+            Z - Clear reference trace\n\
+            [ - Decrease sensitivity\n\
+            ] - Increase sensitivity\n\n\
+            Press any key to close help";
+
+        // Cover at least the middle of the screen, growing to fit the text where possible
+        let area = frame.area();
+        let text_width = help_text.lines().map(str::len).max().unwrap_or(0) as u16 + 2;
+        let text_height = help_text.lines().count() as u16 + 2;
+        let width = (area.width / 2).max(text_width).min(area.width);
+        let height = (area.height / 2).max(text_height).min(area.height);
+        let popup_area = Rect {
+            x: (area.width - width) / 2,
+            y: (area.height - height) / 2,
+            width,
+            height,
+        };
+
+        let help_widget = Paragraph::new(help_text)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(" Help ")
+                    .style(Style::default().bg(Color::Black).fg(Color::White)),
+            )
+            .style(Style::default().bg(Color::Black).fg(Color::White));
+
+        frame.render_widget(Clear, popup_area);
+        frame.render_widget(help_widget, popup_area);
+    }
+
+    /// Render the most recent log entry as a toast in the top-right corner of `area`
+    fn render_toast(&self, frame: &mut Frame, area: Rect) {
+        let Some(entry) = self.log.latest() else {
+            return;
+        };
+        if self.show_log || entry.age() > TOAST_DURATION {
+            return;
+        }
+
+        let width = (entry.message.chars().count() as u16 + 4)
+            .min(area.width.saturating_sub(2))
+            .min(60);
+        let inner_width = width.saturating_sub(2).max(1);
+        let lines = (entry.message.chars().count() as u16).div_ceil(inner_width);
+        let toast_area = Rect {
+            x: area.x + area.width.saturating_sub(width + 1),
+            y: area.y + 1,
+            width,
+            height: (lines + 2).min(area.height.saturating_sub(1)),
+        };
+
+        let toast = Paragraph::new(entry.message.as_str())
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!(" {} ", entry.level.name()))
+                    .border_style(Style::default().fg(level_color(entry.level))),
+            )
+            .style(Style::default().bg(Color::Black).fg(Color::White))
+            .wrap(Wrap { trim: true });
+
+        frame.render_widget(Clear, toast_area);
+        frame.render_widget(toast, toast_area);
+    }
+
+    /// Render the scrollable event log overlay
+    fn render_log_overlay(&self, frame: &mut Frame) {
+        let area = frame.area();
+        let popup_area = Rect {
+            x: area.width / 8,
+            y: area.height / 8,
+            width: area.width * 3 / 4,
+            height: area.height * 3 / 4,
+        };
+
+        // Show the newest entries at the bottom, scrolled back by `log_scroll`
+        let entries = self.log.entries();
+        let visible = popup_area.height.saturating_sub(2) as usize;
+        let end = entries.len().saturating_sub(self.log_scroll);
+        let start = end.saturating_sub(visible);
+        let lines: Vec<Line> = entries[start..end]
+            .iter()
+            .map(|entry| {
+                Line::from(vec![
+                    Span::styled(
+                        format!("{} ", format_time(entry.timestamp)),
+                        Style::default().fg(Color::Gray),
+                    ),
+                    Span::styled(
+                        format!("{:<5} ", entry.level.name()),
+                        Style::default().fg(level_color(entry.level)),
+                    ),
+                    Span::raw(entry.message.clone()),
+                ])
+            })
+            .collect();
+
+        let log_widget = Paragraph::new(lines)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!(
+                        " Event Log ({}/{}) - Up/Down/PgUp/PgDn to scroll, 'l' to close ",
+                        end,
+                        entries.len()
+                    ))
+                    .style(Style::default().bg(Color::Black).fg(Color::White)),
+            )
+            .style(Style::default().bg(Color::Black).fg(Color::White));
+
+        frame.render_widget(Clear, popup_area);
+        frame.render_widget(log_widget, popup_area);
+    }
+
+    /// Prepare bar data for the bar chart widget; frequencies are shown on a separate axis
+    fn prepare_bar_data(&self, harmonic_bars: &[(usize, usize)]) -> Vec<Bar<'static>> {
+        spectrum_to_bars(
+            self.displayed_spectrum(),
+            self.config.bar_count,
+            self.config.sensitivity,
+        )
+        .into_iter()
+        .enumerate()
+        .map(|(index, height)| {
+            let bar = Bar::default().value(height);
+            let harmonic = harmonic_bars.iter().find(|&&(bar, _)| bar == index);
+            if self.cursor == Some(index) {
+                bar.style(Style::default().fg(Color::White))
+            } else if let Some(&(_, number)) = harmonic {
+                bar.style(Style::default().fg(harmonic_color(number)))
+            } else {
+                bar
+            }
+        })
+        .collect()
+    }
+
+    /// Describe the bar under the cursor: its frequency range, level and held peak level
+    fn cursor_readout(&self) -> Option<String> {
+        let cursor = self.cursor?;
+        let bins = bar_bins(self.fft_data.len(), self.config.bar_count, cursor);
+        let fft_size = self.fft_data.len().saturating_sub(1) * 2;
+        let low = bin_frequency(bins.start as f32, self.sample_rate(), fft_size);
+        let high = bin_frequency(bins.end as f32, self.sample_rate(), fft_size);
+        let level = magnitude_to_db(bar_magnitude(
+            self.displayed_spectrum(),
+            self.config.bar_count,
+            cursor,
+        ));
+        let peak = self.cursor_peak.map_or(level, |(peak, _)| peak);
+        Some(format!(
+            " Bar {}: {low:.0}-{high:.0} Hz | {level:.1} dB | peak {peak:.1} dB ",
+            cursor + 1
+        ))
+    }
+
+    /// Track the highest level of the cursor bar, releasing it after [`PEAK_HOLD_DURATION`]
+    fn update_cursor_peak(&mut self) {
+        let Some(cursor) = self.cursor else {
+            return;
+        };
+        let level = magnitude_to_db(bar_magnitude(
+            self.displayed_spectrum(),
+            self.config.bar_count,
+            cursor,
+        ));
+        if self
+            .cursor_peak
+            .is_none_or(|(peak, since)| level >= peak || since.elapsed() >= PEAK_HOLD_DURATION)
+        {
+            self.cursor_peak = Some((level, Instant::now()));
+        }
+    }
+
+    /// Move the cursor to `bar` (clamped to the bars shown), resetting the held peak
+    fn set_cursor(&mut self, bar: usize) {
+        let bar = bar.min(self.config.bar_count - 1);
+        if self.cursor != Some(bar) {
+            self.cursor = Some(bar);
+            self.cursor_peak = None;
+            self.update_cursor_peak();
+        }
+    }
+
+    /// Move the cursor by `delta` bars, showing it in the middle if it was hidden
+    fn move_cursor(&mut self, delta: isize) {
+        let bar = match self.cursor {
+            Some(bar) => bar.saturating_add_signed(delta),
+            None => self.config.bar_count / 2,
+        };
+        self.set_cursor(bar);
+    }
+
+    /// Point the cursor at the bar under the mouse
+    fn on_mouse_event(&mut self, mouse: MouseEvent) {
+        if !matches!(
+            mouse.kind,
+            MouseEventKind::Moved | MouseEventKind::Down(_) | MouseEventKind::Drag(_)
+        ) {
+            return;
+        }
+        let Some((area, bar_width)) = self.chart_layout else {
+            return;
+        };
+        if area.contains((mouse.column, mouse.row).into()) {
+            let bar = (mouse.column - area.x) / bar_width.max(1);
+            if (bar as usize) < self.config.bar_count {
+                self.set_cursor(bar as usize);
+            }
+        }
+    }
+
+    /// Reads the crossterm events and updates the state of [`App`].
+    ///
+    /// If your application needs to perform work in between handling events, you can use the
+    /// [`event::poll`] function to check if there are any events available with a timeout.
+    fn handle_crossterm_events(&mut self) -> Result<()> {
+        match event::read()? {
+            // it's important to check KeyEventKind::Press to avoid handling key release events
+            Event::Key(key) if key.kind == KeyEventKind::Press => self.on_key_event(key),
+            Event::Mouse(mouse) => self.on_mouse_event(mouse),
+            Event::Resize(_, _) => {}
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Handles the key events and updates the state of [`App`].
+    fn on_key_event(&mut self, key: KeyEvent) {
+        // Close help if it's open
+        if self.show_help {
+            self.show_help = false;
+            return;
+        }
+
+        // The log overlay captures navigation keys while open
+        if self.show_log {
+            self.on_log_key_event(key);
+            return;
+        }
+
+        match (key.modifiers, key.code) {
+            // Quit commands
+            (_, KeyCode::Esc | KeyCode::Char('q'))
+            | (KeyModifiers::CONTROL, KeyCode::Char('c') | KeyCode::Char('C')) => self.quit(),
+
+            // Help
+            (_, KeyCode::Char('h') | KeyCode::Char('H')) => {
+                self.show_help = true;
+            }
+
+            // Event log
+            (_, KeyCode::Char('l') | KeyCode::Char('L')) => {
+                self.show_log = true;
+                self.log_scroll = 0;
+            }
+
+            // Color scheme cycling
+            (_, KeyCode::Char('c') | KeyCode::Char('C')) => {
+                self.config.next_color_scheme();
+            }
+
+            // Bar count adjustment
+            (_, KeyCode::Char('+') | KeyCode::Char('=')) => {
+                self.config.increase_bar_count();
+                if let Some(cursor) = self.cursor {
+                    self.set_cursor(cursor);
+                }
+            }
+            (_, KeyCode::Char('-') | KeyCode::Char('_')) => {
+                self.config.decrease_bar_count();
+                if let Some(cursor) = self.cursor {
+                    self.set_cursor(cursor);
+                }
+            }
+
+            // View mode
+            (_, KeyCode::Char('v') | KeyCode::Char('V')) => {
+                self.config.next_view();
+                self.chroma_history.clear();
+            }
+
+            // Tuner
+            (_, KeyCode::Char('t') | KeyCode::Char('T')) => {
+                self.config.show_tuner = !self.config.show_tuner;
+            }
+            (_, KeyCode::Char('<')) => {
+                self.config.decrease_a4();
+            }
+            (_, KeyCode::Char('>')) => {
+                self.config.increase_a4();
+            }
+
+            // Band meters
+            (_, KeyCode::Char('e') | KeyCode::Char('E')) => {
+                self.config.show_bands = !self.config.show_bands;
+            }
+
+            // Spectral features panel
+            (_, KeyCode::Char('i') | KeyCode::Char('I')) => {
+                self.config.show_features = !self.config.show_features;
+            }
+
+            // Distortion measurement
+            (_, KeyCode::Char('m') | KeyCode::Char('M')) => {
+                self.config.measure = !self.config.measure;
+                self.apply_window();
+                self.update_distortion();
+            }
+
+            // Beat pulse
+            (_, KeyCode::Char('k') | KeyCode::Char('K')) => {
+                self.config.beat_pulse = !self.config.beat_pulse;
+            }
+
+            // Averaging and reference trace
+            (_, KeyCode::Char('a')) => {
+                self.config.next_averaging();
+                self.averager.set_mode(self.config.averaging);
+            }
+            (_, KeyCode::Char('A')) => self.averager.reset(),
+            (_, KeyCode::Char('z')) => {
+                self.freeze_reference();
+                if let Some(reference) = &self.reference
+                    && let Some(path) = &self.reference_path
+                    && let Err(e) = reference.save_to(path)
+                {
+                    self.log
+                        .warn(format!("Failed to save reference trace: {e}"));
+                }
+            }
+            (_, KeyCode::Char('Z')) => {
+                self.reference = None;
+                if let Some(path) = &self.reference_path
+                    && let Err(e) = Reference::delete_from(path)
+                {
+                    self.log
+                        .warn(format!("Failed to delete saved reference trace: {e}"));
+                }
+            }
+
+            // Recording
+            (_, KeyCode::Char('w') | KeyCode::Char('W')) => self.toggle_recording(),
+            (_, KeyCode::Char('p') | KeyCode::Char('P')) => self.save_snapshot(),
+
+            // Pause and history
+            (_, KeyCode::Char(' ')) => self.toggle_pause(),
+            (_, KeyCode::Char(',')) | (KeyModifiers::SHIFT, KeyCode::Left) => {
+                self.step_history(-1);
+            }
+            (_, KeyCode::Char('.')) | (KeyModifiers::SHIFT, KeyCode::Right) => {
+                self.step_history(1);
+            }
+            // The arrows scrub while paused; the mouse still moves the cursor
+            (_, KeyCode::Left) if self.paused.is_some() => self.step_history(-1),
+            (_, KeyCode::Right) if self.paused.is_some() => self.step_history(1),
+
+            // Cursor
+            (_, KeyCode::Left) => self.move_cursor(-1),
+            (_, KeyCode::Right) => self.move_cursor(1),
+            (_, KeyCode::Char('x') | KeyCode::Char('X')) => {
+                self.cursor = None;
+                self.cursor_peak = None;
+            }
+
+            // Refresh rate adjustment
+            (_, KeyCode::Char('r')) => {
+                self.config.increase_refresh_rate();
+            }
+            (_, KeyCode::Char('R')) => {
+                self.config.decrease_refresh_rate();
+            }
+
+            // FFT size adjustment
+            (_, KeyCode::Char('f')) => {
+                self.config.increase_fft_size();
+                self.apply_fft_size();
+            }
+            (_, KeyCode::Char('F')) => {
+                self.config.decrease_fft_size();
+                self.apply_fft_size();
+            }
+
+            // dB axis
+            (_, KeyCode::Char('d') | KeyCode::Char('D')) => {
+                self.config.show_db_axis = !self.config.show_db_axis;
+            }
+
+            // Sensitivity adjustment
+            (_, KeyCode::Char('[')) => {
+                self.config.decrease_sensitivity();
+            }
+            (_, KeyCode::Char(']')) => {
+                self.config.increase_sensitivity();
+            }
+
+            // Audio source switching
+            (_, KeyCode::Char('s') | KeyCode::Char('S')) => {
+                self.switch_audio_source();
+            }
+
+            // Audio host switching
+            (_, KeyCode::Char('b') | KeyCode::Char('B')) => {
+                self.switch_audio_host();
+            }
+
+            _ => {}
+        }
+    }
+
+    /// Handles key events while the log overlay is open
+    fn on_log_key_event(&mut self, key: KeyEvent) {
+        let max_scroll = self.log.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Up => self.log_scroll = (self.log_scroll + 1).min(max_scroll),
+            KeyCode::Down => self.log_scroll = self.log_scroll.saturating_sub(1),
+            KeyCode::PageUp => self.log_scroll = (self.log_scroll + 10).min(max_scroll),
+            KeyCode::PageDown => self.log_scroll = self.log_scroll.saturating_sub(10),
+            KeyCode::Home => self.log_scroll = max_scroll,
+            KeyCode::End => self.log_scroll = 0,
+            KeyCode::Esc | KeyCode::Char('l') | KeyCode::Char('L') => self.show_log = false,
+            KeyCode::Char('c') | KeyCode::Char('C') if key.modifiers == KeyModifiers::CONTROL => {
+                self.quit()
+            }
+            _ => {}
+        }
+    }
+
+    /// Pass the configured FFT size on to the running analysis
+    fn apply_fft_size(&mut self) {
+        if let Some(processor) = &self.audio_processor {
+            processor.set_fft_size(self.config.fft_size);
+        }
+    }
+
+    /// Apply the configured analysis window to the audio processor
+    fn apply_window(&mut self) {
+        if let Some(processor) = &self.audio_processor {
+            processor.set_window(self.config.window());
+        }
+    }
+
+    /// Open `device`, or the host's default input device, with the current analysis settings
+    fn open_processor(&self, device: Option<cpal::Device>) -> anyhow::Result<AudioProcessor> {
+        let processor = AudioProcessor::new(&self.host, device, self.config.fft_size)?;
+        processor.set_window(self.config.window());
+        Ok(processor)
+    }
+
+    /// Switch to the next available audio source, opened in the background
+    fn switch_audio_source(&mut self) {
+        if self.available_devices.is_empty() {
+            self.log.warn("No audio devices available to switch to.");
+            return;
+        }
+
+        let previous = self
+            .available_devices
+            .get(self.current_device_index)
+            .cloned();
+        self.current_device_index = (self.current_device_index + 1) % self.available_devices.len();
+        let device = self.available_devices[self.current_device_index].clone();
+        self.preferred_device = Some(device.0.clone());
+        self.reconnect_backoff.reset();
+
+        // Drop the old audio processor first, as some devices only open once at a time
+        self.audio_processor = None;
+        self.request_open(Some(device), OpenPurpose::Switch { previous });
+    }
+
+    /// Switch to the next available audio host backend and remember the choice. Its devices are
+    /// scanned and its default device opened in the background.
+    fn switch_audio_host(&mut self) {
+        let hosts = cpal::available_hosts();
+        if hosts.len() < 2 {
+            self.log.warn(format!(
+                "{} is the only available audio host.",
+                self.host.id().name()
+            ));
+            return;
+        }
+
+        let current = hosts.iter().position(|id| *id == self.host.id());
+        let next_id = hosts[current.map_or(0, |index| (index + 1) % hosts.len())];
+        let host = match cpal::host_from_id(next_id) {
+            Ok(host) => host,
+            Err(e) => {
+                self.log
+                    .error(format!("Failed to open audio host {}: {e}", next_id.name()));
+                return;
+            }
+        };
+
+        // Drop the old audio processor before opening a device on the new host
+        self.audio_processor = None;
+        self.host = host;
+        self.available_devices.clear();
+        self.current_device_index = 0;
+        self.reconnect_backoff.reset();
+        // Results still pending from the old host are dropped with its worker
+        self.devices = match DeviceWorker::new(next_id) {
+            Ok(devices) => Some(devices),
+            Err(e) => {
+                self.log
+                    .warn(format!("Failed to start watching for audio devices: {e}"));
+                None
+            }
+        };
+        self.scan_pending = false;
+        self.pending_open = None;
+
+        self.request_scan();
+        self.request_open(None, OpenPurpose::HostSwitch);
+        if let Some(devices) = &self.devices {
+            devices.remember_host();
+        }
+    }
+
+    /// Count the events reported by the audio stream and apply the configured error policy
+    fn handle_stream_events(&mut self) {
+        let Some(processor) = self.audio_processor.as_mut() else {
+            return;
+        };
+        let events = processor.poll_events();
+        let device_name = processor.device_name().to_string();
+
+        let mut restart = false;
+        for event in events {
+            self.stream_errors.record(&event);
+
+            // The stream runs on without analysis, so rebuild it whatever the policy, or while
+            // restarts are backing off, drop it and let reconnection reopen the device
+            if event == StreamEvent::AnalysisStopped {
+                self.log
+                    .error(format!("Audio analysis for '{device_name}' stopped."));
+                if self.restart_allowed(Instant::now()) {
+                    self.restart_stream();
+                } else {
+                    self.audio_processor = None;
+                    self.request_scan();
+                }
+                return;
+            }
+
+            if event == StreamEvent::DeviceLost {
+                // The stream is dead; drop it and rescan right away
+                self.audio_processor = None;
+                self.log
+                    .warn(format!("Audio device '{device_name}' was disconnected."));
+                self.request_scan();
+                return;
+            }
+
+            if self.config.stream_error_policy == StreamErrorPolicy::Ignore {
+                continue;
+            }
+
+            // Frequent problems are summarised rather than logged every tick
+            let rate_limited = matches!(event, StreamEvent::Xrun(_) | StreamEvent::Overflow(_));
+            if !rate_limited
+                || self
+                    .last_stream_warning
+                    .is_none_or(|last| last.elapsed() >= STREAM_WARNING_INTERVAL)
+            {
+                self.log
+                    .warn(format!("Audio stream on '{device_name}': {event}"));
+                if rate_limited {
+                    self.last_stream_warning = Some(Instant::now());
+                }
+            }
+
+            // Xruns, overflows and stalls are only counted: rebuilding the stream does not stop
+            // a device from xrunning, nor help when the analysis side is the bottleneck
+            restart |= self.config.stream_error_policy == StreamErrorPolicy::Restart
+                && matches!(event, StreamEvent::BackendError(_));
+        }
+
+        if restart && self.restart_allowed(Instant::now()) {
+            self.restart_stream();
+        }
+    }
+
+    /// Whether the stream may be restarted at `now`, counting the restart if so
+    fn restart_allowed(&mut self, now: Instant) -> bool {
+        if self
+            .last_restart
+            .is_some_and(|last| now.duration_since(last) >= MAX_RESTART_INTERVAL)
+        {
+            self.restart_backoff.reset();
+        }
+        if !self.restart_backoff.ready(now) {
+            return false;
+        }
+        self.restart_backoff.failed(now);
+        self.last_restart = Some(now);
+        true
+    }
+
+    /// Rebuild the audio stream on the device currently in use, reopening it in the background
+    fn restart_stream(&mut self) {
+        let Some(name) = self
+            .audio_processor
+            .take()
+            .map(|processor| processor.device_name().to_string())
+        else {
+            return;
+        };
+
+        let device = self
+            .device_index(&name)
+            .map(|index| self.available_devices[index].clone());
+        self.request_open(device, OpenPurpose::Restart);
+    }
+
+    /// Refresh the device list periodically to pick up hot-plugged devices, and act on the
+    /// devices scanned and opened in the background
+    fn update_devices(&mut self) {
+        if !self.scan_pending && self.last_device_scan.elapsed() >= DEVICE_SCAN_INTERVAL {
+            self.request_scan();
+        }
+
+        let updates = self
+            .devices
+            .as_mut()
+            .map(DeviceWorker::poll)
+            .unwrap_or_default();
+        for update in updates {
+            self.on_device_update(update);
+        }
+    }
+
+    /// Act on a result from the device worker
+    fn on_device_update(&mut self, update: DeviceUpdate) {
+        match update {
+            DeviceUpdate::Devices(devices) => {
+                self.scan_pending = false;
+                self.on_devices_scanned(devices);
+            }
+            DeviceUpdate::Opened { id, device, result } => {
+                // Results of superseded requests are dropped, closing any device they opened
+                if let Some((_, purpose)) = self.pending_open.take_if(|(pending, _)| *pending == id)
+                {
+                    self.on_device_opened(purpose, device, result);
+                }
+            }
+            DeviceUpdate::HostRemembered(Err(e)) => {
+                self.log.warn(format!("Failed to save settings: {e}"));
+            }
+            DeviceUpdate::HostRemembered(Ok(())) => {}
+            DeviceUpdate::Stopped => {
+                // Requests in flight died with the thread; hot-plugging resumes with a new one
+                self.log
+                    .error("The audio device thread stopped; starting a new one.");
+                self.devices = match DeviceWorker::new(self.host.id()) {
+                    Ok(devices) => Some(devices),
+                    Err(e) => {
+                        self.log
+                            .warn(format!("Failed to start watching for audio devices: {e}"));
+                        None
+                    }
+                };
+                self.scan_pending = false;
+                self.pending_open = None;
+                self.request_scan();
+            }
+        }
+    }
+
+    /// Ask the device worker for a fresh device list
+    fn request_scan(&mut self) {
+        self.last_device_scan = Instant::now();
+        if let Some(devices) = &self.devices {
+            devices.scan();
+            self.scan_pending = true;
+        }
+    }
+
+    /// Ask the device worker to open `device`, or the default device, with the current analysis
+    /// settings, superseding any open request still pending
+    fn request_open(&mut self, device: Option<(String, cpal::Device)>, purpose: OpenPurpose) {
+        if let Some(devices) = &self.devices {
+            let id = self.next_open_id;
+            self.next_open_id += 1;
+            devices.open(id, device, self.config.fft_size, self.config.window());
+            self.pending_open = Some((id, purpose));
+        }
+    }
+
+    /// Take a new device list, falling back to the default device when the preferred one is
+    /// missing and returning to it when it reappears.
+    fn on_devices_scanned(&mut self, devices: Vec<(String, cpal::Device)>) {
+        self.available_devices = devices;
+
+        let active_name = self
+            .audio_processor
+            .as_ref()
+            .map(|processor| processor.device_name().to_string());
+        if let Some(index) = active_name
+            .as_ref()
+            .and_then(|name| self.device_index(name))
+        {
+            self.current_device_index = index;
+        }
+        if self.pending_open.is_some() {
+            return;
+        }
+
+        // Return to the preferred device as soon as it is available again
+        if let Some(preferred) = &self.preferred_device
+            && active_name.as_ref() != Some(preferred)
+        {
+            match self.device_index(preferred) {
+                Some(index) if self.reconnect_backoff.ready(Instant::now()) => {
+                    let device = self.available_devices[index].clone();
+                    self.request_open(Some(device), OpenPurpose::Reconnect);
+                    return;
+                }
+                Some(_) => {}
+                // Try again as soon as it is plugged back in
+                None => self.reconnect_backoff.reset(),
+            }
+        }
+
+        // Otherwise keep audio flowing from the default device
+        if self.audio_processor.is_none() {
+            self.request_open(None, OpenPurpose::Fallback);
+        }
+    }
+
+    /// Take a device opened in the background for `purpose`: the device named `device`, or the
+    /// default device if `None`
+    fn on_device_opened(
+        &mut self,
+        purpose: OpenPurpose,
+        device: Option<String>,
+        result: anyhow::Result<Box<AudioProcessor>>,
+    ) {
+        let name = device.unwrap_or_else(|| "default device".to_string());
+        match (purpose, result) {
+            (OpenPurpose::Reconnect, Ok(processor)) => {
+                // The user may have picked another device in the meantime
+                if self.preferred_device.as_ref() == Some(&name) {
+                    self.reconnect_backoff.reset();
+                    self.install_processor(*processor);
+                    self.log
+                        .info(format!("Reconnected to audio device: {name}"));
+                }
+            }
+            (OpenPurpose::Reconnect, Err(e)) => {
+                let retry = self.reconnect_backoff.failed(Instant::now());
+                self.log.warn(format!(
+                    "Failed to reconnect to '{name}': {e}. Retrying in {} s.",
+                    retry.as_secs()
+                ));
+                if self.audio_processor.is_none() {
+                    self.request_open(None, OpenPurpose::Fallback);
+                }
+            }
+            (OpenPurpose::Fallback, Ok(processor)) => {
+                if self.audio_processor.is_none() {
+                    self.log.warn(format!(
+                        "Falling back to audio device: {}",
+                        processor.device_name()
+                    ));
+                    self.install_processor(*processor);
+                }
+            }
+            (OpenPurpose::Fallback, Err(_)) => {
+                self.fft_data.fill(0.0);
+                self.update_distortion();
+            }
+            (OpenPurpose::Switch { .. }, Ok(processor)) => {
+                self.install_processor(*processor);
+                self.log.info(format!("Switched to audio device: {name}"));
+            }
+            (OpenPurpose::Switch { previous }, Err(e)) => {
+                self.log.error(format!(
+                    "Failed to switch to device '{name}': {e}. Trying to restart with previous device."
+                ));
+                self.preferred_device = previous.as_ref().map(|(name, _)| name.clone());
+                if let Some(index) = previous
+                    .as_ref()
+                    .and_then(|(name, _)| self.device_index(name))
+                {
+                    self.current_device_index = index;
+                }
+                if previous.is_some() {
+                    self.request_open(previous, OpenPurpose::Restore);
+                }
+            }
+            (OpenPurpose::Restore, Ok(processor)) => {
+                self.install_processor(*processor);
+                self.log.info("Restored previous audio device.");
+            }
+            (OpenPurpose::Restore, Err(_)) => {
+                self.log
+                    .error("Could not restore previous audio device. Audio may not be available.");
+            }
+            (OpenPurpose::HostSwitch, Ok(processor)) => {
+                self.preferred_device = Some(processor.device_name().to_string());
+                self.log.info(format!(
+                    "Switched to audio host {} ({})",
+                    self.host.id().name(),
+                    processor.device_name()
+                ));
+                self.install_processor(*processor);
+            }
+            (OpenPurpose::HostSwitch, Err(e)) => {
+                self.preferred_device = None;
+                self.log.warn(format!(
+                    "Switched to audio host {}, but no input is available: {e}",
+                    self.host.id().name()
+                ));
+            }
+            (OpenPurpose::Restart, Ok(processor)) => {
+                self.log.info(format!(
+                    "Restarted audio stream on '{}'",
+                    processor.device_name()
+                ));
+                self.install_processor(*processor);
+            }
+            (OpenPurpose::Restart, Err(e)) => {
+                // Leave recovery to the next device scan
+                self.log
+                    .error(format!("Failed to restart audio stream on '{name}': {e}"));
+            }
+        }
+    }
+
+    /// Start showing the audio from `processor`, opened in the background
+    fn install_processor(&mut self, processor: AudioProcessor) {
+        if let Some(index) = self.device_index(processor.device_name()) {
+            self.current_device_index = index;
+        }
+        // The settings may have changed while the device was being opened
+        processor.set_fft_size(self.config.fft_size);
+        processor.set_window(self.config.window());
+        self.audio_processor = Some(processor);
+    }
+
+    /// Find a device in the available device list by name
+    fn device_index(&self, name: &str) -> Option<usize> {
+        self.available_devices.iter().position(|(n, _)| n == name)
+    }
+
+    /// Set running to false to quit the application.
+    fn quit(&mut self) {
+        self.running = false;
+    }
+}
+
+/// Marker color of the fundamental (number 1) or a harmonic of a measured tone
+fn harmonic_color(number: usize) -> Color {
+    if number == 1 {
+        Color::Green
+    } else {
+        Color::Yellow
+    }
+}
+
+/// Get the display color for a log level
+fn level_color(level: Level) -> Color {
+    match level {
+        Level::Info => Color::Cyan,
+        Level::Warn => Color::Yellow,
+        Level::Error => Color::Red,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::{Analyzer, Spectrum, Window};
+    use crate::bands::Band;
+    use crate::plot::ImageFormat;
+    use ratatui::{Terminal, backend::TestBackend, buffer::Buffer};
+
+    fn test_app() -> App {
+        App::without_audio(Config::default(), cpal::default_host(), EventLog::new())
+    }
+
+    fn press(app: &mut App, code: KeyCode) {
+        app.on_key_event(KeyEvent::new(code, KeyModifiers::NONE));
+    }
+
+    /// Render into a `width` x `height` test terminal and return the symbols of each row
+    fn render_lines(width: u16, height: u16, render: impl FnOnce(&mut Frame)) -> Vec<String> {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal.draw(render).unwrap();
+        buffer_lines(terminal.backend().buffer())
+    }
+
+    fn buffer_lines(buffer: &Buffer) -> Vec<String> {
+        (0..buffer.area.height)
+            .map(|y| {
+                (0..buffer.area.width)
+                    .map(|x| buffer[(x, y)].symbol())
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Spectrum of 16 bins rising by a factor of e per bin up to a magnitude of 1
+    fn rising_spectrum() -> Vec<f32> {
+        (0..16).map(|i| (i as f32 - 15.0).exp()).collect()
+    }
+
+    #[test]
+    fn render_title_snapshot() {
+        let app = test_app();
+        let lines = render_lines(40, 3, |frame| app.render_title(frame, frame.area()));
+        assert_eq!(
+            lines,
+            [
+                "┌──────────────────────────────────────┐",
+                "│ Audio Visualizer - Press 'h' for help│",
+                "└──────────────────────────────────────┘",
+            ]
+        );
+    }
+
+    #[test]
+    fn render_visualizer_snapshot() {
+        let mut app = test_app();
+        app.config.bar_count = 8;
+        app.set_spectrum(&rising_spectrum());
+
+        let lines = render_lines(40, 12, |frame| app.render_visualizer(frame, frame.area()));
+        assert_eq!(
+            lines,
+            [
+                "┌Frequency Spectrum (44100Hz) - 8 bars ┐",
+                "│                            ████      │",
+                "│                        ▁▁▁▁████      │",
+                "│                        ████████      │",
+                "│                    ▂▂▂▂████████      │",
+                "│                    ████████████      │",
+                "│                ▃▃▃▃████████████      │",
+                "│                ████████████████      │",
+                "│            ▄▄▄▄████████████████      │",
+                "│            █16██36██56██76██96█      │",
+                "│     5k     10k           20k         │",
+                "└──────────────────────────────────────┘",
+            ]
+        );
+    }
+
+    #[test]
+    fn render_visualizer_with_db_axis_snapshot() {
+        let mut app = test_app();
+        app.config.bar_count = 8;
+        app.config.show_db_axis = true;
+        app.set_spectrum(&rising_spectrum());
+
+        let lines = render_lines(45, 12, |frame| app.render_visualizer(frame, frame.area()));
+        assert_eq!(
+            lines,
+            [
+                "┌Frequency Spectrum (44100Hz) - 8 bars - Rai┐",
+                "│   0                             ▅▅▅▅      │",
+                "│                                 ████      │",
+                "│ -20                         ▆▆▆▆████      │",
+                "│                             ████████      │",
+                "│ -40                     ████████████      │",
+                "│                     ▁▁▁▁████████████      │",
+                "│ -60                 ████████████████      │",
+                "│                 ▃▃▃▃████████████████      │",
+                "│ -80             █16██36██56██76██96█      │",
+                "│          5k     10k           20k         │",
+                "└───────────────────────────────────────────┘",
+            ]
+        );
+    }
+
+    #[test]
+    fn render_visualizer_uses_scheme_color() {
+        let mut app = test_app();
+        app.config.bar_count = 8;
+        app.config.color_scheme = config::ColorScheme::Green;
+        app.set_spectrum(&rising_spectrum());
+
+        let mut terminal = Terminal::new(TestBackend::new(40, 12)).unwrap();
+        terminal
+            .draw(|frame| app.render_visualizer(frame, frame.area()))
+            .unwrap();
+        assert_eq!(terminal.backend().buffer()[(30, 1)].fg, Color::Green);
+    }
+
+    #[test]
+    fn render_visualizer_cursor_readout() {
+        let mut app = test_app();
+        app.config.bar_count = 8;
+        app.set_spectrum(&rising_spectrum());
+        press(&mut app, KeyCode::Right);
+        press(&mut app, KeyCode::Right);
+        assert_eq!(app.cursor, Some(5));
+
+        let mut terminal = Terminal::new(TestBackend::new(60, 12)).unwrap();
+        terminal
+            .draw(|frame| app.render_visualizer(frame, frame.area()))
+            .unwrap();
+        let buffer = terminal.backend().buffer();
+        assert_eq!(
+            buffer_lines(buffer)[11],
+            "└ Bar 6: 14700-17640 Hz | -38.0 dB | peak -38.0 dB ────────┘"
+        );
+        // The highlighted bar stands out from the others
+        assert_eq!(buffer[(1 + 7 * 5, 5)].fg, Color::White);
+        assert_eq!(buffer[(1 + 7 * 6, 5)].fg, Color::Magenta);
+    }
+
+    #[test]
+    fn max_hold_keeps_the_loudest_spectrum() {
+        let mut app = test_app();
+        app.config.bar_count = 8;
+        for _ in 0..3 {
+            press(&mut app, KeyCode::Char('a'));
+        }
+        assert_eq!(app.config.averaging, AveragingMode::MaxHold);
+
+        let loud = rising_spectrum();
+        let quiet: Vec<f32> = loud.iter().map(|magnitude| magnitude * 0.5).collect();
+        app.set_spectrum(&loud);
+        app.set_spectrum(&quiet);
+        assert_eq!(app.displayed_spectrum(), loud);
+        let lines = render_lines(80, 3, |frame| app.render_visualizer(frame, frame.area()));
+        assert!(lines[0].contains("- max hold"), "{}", lines[0]);
+
+        // Restarting lets the quieter spectrum through
+        press(&mut app, KeyCode::Char('A'));
+        app.set_spectrum(&quiet);
+        assert_eq!(app.displayed_spectrum(), quiet);
+
+        // Switching averaging off shows the latest spectrum again
+        press(&mut app, KeyCode::Char('a'));
+        press(&mut app, KeyCode::Char('a'));
+        assert_eq!(app.config.averaging, AveragingMode::Off);
+        app.set_spectrum(&loud);
+        assert_eq!(app.displayed_spectrum(), loud);
+    }
+
+    #[test]
+    fn pause_freezes_the_display_and_steps_through_history() {
+        let mut app = test_app();
+        let frames: Vec<Vec<f32>> = (1..=4).map(|i| vec![i as f32; 16]).collect();
+        app.set_spectrum(&frames[0]);
+        app.set_spectrum(&frames[1]);
+        press(&mut app, KeyCode::Char(' '));
+        let lines = render_lines(60, 3, |frame| app.render_title(frame, frame.area()));
+        assert!(lines[1].contains("[PAUSED -0.00 s]"), "{}", lines[1]);
+
+        // Capture continues behind the frozen display
+        app.set_spectrum(&frames[2]);
+        app.set_spectrum(&frames[3]);
+        assert_eq!(app.displayed_spectrum(), frames[1]);
+
+        press(&mut app, KeyCode::Char(','));
+        assert_eq!(app.displayed_spectrum(), frames[0]);
+        press(&mut app, KeyCode::Char(','));
+        assert_eq!(app.displayed_spectrum(), frames[0]);
+        app.on_key_event(KeyEvent::new(KeyCode::Right, KeyModifiers::SHIFT));
+        press(&mut app, KeyCode::Char('.'));
+        assert_eq!(app.displayed_spectrum(), frames[2]);
+
+        // The plain arrows scrub too while paused, rather than moving the cursor
+        press(&mut app, KeyCode::Left);
+        assert_eq!(app.displayed_spectrum(), frames[1]);
+        press(&mut app, KeyCode::Right);
+        press(&mut app, KeyCode::Right);
+        assert_eq!(app.displayed_spectrum(), frames[3]);
+        assert_eq!(app.cursor, None);
+
+        // Resuming jumps to the newest spectrum
+        press(&mut app, KeyCode::Char(' '));
+        assert_eq!(app.displayed_spectrum(), frames[3]);
+        let lines = render_lines(60, 3, |frame| app.render_title(frame, frame.area()));
+        assert!(!lines[1].contains("PAUSED"), "{}", lines[1]);
+    }
+
+    #[test]
+    fn record_key_writes_a_wav_file() {
+        let mut app = test_app();
+        press(&mut app, KeyCode::Char('w'));
+        assert_eq!(app.log.latest().unwrap().level, Level::Warn);
+
+        let dir = std::env::temp_dir().join(format!(
+            "audio-visualizer-test-{}-recordings",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        app.config.record_dir = dir.clone();
+        app.recorder = Some(Recorder::new(8, 1, Duration::from_millis(500)));
+        app.recorder.as_mut().unwrap().push(&[0.5; 8]).unwrap();
+
+        press(&mut app, KeyCode::Char('w'));
+        let lines = render_lines(60, 3, |frame| app.render_title(frame, frame.area()));
+        assert!(lines[1].contains("[REC 0.5 s]"), "{}", lines[1]);
+        press(&mut app, KeyCode::Char('w'));
+        assert!(
+            app.log
+                .latest()
+                .unwrap()
+                .message
+                .starts_with("Saved 0.5 s recording")
+        );
+
+        let files: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(files.len(), 1);
+        assert!(files[0].starts_with("recording-") && files[0].ends_with(".wav"));
+    }
+
+    #[test]
+    fn audio_dropped_while_recording_is_reported() {
+        let mut app = test_app();
+        app.recorder = Some(Recorder::new(8, 2, Duration::ZERO));
+
+        // Nothing is lost from a recording that is not running
+        app.report_dropped_capture(16);
+        assert!(app.log.latest().is_none());
+
+        let path = std::env::temp_dir().join(format!(
+            "audio-visualizer-test-{}-dropped-capture.wav",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        app.recorder.as_mut().unwrap().start(&path).unwrap();
+        app.report_dropped_capture(0);
+        assert!(app.log.latest().is_none());
+        app.report_dropped_capture(16);
+        app.recorder.as_mut().unwrap().stop().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let entry = app.log.latest().unwrap();
+        assert_eq!(entry.level, Level::Warn);
+        assert_eq!(
+            entry.message,
+            "Recording lost 1.00 s of audio while the app was busy"
+        );
+    }
+
+    #[test]
+    fn snapshot_key_saves_an_image() {
+        let mut app = test_app();
+        app.set_spectrum(&rising_spectrum());
+        let dir = std::env::temp_dir().join(format!(
+            "audio-visualizer-test-{}-snapshots",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        app.config.record_dir = dir.clone();
+        app.config.snapshot_format = ImageFormat::Svg;
+
+        press(&mut app, KeyCode::Char('p'));
+        let files: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(files.len(), 1, "{files:?}");
+        let svg = std::fs::read_to_string(&files[0]).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(
+            app.log
+                .latest()
+                .unwrap()
+                .message
+                .starts_with("Saved spectrum snapshot to")
+        );
+        assert!(svg.contains("Frequency Spectrum (44100 Hz) - 32 bars"));
+    }
+
+    #[test]
+    fn stream_restarts_back_off() {
+        let mut app = test_app();
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        assert!(app.restart_allowed(at(0)));
+        assert!(!app.restart_allowed(at(500)));
+        assert!(app.restart_allowed(at(1_000)));
+        assert!(!app.restart_allowed(at(2_500)));
+        assert!(app.restart_allowed(at(3_000)));
+
+        // A stream that kept running long enough may restart quickly again
+        assert!(app.restart_allowed(at(63_000)));
+        assert!(app.restart_allowed(at(64_000)));
+    }
+
+    #[test]
+    fn superseded_device_opens_are_ignored() {
+        let mut app = test_app();
+        app.pending_open = Some((1, OpenPurpose::Restart));
+        let failed = |id| DeviceUpdate::Opened {
+            id,
+            device: Some("Mic".to_string()),
+            result: Err(anyhow::anyhow!("busy")),
+        };
+
+        app.on_device_update(failed(0));
+        assert!(app.pending_open.is_some());
+        assert!(app.log.latest().is_none());
+
+        app.on_device_update(failed(1));
+        assert!(app.pending_open.is_none());
+        let entry = app.log.latest().unwrap();
+        assert_eq!(entry.level, Level::Error);
+        assert_eq!(
+            entry.message,
+            "Failed to restart audio stream on 'Mic': busy"
+        );
+    }
+
+    #[test]
+    fn a_stopped_device_thread_is_replaced() {
+        let mut app = test_app();
+        app.pending_open = Some((0, OpenPurpose::Fallback));
+        app.on_device_update(DeviceUpdate::Stopped);
+        assert_eq!(app.log.latest().unwrap().level, Level::Error);
+        assert!(app.devices.is_some());
+        assert!(app.pending_open.is_none());
+        assert!(app.scan_pending);
+    }
+
+    #[test]
+    fn reference_trace_overlays_the_bars() {
+        let mut app = test_app();
+        app.config.bar_count = 8;
+        app.set_spectrum(&rising_spectrum());
+        app.freeze_reference();
+        let quiet: Vec<f32> = rising_spectrum()
+            .iter()
+            .map(|magnitude| magnitude * 0.2)
+            .collect();
+        app.set_spectrum(&quiet);
+
+        let lines = render_lines(40, 12, |frame| app.render_visualizer(frame, frame.area()));
+        // The trace keeps the frozen heights while the live bars fall below it
+        assert_eq!(
+            lines,
+            [
+                "┌Frequency Spectrum (44100Hz) - 8 bars ┐",
+                "│                            ────      │",
+                "│                        ────▄▄▄▄      │",
+                "│                            ████      │",
+                "│                    ────▅▅▅▅████      │",
+                "│                        ████████      │",
+                "│                ────▆▆▆▆████████      │",
+                "│                    ████████████      │",
+                "│            ────▇▇▇▇████████████      │",
+                "│                █20██40██60██80█      │",
+                "│     5k     10k           20k         │",
+                "└──────────────────────────────────────┘",
+            ]
+        );
+
+        // The trace is saved to and deleted from the app's reference path only
+        let path = std::env::temp_dir()
+            .join(format!(
+                "audio-visualizer-test-{}-reference",
+                std::process::id()
+            ))
+            .join("reference.json");
+        app.reference_path = Some(path.clone());
+        press(&mut app, KeyCode::Char('z'));
+        assert_eq!(Reference::load_from(&path).ok(), app.reference);
+        press(&mut app, KeyCode::Char('Z'));
+        assert!(app.reference.is_none());
+        assert!(!path.exists());
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn cursor_follows_keys_and_holds_peaks() {
+        let mut app = test_app();
+        app.config.bar_count = 8;
+        app.set_spectrum(&rising_spectrum());
+
+        // The cursor appears in the middle and stays within the bars
+        press(&mut app, KeyCode::Left);
+        assert_eq!(app.cursor, Some(4));
+        for _ in 0..10 {
+            press(&mut app, KeyCode::Right);
+        }
+        assert_eq!(app.cursor, Some(7));
+
+        // The peak is held while the level drops, and reset by moving the cursor
+        let peak = app.cursor_peak.unwrap().0;
+        app.set_spectrum(&[0.0; 16]);
+        assert_eq!(app.cursor_peak.unwrap().0, peak);
+        press(&mut app, KeyCode::Left);
+        assert_eq!(app.cursor_peak.unwrap().0, f32::NEG_INFINITY);
+
+        // Fewer bars pull the cursor along
+        for _ in 0..10 {
+            press(&mut app, KeyCode::Char('-'));
+        }
+        press(&mut app, KeyCode::Char('x'));
+        assert_eq!(app.cursor, None);
+    }
+
+    #[test]
+    fn mouse_hover_moves_cursor() {
+        let mut app = test_app();
+        app.config.bar_count = 8;
+        render_lines(40, 12, |frame| app.render_visualizer(frame, frame.area()));
+
+        let hover = |column, row| MouseEvent {
+            kind: MouseEventKind::Moved,
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        };
+
+        // Bars are 4 columns wide, starting inside the border
+        app.on_mouse_event(hover(1 + 4 * 3 + 2, 5));
+        assert_eq!(app.cursor, Some(3));
+
+        // Outside the chart, or right of the last bar, nothing changes
+        app.on_mouse_event(hover(0, 0));
+        app.on_mouse_event(hover(36, 5));
+        assert_eq!(app.cursor, Some(3));
+    }
+
+    /// Spectrum of a sine at `frequency` as captured at the fallback sample rate
+    fn tone_spectrum(frequency: f32, fft_size: usize) -> Vec<f32> {
+        let mut analyzer = Analyzer::new(fft_size, fft_size);
+        analyzer.advance(|samples| {
+            for (i, sample) in samples.iter_mut().enumerate() {
+                let t = i as f32 / 44100.0;
+                *sample = 0.5 * (2.0 * std::f32::consts::PI * frequency * t).sin();
+            }
+        });
+        let mut spectrum = Spectrum::new(fft_size);
+        analyzer.compute(&mut spectrum);
+        spectrum.magnitudes
+    }
+
+    #[test]
+    fn render_tuner_snapshot() {
+        let mut app = test_app();
+        app.set_spectrum(&tone_spectrum(445.0, 8192));
+
+        let lines = render_lines(41, 5, |frame| app.render_tuner(frame, frame.area()));
+        assert_eq!(
+            lines,
+            [
+                "┌ Tuner (A4 = 440 Hz) ──────────────────┐",
+                "│      A4  +19.2 cents  (444.9 Hz)      │",
+                "│───────────────────┼──────●────────────│",
+                "│-50                0                +50│",
+                "└───────────────────────────────────────┘",
+            ]
+        );
+    }
+
+    #[test]
+    fn render_bands_snapshot() {
+        let mut app = test_app();
+        app.config.bands = vec![
+            Band::new("Bass", 60.0, 250.0),
+            Band::new("Mid", 500.0, 2000.0),
+            Band::new("Treble", 4000.0, 20000.0),
+        ];
+        app.set_spectrum(&tone_spectrum(1000.0, 8192));
+
+        let lines = render_lines(32, 4, |frame| app.render_bands(frame, frame.area()));
+        assert_eq!(
+            lines,
+            [
+                "┌ Bands (dB) ──────────────────┐",
+                "│Bass -115 Mid    -6 Treb -101 │",
+                "│········· ████████· ········· │",
+                "└──────────────────────────────┘",
+            ]
+        );
+    }
+
+    #[test]
+    fn band_meters_stack_below_the_tuner() {
+        let mut app = test_app();
+        press(&mut app, KeyCode::Char('e'));
+        assert!(app.config.show_bands);
+        let lines = render_lines(60, 30, |frame| app.render(frame));
+        assert!(lines[23].starts_with("┌ Bands (dB) "), "{lines:#?}");
+
+        press(&mut app, KeyCode::Char('t'));
+        let lines = render_lines(60, 30, |frame| app.render(frame));
+        assert!(lines[18].starts_with("┌ Tuner "), "{lines:#?}");
+        assert!(lines[23].starts_with("┌ Bands (dB) "), "{lines:#?}");
+    }
+
+    #[test]
+    fn render_features_snapshot() {
+        let mut app = test_app();
+        app.set_spectrum(&tone_spectrum(1000.0, 8192));
+
+        let lines = render_lines(64, 4, |frame| app.render_features(frame, frame.area()));
+        assert_eq!(
+            lines,
+            [
+                "┌ Spectral features ───────────────────────────────────────────┐",
+                "│   Centroid: 1005 Hz | Bandwidth: 258 Hz | Rolloff: 1001 Hz   │",
+                "│         Flatness: 0.000 | Crest: 1887.1 | Flux: 0.000        │",
+                "└──────────────────────────────────────────────────────────────┘",
+            ]
+        );
+    }
+
+    /// Flat-top spectrum of a 1 kHz tone at half scale with a -40 dB second harmonic
+    fn distorted_tone_spectrum() -> Vec<f32> {
+        let mut analyzer = Analyzer::new(8192, 8192);
+        analyzer.set_window(Window::FlatTop);
+        analyzer.advance(|samples| {
+            for (i, sample) in samples.iter_mut().enumerate() {
+                let phase = 2.0 * std::f32::consts::PI * 1000.0 * i as f32 / 44100.0;
+                *sample = 0.5 * (phase.sin() + 0.01 * (2.0 * phase).sin());
+            }
+        });
+        let mut spectrum = Spectrum::new(8192);
+        analyzer.compute(&mut spectrum);
+        spectrum.magnitudes
+    }
+
+    #[test]
+    fn render_measurement_snapshot() {
+        let mut app = test_app();
+        press(&mut app, KeyCode::Char('m'));
+        assert_eq!(app.config.window(), Window::FlatTop);
+        app.set_spectrum(&distorted_tone_spectrum());
+
+        let lines = render_lines(72, 5, |frame| app.render_measurement(frame, frame.area()));
+        assert_eq!(
+            lines[..3],
+            [
+                "┌ Measurement (flat-top window) ───────────────────────────────────────┐",
+                "│                 Fundamental: 1000.0 Hz at -6.02 dBFS                 │",
+                "│      THD: 1.000% | THD+N: 1.000% | SNR: 82.6 dB | SINAD: 40.0 dB     │",
+            ]
+        );
+        // Harmonics above the second are rounding noise, so only their layout is stable
+        assert!(lines[3].starts_with("│ H2 -40.0  H3 -1"), "{}", lines[3]);
+        assert!(lines[3].ends_with("  dBc │"), "{}", lines[3]);
+    }
+
+    #[test]
+    fn measurement_marks_harmonics_on_the_spectrum() {
+        let mut app = test_app();
+        press(&mut app, KeyCode::Char('m'));
+        app.set_spectrum(&distorted_tone_spectrum());
+        app.config.bar_count = 128;
+
+        let mut terminal = Terminal::new(TestBackend::new(130, 12)).unwrap();
+        terminal
+            .draw(|frame| app.render_visualizer(frame, frame.area()))
+            .unwrap();
+        let buffer = terminal.backend().buffer();
+        // 32 bins of 5.4 Hz per bar: 1 kHz is in bar 5 and 2 kHz in bar 11
+        assert_eq!(buffer[(6, 1)].symbol(), "1");
+        assert_eq!(buffer[(6, 1)].fg, Color::Green);
+        assert_eq!(buffer[(12, 1)].symbol(), "2");
+        assert_eq!(buffer[(12, 1)].fg, Color::Yellow);
+
+        // Nothing is marked outside measurement mode
+        press(&mut app, KeyCode::Char('m'));
+        assert!(app.harmonic_bars().is_empty());
+    }
+
+    #[test]
+    fn tuner_follows_a4_reference() {
+        let mut app = test_app();
+        app.set_spectrum(&tone_spectrum(432.0, 8192));
+        press(&mut app, KeyCode::Char('t'));
+        assert!(app.config.show_tuner);
+        for _ in 0..8 {
+            press(&mut app, KeyCode::Char('<'));
+        }
+        assert_eq!(app.config.a4, 432.0);
+
+        let lines = render_lines(41, 5, |frame| app.render_tuner(frame, frame.area()));
+        assert_eq!(lines[0], "┌ Tuner (A4 = 432 Hz) ──────────────────┐");
+        assert!(lines[1].contains("A4  +0.3 cents"), "{}", lines[1]);
+    }
+
+    #[test]
+    fn tuner_without_pitch() {
+        let app = test_app();
+        let lines = render_lines(41, 5, |frame| app.render_tuner(frame, frame.area()));
+        assert_eq!(lines[1], "│           No pitch detected           │");
+    }
+
+    #[test]
+    fn render_chroma_bars_snapshot() {
+        let mut app = test_app();
+        app.set_spectrum(&tone_spectrum(440.0, 8192));
+        let lines = render_lines(38, 8, |frame| app.render_chroma_bars(frame, frame.area()));
+        assert_eq!(
+            lines,
+            [
+                "┌Chromagram (A4 = 440 Hz) - Rainbow s┐",
+                "│                           ██       │",
+                "│                           ██       │",
+                "│                           ██       │",
+                "│                           ██       │",
+                "│                           ██       │",
+                "│C  C# D  D# E  F  F# G  G# A  A# B  │",
+                "└────────────────────────────────────┘",
+            ]
+        );
+    }
+
+    #[test]
+    fn render_chroma_heatmap_snapshot() {
+        let mut app = test_app();
+        press(&mut app, KeyCode::Char('v'));
+        press(&mut app, KeyCode::Char('v'));
+        assert_eq!(app.config.view, ViewMode::ChromaHeatmap);
+        for frequency in [261.63, 261.63, 440.0, 440.0, 440.0] {
+            app.set_spectrum(&tone_spectrum(frequency, 8192));
+        }
+        let lines = render_lines(12, 14, |frame| {
+            app.render_chroma_heatmap(frame, frame.area())
+        });
+        assert_eq!(
+            lines,
+            [
+                "┌Chromagram┐",
+                "│B         │",
+                "│A#        │",
+                "│A      ███│",
+                "│G#        │",
+                "│G         │",
+                "│F#        │",
+                "│F         │",
+                "│E         │",
+                "│D#        │",
+                "│D         │",
+                "│C#        │",
+                "│C    ██   │",
+                "└──────────┘",
+            ]
+        );
+    }
+
+    #[test]
+    fn render_status_snapshot() {
+        let app = test_app();
+        let lines = render_lines(220, 3, |frame| app.render_status(frame, frame.area()));
+        assert_eq!(
+            lines[1].trim_matches(|c| c == '│' || c == ' '),
+            format!(
+                "Host: {} | Device: No Device | Bars: 32 | FFT: 1024 | FPS: 62 | Sensitivity: 1.0 | BPM: -- ○ | Press 'q' to quit, 'h' for help",
+                app.host.id().name()
+            )
+        );
+    }
+
+    #[test]
+    fn status_shows_tempo_and_beats() {
+        let mut app = test_app();
+        app.set_rhythm(Rhythm {
+            bpm: Some(121.6),
+            ..Rhythm::default()
+        });
+        let status = render_lines(220, 3, |frame| app.render_status(frame, frame.area()));
+        assert!(status[1].contains("| BPM: 122 ○ |"), "{}", status[1]);
+
+        app.set_rhythm(Rhythm {
+            beats: 1,
+            bpm: Some(121.6),
+            ..Rhythm::default()
+        });
+        let status = render_lines(220, 3, |frame| app.render_status(frame, frame.area()));
+        assert!(status[1].contains("| BPM: 122 ● |"), "{}", status[1]);
+
+        // A restarted stream counting from zero is not a beat
+        app.last_beat = None;
+        app.set_rhythm(Rhythm::default());
+        assert!(!app.on_beat());
+    }
+
+    #[test]
+    fn beat_pulse_lights_the_view_border() {
+        let mut app = test_app();
+        let border_color = |app: &mut App| {
+            let mut terminal = Terminal::new(TestBackend::new(40, 10)).unwrap();
+            terminal
+                .draw(|frame| app.render_visualizer(frame, frame.area()))
+                .unwrap();
+            terminal.backend().buffer()[(0, 0)].fg
+        };
+
+        app.set_rhythm(Rhythm {
+            beats: 1,
+            ..Rhythm::default()
+        });
+        assert_eq!(border_color(&mut app), Color::Reset);
+
+        press(&mut app, KeyCode::Char('k'));
+        assert!(app.config.beat_pulse);
+        assert_eq!(border_color(&mut app), app.get_bar_color());
+
+        app.last_beat = Some(Instant::now() - BEAT_FLASH_DURATION);
+        assert_eq!(border_color(&mut app), Color::Reset);
+    }
+
+    #[test]
+    fn render_help_overlay_snapshot() {
+        let app = test_app();
+        let lines = render_lines(64, 44, |frame| app.render_help_overlay(frame));
+        let popup: Vec<&str> = lines
+            .iter()
+            .map(String::as_str)
+            .filter(|line| !line.trim().is_empty())
+            .collect();
+        assert_eq!(
+            popup,
+            [
+                "              ┌ Help ───────────────────────────┐               ",
+                "              │                                 │               ",
+                "              │Keyboard Controls:               │               ",
+                "              │                                 │               ",
+                "              │h - Toggle this help             │               ",
+                "              │q, Esc, Ctrl+C - Quit            │               ",
+                "              │c - Change color scheme          │               ",
+                "              │+ / = - Increase bars            │               ",
+                "              │- / _ - Decrease bars            │               ",
+                "              │r - Increase refresh rate        │               ",
+                "              │R - Decrease refresh rate        │               ",
+                "              │f - Increase FFT size            │               ",
+                "              │F - Decrease FFT size            │               ",
+                "              │s - Switch audio source          │               ",
+                "              │b - Switch audio host backend    │               ",
+                "              │l - Toggle event log             │               ",
+                "              │d - Toggle dB axis               │               ",
+                "              │Left / Right, mouse - Move cursor│               ",
+                "              │x - Hide cursor                  │               ",
+                "              │v - Cycle spectrum / chroma view │               ",
+                "              │t - Toggle tuner                 │               ",
+                "              │< / > - Lower / raise tuner A4   │               ",
+                "              │k - Toggle beat pulse            │               ",
+                "              │e - Toggle band meters           │               ",
+                "              │i - Toggle spectral features     │               ",
+                "              │m - Toggle THD / SNR measurement │               ",
+                "              │a - Cycle averaging / hold mode  │               ",
+                "              │A - Restart averaging            │               ",
+                "              │z - Freeze reference trace       │               ",
+                "              │Space - Pause / resume           │               ",
+                "              │Left / Right when paused - Scrub │               ",
+                "              │, / ., Shift+Left/Right - Scrub  │               ",
+                "              │w - Start / stop recording       │               ",
+                "              │p - Save spectrum snapshot       │               ",
+                "              │Z - Clear reference trace        │               ",
+                "              │[ - Decrease sensitivity         │               ",
+                "              │] - Increase sensitivity         │               ",
+                "              │                                 │               ",
+                "              │Press any key to close help      │               ",
+                "              └─────────────────────────────────┘               ",
+            ]
+        );
+    }
+
+    #[test]
+    fn keys_adjust_config() {
+        let mut app = test_app();
+
+        press(&mut app, KeyCode::Char('+'));
+        assert_eq!(app.config.bar_count, 40);
+        press(&mut app, KeyCode::Char('-'));
+        press(&mut app, KeyCode::Char('_'));
+        assert_eq!(app.config.bar_count, 24);
+
+        press(&mut app, KeyCode::Char('r'));
+        assert_eq!(app.config.refresh_rate, 12);
+        press(&mut app, KeyCode::Char('R'));
+        press(&mut app, KeyCode::Char('R'));
+        assert_eq!(app.config.refresh_rate, 20);
+
+        press(&mut app, KeyCode::Char('f'));
+        assert_eq!(app.config.fft_size, 2048);
+        press(&mut app, KeyCode::Char('F'));
+        press(&mut app, KeyCode::Char('F'));
+        assert_eq!(app.config.fft_size, 512);
+
+        press(&mut app, KeyCode::Char(']'));
+        assert!((app.config.sensitivity - 1.2).abs() < 1e-6);
+        press(&mut app, KeyCode::Char('['));
+        press(&mut app, KeyCode::Char('['));
+        assert!((app.config.sensitivity - 1.0 / 1.2).abs() < 1e-6);
+
+        press(&mut app, KeyCode::Char('c'));
+        assert_eq!(app.config.color_scheme, config::ColorScheme::Blue);
+    }
+
+    #[test]
+    fn config_adjustments_are_clamped() {
+        let mut app = test_app();
+        for _ in 0..30 {
+            press(&mut app, KeyCode::Char('+'));
+            press(&mut app, KeyCode::Char('f'));
+            press(&mut app, KeyCode::Char('r'));
+        }
+        assert_eq!(app.config.bar_count, 128);
+        assert_eq!(app.config.fft_size, config::MAX_FFT_SIZE);
+        assert_eq!(app.config.refresh_rate, 8);
+
+        for _ in 0..30 {
+            press(&mut app, KeyCode::Char('-'));
+            press(&mut app, KeyCode::Char('F'));
+            press(&mut app, KeyCode::Char('R'));
+        }
+        assert_eq!(app.config.bar_count, 8);
+        assert_eq!(app.config.fft_size, config::MIN_FFT_SIZE);
+        assert_eq!(app.config.refresh_rate, 100);
+    }
+
+    #[test]
+    fn help_overlay_swallows_next_key() {
+        let mut app = test_app();
+        press(&mut app, KeyCode::Char('h'));
+        assert!(app.show_help);
+
+        press(&mut app, KeyCode::Char('+'));
+        assert!(!app.show_help);
+        assert_eq!(app.config.bar_count, 32);
+    }
+
+    #[test]
+    fn log_overlay_captures_keys() {
+        let mut app = test_app();
+        press(&mut app, KeyCode::Char('l'));
+        assert!(app.show_log);
+
+        press(&mut app, KeyCode::Char('+'));
+        assert_eq!(app.config.bar_count, 32);
+
+        press(&mut app, KeyCode::Char('l'));
+        assert!(!app.show_log);
+    }
+
+    #[test]
+    fn quit_keys_stop_the_app() {
+        for key in [
+            KeyEvent::new(KeyCode::Char('q'), KeyModifiers::NONE),
+            KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE),
+            KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL),
+        ] {
+            let mut app = test_app();
+            app.running = true;
+            app.on_key_event(key);
+            assert!(!app.running, "{key:?} should quit");
+        }
+    }
+
+    #[test]
+    fn switching_source_without_devices_warns() {
+        let mut app = test_app();
+        press(&mut app, KeyCode::Char('s'));
+        assert!(app.audio_processor.is_none());
+        assert_eq!(app.log.latest().unwrap().level, Level::Warn);
+    }
+}
@@ -1,47 +1,239 @@
 use anyhow::Result;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::{Device, Sample, SampleFormat, Stream, StreamConfig};
+use cpal::{Device, FromSample, SampleFormat, SizedSample, Stream, StreamConfig};
 use ringbuf::{
     HeapRb,
     traits::{Consumer, Observer, Producer, Split},
 };
-use rustfft::{FftPlanner, num_complex::Complex};
-use tokio::sync::mpsc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle, Thread};
+use std::time::{Duration, Instant};
 
-/// Audio capture and processing module
-pub struct AudioProcessor {
-    _stream: Stream,
-    fft_rx: mpsc::Receiver<Vec<f32>>,
-    sample_rate: u32,
+use crate::analysis::{Analyzer, Spectrum, Window};
+use crate::features::{SpectralFeatures, spectral_features};
+use crate::frame_queue::{FrameQueue, FrameTap, frame_queue};
+use crate::rhythm::BeatTracker;
+use crate::triple_buffer::{TripleInput, TripleOutput, triple_buffer};
+
+/// How long the stream may go without delivering data before it is reported as stalled
+const STALL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How much raw audio is buffered for [`AudioProcessor::take_captured`] before it is dropped
+const CAPTURE_BUFFER_DURATION: Duration = Duration::from_secs(2);
+
+/// How much analysed audio [`AudioProcessor::queue_frames`] holds before frames are dropped
+const FRAME_QUEUE_DURATION: Duration = Duration::from_secs(2);
+
+/// Fewest frames the frame queue holds, however large the FFT
+const MIN_FRAME_QUEUE_LENGTH: usize = 64;
+
+/// Signature shared by the per-sample-format instantiations of [`AudioProcessor::build_stream`]
+type BuildStreamFn = fn(
+    &Device,
+    &StreamConfig,
+    ringbuf::HeapProd<f32>,
+    ringbuf::HeapProd<f32>,
+    u16,
+    StreamMonitor,
+) -> Result<Stream>;
+
+/// Problems reported by the capture stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamEvent {
+    /// The capture device disappeared; the stream is dead
+    DeviceLost,
+    /// The backend skipped audio between callbacks (count of xruns)
+    Xrun(u64),
+    /// Samples were dropped because analysis could not keep up (count of samples)
+    Overflow(u64),
+    /// The stream stopped delivering data without reporting an error
+    Stalled,
+    /// Any other error reported by the audio backend
+    BackendError(String),
+    /// The analysis thread exited, e.g. after a panic; the stream runs on but no new spectra come
+    AnalysisStopped,
 }
 
-impl AudioProcessor {
-    /// Get the best available audio host, preferring non-ALSA backends to avoid timestamp issues
-    fn get_best_audio_host() -> cpal::Host {
-        // Get all available hosts and try them in order
-        let available_hosts = cpal::available_hosts();
-
-        // Try non-default hosts first (typically more stable than ALSA)
-        for host_id in available_hosts {
-            if let Ok(host) = cpal::host_from_id(host_id) {
-                // Test if the host actually works by checking for devices
-                if let Ok(mut devices) = host.input_devices() {
-                    if devices.next().is_some() {
-                        // This host has input devices, use it
-                        return host;
-                    }
-                }
-            }
+impl std::fmt::Display for StreamEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StreamEvent::DeviceLost => write!(f, "audio device lost"),
+            StreamEvent::Xrun(count) => write!(f, "{count} audio xrun(s)"),
+            StreamEvent::Overflow(samples) => write!(f, "{samples} samples dropped (overflow)"),
+            StreamEvent::Stalled => write!(f, "audio stream stalled"),
+            StreamEvent::BackendError(message) => write!(f, "audio backend error: {message}"),
+            StreamEvent::AnalysisStopped => write!(f, "audio analysis stopped"),
         }
+    }
+}
+
+/// Running totals of the problems reported by a capture stream
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct StreamErrorCounts {
+    pub device_lost: u64,
+    pub xruns: u64,
+    pub dropped_samples: u64,
+    pub stalls: u64,
+    pub backend_errors: u64,
+}
 
-        // Fallback to default host
-        cpal::default_host()
+impl StreamErrorCounts {
+    /// Add a reported event to the totals
+    pub fn record(&mut self, event: &StreamEvent) {
+        match event {
+            StreamEvent::DeviceLost => self.device_lost += 1,
+            StreamEvent::Xrun(count) => self.xruns += count,
+            StreamEvent::Overflow(samples) => self.dropped_samples += samples,
+            StreamEvent::Stalled => self.stalls += 1,
+            StreamEvent::BackendError(_) | StreamEvent::AnalysisStopped => self.backend_errors += 1,
+        }
     }
 
-    /// Create a new AudioProcessor with the specified device
-    pub fn new(device: Option<Device>) -> Result<Self> {
-        // Try different hosts in order of preference to avoid ALSA timestamp issues
-        let host = Self::get_best_audio_host();
+    /// Short summary for the status bar, or `None` if nothing has gone wrong
+    pub fn summary(&self) -> Option<String> {
+        let parts: Vec<String> = [
+            (self.xruns, "xruns"),
+            (self.dropped_samples, "dropped"),
+            (self.stalls, "stalls"),
+            (self.backend_errors, "errors"),
+            (self.device_lost, "disconnects"),
+        ]
+        .iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, label)| format!("{count} {label}"))
+        .collect();
+
+        (!parts.is_empty()).then(|| parts.join(", "))
+    }
+}
+
+/// Counters updated from the real-time data callback without allocating or blocking
+#[derive(Default)]
+struct StreamCounters {
+    callbacks: AtomicU64,
+    xruns: AtomicU64,
+    dropped_samples: AtomicU64,
+    /// Raw samples dropped because the buffer for [`AudioProcessor::take_captured`] was full
+    dropped_captured: AtomicU64,
+}
+
+/// Handles used by the stream callbacks to report problems back to the [`AudioProcessor`]
+#[derive(Clone)]
+struct StreamMonitor {
+    events: mpsc::Sender<StreamEvent>,
+    counters: Arc<StreamCounters>,
+    /// Analysis thread, woken whenever new samples have been pushed
+    analysis_thread: Thread,
+}
+
+/// Snapshot of how well the analysis keeps up with the incoming audio
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AnalysisStats {
+    /// FFT frames analysed and published
+    pub frames: u64,
+    /// FFT frames skipped because the analysis fell too far behind the input
+    pub dropped_frames: u64,
+    /// Samples waiting in the ring buffer after the last analysis pass
+    pub backlog_samples: usize,
+}
+
+/// Counters updated by the analysis thread
+#[derive(Default)]
+struct AnalysisCounters {
+    frames: AtomicU64,
+    dropped_frames: AtomicU64,
+    backlog_samples: AtomicUsize,
+}
+
+/// Capture stream kept alive on a thread of its own, since cpal streams cannot move between
+/// threads. This lets an [`AudioProcessor`] be opened in the background and handed to the UI.
+struct StreamThread {
+    /// Dropped to end the thread, which stops the stream
+    stop: Option<mpsc::Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl StreamThread {
+    /// Build a stream with `build` on a new thread and start it, returning once it is running
+    fn spawn(build: impl FnOnce() -> Result<Stream> + Send + 'static) -> Result<Self> {
+        let (started_tx, started_rx) = mpsc::channel();
+        let (stop, stop_rx) = mpsc::channel::<()>();
+        let handle = thread::Builder::new()
+            .name("audio-stream".to_string())
+            .spawn(move || {
+                let stream = build().and_then(|stream| {
+                    stream.play()?;
+                    Ok(stream)
+                });
+                let running = stream.is_ok();
+                let _ = started_tx.send(stream.map(|_| ()));
+                if running {
+                    // Blocks until the sender is dropped; the stream is dropped with `stream`
+                    let _ = stop_rx.recv();
+                }
+            })?;
+
+        let stream = Self {
+            stop: Some(stop),
+            handle: Some(handle),
+        };
+        started_rx
+            .recv()
+            .map_err(|_| anyhow::anyhow!("Audio stream thread exited unexpectedly"))?
+            .map(|()| stream)
+    }
+}
+
+impl Drop for StreamThread {
+    fn drop(&mut self) {
+        self.stop.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Audio capture and processing module
+pub struct AudioProcessor {
+    _stream: StreamThread,
+    /// Latest spectrum published by the analysis thread
+    spectrum: TripleOutput<Spectrum>,
+    analysis_thread: Option<JoinHandle<()>>,
+    analysis_running: Arc<AtomicBool>,
+    analysis_counters: Arc<AnalysisCounters>,
+    /// FFT size requested from the analysis thread
+    fft_size: Arc<AtomicUsize>,
+    /// Index in [`Window::ALL`] of the window requested from the analysis thread
+    window: Arc<AtomicUsize>,
+    /// Hands the analysis thread the producing side of `frame_queue`
+    frame_taps: mpsc::Sender<FrameTap>,
+    /// Copy of every analysed frame, once enabled by [`AudioProcessor::queue_frames`]
+    frame_queue: Option<FrameQueue>,
+    sample_rate: u32,
+    channels: u16,
+    /// Raw interleaved samples of all channels, for recording
+    captured: ringbuf::HeapCons<f32>,
+    device_name: String,
+    /// Errors forwarded from the stream error callback
+    events_rx: mpsc::Receiver<StreamEvent>,
+    counters: Arc<StreamCounters>,
+    /// Counter values already turned into events
+    reported_xruns: u64,
+    reported_dropped_samples: u64,
+    reported_dropped_captured: u64,
+    /// Callback count at the last time data was seen flowing, used to detect stalls
+    last_callbacks: u64,
+    last_activity: Instant,
+    stall_reported: bool,
+    analysis_stop_reported: bool,
+}
+
+impl AudioProcessor {
+    /// Create a new AudioProcessor with the specified device, or the host's default input device,
+    /// analysing frames of `fft_size` samples
+    pub fn new(host: &cpal::Host, device: Option<Device>, fft_size: usize) -> Result<Self> {
         let device = match device {
             Some(dev) => dev,
             None => host
@@ -49,6 +241,9 @@ impl AudioProcessor {
                 .ok_or_else(|| anyhow::anyhow!("No input device available"))?,
         };
 
+        let device_name = device
+            .name()
+            .unwrap_or_else(|_| "Unknown Device".to_string());
         let config = device.default_input_config()?;
         let sample_rate = config.sample_rate().0;
         let channels = config.channels();
@@ -56,80 +251,102 @@ impl AudioProcessor {
         // Create a ring buffer for audio samples
         let buffer_size = sample_rate as usize; // 1 second of audio
         let rb = HeapRb::<f32>::new(buffer_size);
-        let (producer, mut consumer) = rb.split();
-
-        // Create channel for FFT results
-        let (fft_tx, fft_rx) = mpsc::channel(10);
+        let (producer, consumer) = rb.split();
+        let captured_size = (CAPTURE_BUFFER_DURATION.as_secs_f32() * sample_rate as f32) as usize
+            * channels as usize;
+        let (captured_producer, captured) = HeapRb::<f32>::new(captured_size).split();
 
-        // Clone for move into stream closure
-        let fft_tx_clone = fft_tx.clone();
+        // Start the analysis thread before the stream so it is ready to be woken. Frames are
+        // produced every hop (half an FFT frame) as samples arrive, so the analysis rate depends
+        // only on the input sample rate, not on wall-clock timing.
+        let (spectrum_input, spectrum) = triple_buffer(Spectrum::new(fft_size));
+        let analysis_running = Arc::new(AtomicBool::new(true));
+        let analysis_counters = Arc::new(AnalysisCounters::default());
+        let fft_size = Arc::new(AtomicUsize::new(fft_size));
+        let window = Arc::new(AtomicUsize::new(Window::default().index()));
+        let (frame_taps, frame_taps_rx) = mpsc::channel();
+        let analysis_thread = {
+            let shared = AnalysisShared {
+                running: analysis_running.clone(),
+                counters: analysis_counters.clone(),
+                fft_size: fft_size.clone(),
+                window: window.clone(),
+                frame_taps: frame_taps_rx,
+                // Skip ahead rather than let the ring buffer overflow if analysis falls behind
+                max_backlog: buffer_size / 2,
+                sample_rate,
+            };
+            thread::Builder::new()
+                .name("audio-analysis".to_string())
+                .spawn(move || run_analysis(consumer, spectrum_input, shared))?
+        };
 
         // Build the input stream with error handling
-        let stream = match config.sample_format() {
-            SampleFormat::F32 => {
-                Self::build_stream::<f32>(&device, &config.into(), producer, channels)
-            }
-            SampleFormat::I16 => {
-                Self::build_stream::<i16>(&device, &config.into(), producer, channels)
-            }
-            SampleFormat::U16 => {
-                Self::build_stream::<u16>(&device, &config.into(), producer, channels)
-            }
-            _ => return Err(anyhow::anyhow!("Unsupported sample format")),
+        let stream_config = config.config();
+        let (events_tx, events_rx) = mpsc::channel();
+        let monitor = StreamMonitor {
+            events: events_tx,
+            counters: Arc::new(StreamCounters::default()),
+            analysis_thread: analysis_thread.thread().clone(),
         };
-
-        let stream = stream?;
-
-        // Start the stream
-        stream.play()?;
-
-        // Spawn FFT processing task
-        tokio::spawn(async move {
-            let mut fft_planner = FftPlanner::new();
-            let fft_size = 1024;
-            let fft = fft_planner.plan_fft_forward(fft_size);
-            let mut buffer = vec![Complex::new(0.0, 0.0); fft_size];
-            let mut samples = vec![0.0f32; fft_size];
-
-            loop {
-                // Collect samples from ring buffer
-                if consumer.occupied_len() >= fft_size {
-                    for sample in samples.iter_mut().take(fft_size) {
-                        *sample = consumer.try_pop().unwrap_or(0.0);
-                    }
-
-                    // Apply window function (Hann window)
-                    for (i, sample) in samples.iter_mut().enumerate() {
-                        let window = 0.5
-                            * (1.0
-                                - ((2.0 * std::f32::consts::PI * i as f32)
-                                    / (fft_size - 1) as f32)
-                                    .cos());
-                        *sample *= window;
-                        buffer[i] = Complex::new(*sample, 0.0);
-                    }
-
-                    // Perform FFT
-                    fft.process(&mut buffer);
-
-                    // Calculate magnitude spectrum (only first half due to symmetry)
-                    let magnitudes: Vec<f32> =
-                        buffer.iter().take(fft_size / 2).map(|c| c.norm()).collect();
-
-                    // Send results
-                    if fft_tx_clone.send(magnitudes).await.is_err() {
-                        break; // Receiver dropped
-                    }
-                }
-
-                tokio::time::sleep(tokio::time::Duration::from_millis(16)).await; // ~60 FPS
+        let counters = monitor.counters.clone();
+        let build_stream: BuildStreamFn = match config.sample_format() {
+            SampleFormat::I8 => Self::build_stream::<i8>,
+            SampleFormat::I16 => Self::build_stream::<i16>,
+            SampleFormat::I32 => Self::build_stream::<i32>,
+            SampleFormat::I64 => Self::build_stream::<i64>,
+            SampleFormat::U8 => Self::build_stream::<u8>,
+            SampleFormat::U16 => Self::build_stream::<u16>,
+            SampleFormat::U32 => Self::build_stream::<u32>,
+            SampleFormat::U64 => Self::build_stream::<u64>,
+            SampleFormat::F32 => Self::build_stream::<f32>,
+            SampleFormat::F64 => Self::build_stream::<f64>,
+            format => {
+                stop_analysis(&analysis_running, Some(analysis_thread));
+                return Err(anyhow::anyhow!("Unsupported sample format: {format}"));
             }
+        };
+        let stream = StreamThread::spawn(move || {
+            build_stream(
+                &device,
+                &stream_config,
+                producer,
+                captured_producer,
+                channels,
+                monitor,
+            )
         });
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                stop_analysis(&analysis_running, Some(analysis_thread));
+                return Err(e);
+            }
+        };
 
         Ok(AudioProcessor {
             _stream: stream,
-            fft_rx,
+            spectrum,
+            analysis_thread: Some(analysis_thread),
+            analysis_running,
+            analysis_counters,
+            fft_size,
+            window,
+            frame_taps,
+            frame_queue: None,
             sample_rate,
+            channels,
+            captured,
+            device_name,
+            events_rx,
+            counters,
+            reported_xruns: 0,
+            reported_dropped_samples: 0,
+            reported_dropped_captured: 0,
+            last_callbacks: 0,
+            last_activity: Instant::now(),
+            stall_reported: false,
+            analysis_stop_reported: false,
         })
     }
 
@@ -138,10 +355,13 @@ impl AudioProcessor {
         device: &Device,
         config: &StreamConfig,
         mut producer: ringbuf::HeapProd<f32>,
+        mut captured: ringbuf::HeapProd<f32>,
         channels: u16,
+        monitor: StreamMonitor,
     ) -> Result<Stream>
     where
-        T: Sample + Into<f32> + cpal::SizedSample,
+        T: SizedSample,
+        f32: FromSample<T>,
     {
         // Use a configuration that's less likely to cause ALSA timestamp issues
         let mut safe_config = config.clone();
@@ -149,27 +369,58 @@ impl AudioProcessor {
         // Use default buffer size which is usually safer
         safe_config.buffer_size = cpal::BufferSize::Default;
 
+        let sample_rate = config.sample_rate.0 as f64;
+        let counters = monitor.counters.clone();
+        let mut last_capture: Option<(cpal::StreamInstant, usize)> = None;
+
         let result = device.build_input_stream(
             &safe_config,
-            move |data: &[T], _: &cpal::InputCallbackInfo| {
-                // Convert samples to f32 and handle multi-channel by averaging
-                for chunk in data.chunks(channels as usize) {
-                    let sample = chunk.iter().map(|&s| s.into()).sum::<f32>() / channels as f32;
+            move |data: &[T], info: &cpal::InputCallbackInfo| {
+                counters.callbacks.fetch_add(1, Ordering::Relaxed);
+                let frames = data.len() / channels as usize;
 
-                    let _ = producer.try_push(sample);
-                }
-            },
-            |err| {
-                // Suppress common ALSA errors that are mostly harmless
-                let err_str = err.to_string();
-                if !err_str.contains("htstamp")
-                    && !err_str.contains("timestamp")
-                    && !err_str.contains("trigger")
-                    && !err_str.contains("spuriously returned")
-                    && !err_str.contains("poll()")
+                let capture = info.timestamp().capture;
+                if let Some((previous, previous_frames)) = last_capture
+                    && let Some(gap) = capture.duration_since(&previous)
+                    && is_xrun(gap, previous_frames, sample_rate)
                 {
-                    eprintln!("Audio stream error: {err}");
+                    counters.xruns.fetch_add(1, Ordering::Relaxed);
+                }
+                last_capture = Some((capture, frames));
+
+                // Keep the raw samples for recording; a buffer that does not fit is dropped whole
+                // so the channels stay interleaved in order
+                if captured.vacant_len() >= data.len() {
+                    captured.push_iter(data.iter().copied().map(normalize));
+                } else {
+                    counters
+                        .dropped_captured
+                        .fetch_add(data.len() as u64, Ordering::Relaxed);
+                }
+
+                // Handle multi-channel by averaging
+                let mut dropped = 0;
+                for chunk in data.chunks(channels as usize) {
+                    if producer.try_push(mix_down(chunk)).is_err() {
+                        dropped += 1;
+                    }
                 }
+                if dropped > 0 {
+                    counters
+                        .dropped_samples
+                        .fetch_add(dropped, Ordering::Relaxed);
+                }
+                monitor.analysis_thread.unpark();
+            },
+            move |err| {
+                let event = match err {
+                    cpal::StreamError::DeviceNotAvailable => StreamEvent::DeviceLost,
+                    cpal::StreamError::BackendSpecific { err } => {
+                        StreamEvent::BackendError(err.description)
+                    }
+                };
+                // The receiver only goes away together with the stream
+                let _ = monitor.events.send(event);
             },
             None, // No timeout to avoid timestamp checking
         );
@@ -177,25 +428,411 @@ impl AudioProcessor {
         result.map_err(|e| anyhow::anyhow!("Failed to create audio stream: {e}"))
     }
 
-    /// Get the latest FFT data
-    pub async fn get_fft_data(&mut self) -> Option<Vec<f32>> {
-        // Get the most recent FFT data, discarding older ones
-        let mut latest = None;
-        while let Ok(data) = self.fft_rx.try_recv() {
-            latest = Some(data);
+    /// Get the latest spectrum, if a new frame has been analysed since the last call
+    pub fn latest_spectrum(&mut self) -> Option<&Spectrum> {
+        self.spectrum
+            .update()
+            .then(|| self.spectrum.output_buffer())
+    }
+
+    /// Start queueing every analysed frame for [`AudioProcessor::drain_frames`], for consumers
+    /// such as exports that must not miss frames the way [`AudioProcessor::latest_spectrum`] can.
+    /// Does nothing if the queue is already enabled.
+    pub fn queue_frames(&mut self) {
+        if self.frame_queue.is_some() {
+            return;
+        }
+        let fft_size = self.fft_size.load(Ordering::Relaxed);
+        let length = (FRAME_QUEUE_DURATION.as_secs_f32() * self.sample_rate as f32
+            / (fft_size / 2) as f32) as usize;
+        let (tap, queue) = frame_queue(length.max(MIN_FRAME_QUEUE_LENGTH), fft_size);
+        // The thread only goes away together with the processor
+        let _ = self.frame_taps.send(tap);
+        self.frame_queue = Some(queue);
+    }
+
+    /// Pass every frame analysed since the last call to `f`, oldest first, once enabled with
+    /// [`AudioProcessor::queue_frames`]. Up to two seconds of frames are queued; frames analysed
+    /// while the queue is full are dropped and counted by [`AudioProcessor::take_dropped_frames`].
+    pub fn drain_frames(&mut self, f: impl FnMut(&Spectrum)) {
+        if let Some(queue) = &mut self.frame_queue {
+            queue.drain(f);
+        }
+    }
+
+    /// Number of frames dropped from the frame queue since the last call
+    pub fn take_dropped_frames(&mut self) -> u64 {
+        self.frame_queue
+            .as_mut()
+            .map_or(0, FrameQueue::take_dropped)
+    }
+
+    /// Change the FFT size; takes effect from the next analysed frame
+    pub fn set_fft_size(&self, fft_size: usize) {
+        self.fft_size.store(fft_size, Ordering::Relaxed);
+        if let Some(handle) = &self.analysis_thread {
+            handle.thread().unpark();
+        }
+    }
+
+    /// Change the window applied to each frame; takes effect from the next analysed frame
+    pub fn set_window(&self, window: Window) {
+        self.window.store(window.index(), Ordering::Relaxed);
+        if let Some(handle) = &self.analysis_thread {
+            handle.thread().unpark();
         }
-        latest
     }
 
     /// Get sample rate
     pub fn sample_rate(&self) -> u32 {
         self.sample_rate
     }
+
+    /// Number of channels captured
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    /// Pass the raw samples captured since the last call to `f`, as interleaved frames of all
+    /// channels before they are mixed down for analysis.
+    ///
+    /// Up to two seconds are buffered; audio captured while the buffer is full is dropped. Returns
+    /// the number of samples dropped since the last call, so a gap in the audio passed on can be
+    /// reported.
+    pub fn take_captured(&mut self, mut f: impl FnMut(&[f32])) -> u64 {
+        let (first, second) = self.captured.as_slices();
+        let len = first.len() + second.len();
+        f(first);
+        if !second.is_empty() {
+            f(second);
+        }
+        self.captured.skip(len);
+
+        let dropped = self.counters.dropped_captured.load(Ordering::Relaxed);
+        let new = dropped - self.reported_dropped_captured;
+        self.reported_dropped_captured = dropped;
+        new
+    }
+
+    /// Number of FFT frames analysed per second of input audio
+    pub fn analysis_rate(&self) -> f32 {
+        self.sample_rate as f32 / (self.fft_size.load(Ordering::Relaxed) / 2) as f32
+    }
+
+    /// Get the analysis frame and backlog counters
+    pub fn analysis_stats(&self) -> AnalysisStats {
+        AnalysisStats {
+            frames: self.analysis_counters.frames.load(Ordering::Relaxed),
+            dropped_frames: self
+                .analysis_counters
+                .dropped_frames
+                .load(Ordering::Relaxed),
+            backlog_samples: self
+                .analysis_counters
+                .backlog_samples
+                .load(Ordering::Relaxed),
+        }
+    }
+
+    /// Get the name of the device this processor is capturing from
+    pub fn device_name(&self) -> &str {
+        &self.device_name
+    }
+
+    /// Collect the stream problems reported since the last call
+    pub fn poll_events(&mut self) -> Vec<StreamEvent> {
+        let mut events = Vec::new();
+        while let Ok(event) = self.events_rx.try_recv() {
+            events.push(event);
+        }
+
+        let xruns = self.counters.xruns.load(Ordering::Relaxed);
+        if xruns > self.reported_xruns {
+            events.push(StreamEvent::Xrun(xruns - self.reported_xruns));
+            self.reported_xruns = xruns;
+        }
+
+        let dropped = self.counters.dropped_samples.load(Ordering::Relaxed);
+        if dropped > self.reported_dropped_samples {
+            events.push(StreamEvent::Overflow(
+                dropped - self.reported_dropped_samples,
+            ));
+            self.reported_dropped_samples = dropped;
+        }
+
+        // A stream that silently stops calling back is as dead as one that reports an error
+        let callbacks = self.counters.callbacks.load(Ordering::Relaxed);
+        if callbacks != self.last_callbacks {
+            self.last_callbacks = callbacks;
+            self.last_activity = Instant::now();
+            self.stall_reported = false;
+        } else if !self.stall_reported && self.last_activity.elapsed() > STALL_TIMEOUT {
+            events.push(StreamEvent::Stalled);
+            self.stall_reported = true;
+        }
+
+        // The watchdog above only sees the stream callbacks, which keep running without analysis
+        if !self.analysis_stop_reported
+            && self
+                .analysis_thread
+                .as_ref()
+                .is_some_and(JoinHandle::is_finished)
+        {
+            events.push(StreamEvent::AnalysisStopped);
+            self.analysis_stop_reported = true;
+        }
+
+        events
+    }
+}
+
+impl Drop for AudioProcessor {
+    fn drop(&mut self) {
+        stop_analysis(&self.analysis_running, self.analysis_thread.take());
+    }
+}
+
+/// State shared between an [`AudioProcessor`] and its analysis thread
+struct AnalysisShared {
+    running: Arc<AtomicBool>,
+    counters: Arc<AnalysisCounters>,
+    fft_size: Arc<AtomicUsize>,
+    window: Arc<AtomicUsize>,
+    frame_taps: mpsc::Receiver<FrameTap>,
+    max_backlog: usize,
+    sample_rate: u32,
+}
+
+/// The analysis run for every hop of input audio: a windowed FFT over the latest frame, beat
+/// tracking and spectral features. Public so benchmarks measure the analysis thread's own code.
+pub struct AnalysisPipeline {
+    analyzer: Analyzer,
+    beat_tracker: BeatTracker,
+    sample_rate: u32,
+    /// Number of the next frame published
+    frame: u64,
+    /// Receives a copy of every frame published, if set
+    frame_tap: Option<FrameTap>,
+}
+
+impl AnalysisPipeline {
+    /// Analyse frames of `fft_size` samples of audio at `sample_rate`, one every half frame
+    pub fn new(fft_size: usize, sample_rate: u32) -> Self {
+        let analyzer = Analyzer::new(fft_size, fft_size / 2);
+        let beat_tracker = BeatTracker::new(
+            sample_rate as f32 / analyzer.hop_size() as f32,
+            fft_size / 2 + 1,
+        );
+        Self {
+            analyzer,
+            beat_tracker,
+            sample_rate,
+            frame: 0,
+            frame_tap: None,
+        }
+    }
+
+    /// Switch to frames of `fft_size` samples and to `window`; allocates only if the FFT size
+    /// changed
+    pub fn configure(&mut self, fft_size: usize, window: Window) {
+        if fft_size != self.analyzer.fft_size() {
+            self.analyzer.set_fft_size(fft_size, fft_size / 2);
+            self.beat_tracker.reset(
+                self.sample_rate as f32 / self.analyzer.hop_size() as f32,
+                fft_size / 2 + 1,
+            );
+        }
+        self.analyzer.set_window(window);
+    }
+
+    /// Also pass a copy of every frame published from now on to `tap`
+    pub fn set_frame_tap(&mut self, tap: FrameTap) {
+        self.frame_tap = Some(tap);
+    }
+
+    /// Count `frames` hops of audio dropped without analysis, so frame numbers keep measuring
+    /// audio time
+    pub fn skip_frames(&mut self, frames: u64) {
+        self.frame += frames;
+    }
+
+    /// Number of new samples each frame is analysed after
+    pub fn hop_size(&self) -> usize {
+        self.analyzer.hop_size()
+    }
+
+    /// Analyse every complete hop of samples waiting in `samples`, publishing a spectrum for each
+    /// to `spectrum`. Returns the number of frames published; an incomplete hop is left waiting
+    /// for more samples.
+    pub fn analyse_pending(
+        &mut self,
+        samples: &mut impl Consumer<Item = f32>,
+        spectrum: &mut TripleInput<Spectrum>,
+    ) -> u64 {
+        let hop_size = self.hop_size();
+        let mut frames = 0;
+        while samples.occupied_len() >= hop_size {
+            self.analyzer.advance(|hop| {
+                samples.pop_slice(hop);
+            });
+
+            let output = spectrum.input_buffer();
+            self.analyzer.compute(output);
+            output.rhythm = self.beat_tracker.process(&output.magnitudes);
+            output.features = SpectralFeatures {
+                flux: output.rhythm.flux,
+                ..spectral_features(&output.magnitudes, self.sample_rate, output.fft_size)
+            };
+            output.frame = self.frame;
+            self.frame += 1;
+            if let Some(tap) = &mut self.frame_tap {
+                tap.push(output);
+            }
+            spectrum.publish();
+            frames += 1;
+        }
+        frames
+    }
+}
+
+/// Analysis thread body: turns every complete hop of samples into a published spectrum frame,
+/// tracking onsets, tempo and spectral features along the way.
+///
+/// Runs without locking, and without allocating except when the FFT size changes; it parks
+/// until the stream callback wakes it.
+fn run_analysis(
+    mut consumer: ringbuf::HeapCons<f32>,
+    mut spectrum: TripleInput<Spectrum>,
+    shared: AnalysisShared,
+) {
+    let AnalysisShared {
+        running,
+        counters,
+        fft_size,
+        window,
+        frame_taps,
+        max_backlog,
+        sample_rate,
+    } = shared;
+    let mut pipeline = AnalysisPipeline::new(fft_size.load(Ordering::Relaxed), sample_rate);
+
+    while running.load(Ordering::Acquire) {
+        if let Ok(tap) = frame_taps.try_recv() {
+            pipeline.set_frame_tap(tap);
+        }
+        pipeline.configure(
+            fft_size.load(Ordering::Relaxed),
+            Window::ALL[window.load(Ordering::Relaxed)],
+        );
+        let skipped_hops = skip_backlog(&mut consumer, max_backlog, pipeline.hop_size());
+        pipeline.skip_frames(skipped_hops);
+        counters
+            .dropped_frames
+            .fetch_add(skipped_hops, Ordering::Relaxed);
+
+        let frames = pipeline.analyse_pending(&mut consumer, &mut spectrum);
+        counters.frames.fetch_add(frames, Ordering::Relaxed);
+        counters
+            .backlog_samples
+            .store(consumer.occupied_len(), Ordering::Relaxed);
+
+        // Woken by the stream callback; spurious wakeups just find nothing to do
+        thread::park();
+    }
 }
 
-/// Get available audio input devices
-pub fn get_input_devices() -> Result<Vec<(String, Device)>> {
-    let host = AudioProcessor::get_best_audio_host();
+/// Drop whole hops of the oldest samples if more than `max_backlog` are waiting, so analysis that
+/// fell behind catches up while staying aligned to hops. Returns the number of hops dropped.
+fn skip_backlog(
+    samples: &mut impl Consumer<Item = f32>,
+    max_backlog: usize,
+    hop_size: usize,
+) -> u64 {
+    let backlog = samples.occupied_len();
+    if backlog <= max_backlog {
+        return 0;
+    }
+    let skipped_hops = (backlog - max_backlog).div_ceil(hop_size);
+    samples.skip(skipped_hops * hop_size);
+    skipped_hops as u64
+}
+
+/// Whether a gap of `gap` between the capture times of two callbacks, the first of which delivered
+/// `previous_frames` frames at `sample_rate`, means the backend skipped audio: the gap is well
+/// beyond the duration of the previous buffer
+fn is_xrun(gap: Duration, previous_frames: usize, sample_rate: f64) -> bool {
+    let expected = Duration::from_secs_f64(previous_frames as f64 / sample_rate);
+    previous_frames > 0 && gap > expected * 2
+}
+
+/// Normalize a sample of any format to [-1, 1]; unsigned formats are re-centred around their
+/// midpoint
+fn normalize<T>(sample: T) -> f32
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    sample.to_sample()
+}
+
+/// Mix the samples of one frame of all channels down to a single normalized sample
+fn mix_down<T>(frame: &[T]) -> f32
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    frame.iter().copied().map(normalize).sum::<f32>() / frame.len() as f32
+}
+
+/// Signal the analysis thread to exit and wait for it
+fn stop_analysis(running: &AtomicBool, handle: Option<JoinHandle<()>>) {
+    running.store(false, Ordering::Release);
+    if let Some(handle) = handle {
+        handle.thread().unpark();
+        let _ = handle.join();
+    }
+}
+
+/// Get the best available audio host: the first one that has any input device
+pub fn get_best_audio_host() -> cpal::Host {
+    // Get all available hosts and try them in order
+    let available_hosts = cpal::available_hosts();
+
+    for host_id in available_hosts {
+        if let Ok(host) = cpal::host_from_id(host_id) {
+            // Test if the host actually works by checking for devices
+            if let Ok(mut devices) = host.input_devices()
+                && devices.next().is_some()
+            {
+                // This host has input devices, use it
+                return host;
+            }
+        }
+    }
+
+    // Fallback to default host
+    cpal::default_host()
+}
+
+/// Open the audio host with the given name (e.g. "ALSA", "JACK"), ignoring case
+pub fn get_host_by_name(name: &str) -> Result<cpal::Host> {
+    let available = cpal::available_hosts();
+    let host_id = available
+        .iter()
+        .find(|id| id.name().eq_ignore_ascii_case(name))
+        .ok_or_else(|| {
+            let names: Vec<&str> = available.iter().map(|id| id.name()).collect();
+            anyhow::anyhow!(
+                "Unknown audio host '{name}'. Available hosts: {}",
+                names.join(", ")
+            )
+        })?;
+
+    Ok(cpal::host_from_id(*host_id)?)
+}
+
+/// Get available audio input devices on `host`
+pub fn get_input_devices(host: &cpal::Host) -> Result<Vec<(String, Device)>> {
     let mut devices = Vec::new();
 
     for device in host.input_devices()? {
@@ -206,3 +843,147 @@ pub fn get_input_devices() -> Result<Vec<(String, Device)>> {
 
     Ok(devices)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check that the lowest, midpoint and highest values of a sample type span [-1, 1]
+    fn assert_full_scale<T>(min: T, zero: T, max: T)
+    where
+        T: SizedSample + std::fmt::Debug,
+        f32: FromSample<T>,
+    {
+        assert_eq!(normalize(min), -1.0, "{min:?}");
+        assert_eq!(normalize(zero), 0.0, "{zero:?}");
+        let top = normalize(max);
+        assert!(top <= 1.0 && top > 0.99, "{max:?} -> {top}");
+    }
+
+    #[test]
+    fn every_sample_format_spans_full_scale() {
+        assert_full_scale(i8::MIN, 0, i8::MAX);
+        assert_full_scale(i16::MIN, 0, i16::MAX);
+        assert_full_scale(i32::MIN, 0, i32::MAX);
+        assert_full_scale(i64::MIN, 0, i64::MAX);
+        assert_full_scale(u8::MIN, 1 << 7, u8::MAX);
+        assert_full_scale(u16::MIN, 1 << 15, u16::MAX);
+        assert_full_scale(u32::MIN, 1 << 31, u32::MAX);
+        assert_full_scale(u64::MIN, 1 << 63, u64::MAX);
+        assert_full_scale(-1.0f32, 0.0, 1.0);
+        assert_full_scale(-1.0f64, 0.0, 1.0);
+    }
+
+    #[test]
+    fn hosts_are_found_by_name_ignoring_case() {
+        let default = cpal::default_host().id();
+        let host = get_host_by_name(&default.name().to_lowercase()).unwrap();
+        assert_eq!(host.id(), default);
+        let host = get_host_by_name(&default.name().to_uppercase()).unwrap();
+        assert_eq!(host.id(), default);
+    }
+
+    #[test]
+    fn unknown_hosts_list_the_available_ones() {
+        let message = get_host_by_name("no-such-host").err().unwrap().to_string();
+        assert!(
+            message.starts_with("Unknown audio host 'no-such-host'. Available hosts: "),
+            "{message}"
+        );
+        assert!(
+            message.contains(cpal::default_host().id().name()),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn gaps_beyond_twice_the_buffer_are_xruns() {
+        // 480 frames at 48 kHz last 10 ms
+        let ms = Duration::from_millis;
+        assert!(!is_xrun(ms(10), 480, 48_000.0));
+        assert!(!is_xrun(ms(20), 480, 48_000.0));
+        assert!(is_xrun(ms(21), 480, 48_000.0));
+        // Timing jitter on a late callback is not an xrun
+        assert!(!is_xrun(ms(15), 480, 48_000.0));
+        // Without a previous buffer there is nothing to compare against
+        assert!(!is_xrun(ms(100), 0, 48_000.0));
+    }
+
+    #[test]
+    fn a_frame_is_published_for_every_complete_hop() {
+        let mut pipeline = AnalysisPipeline::new(8, 48_000);
+        assert_eq!(pipeline.hop_size(), 4);
+        let (mut producer, mut consumer) = HeapRb::<f32>::new(64).split();
+        let (mut input, mut output) = triple_buffer(Spectrum::new(8));
+
+        producer.push_slice(&[0.5; 10]);
+        assert_eq!(pipeline.analyse_pending(&mut consumer, &mut input), 2);
+        assert_eq!(consumer.occupied_len(), 2);
+        assert!(output.update());
+        assert_eq!(output.output_buffer().frame, 1);
+
+        // Nothing happens until the hop is complete, however long that takes
+        producer.push_slice(&[0.5; 1]);
+        assert_eq!(pipeline.analyse_pending(&mut consumer, &mut input), 0);
+        assert!(!output.update());
+        producer.push_slice(&[0.5; 1]);
+        assert_eq!(pipeline.analyse_pending(&mut consumer, &mut input), 1);
+        assert!(output.update());
+        assert_eq!(output.output_buffer().frame, 2);
+        assert_eq!(consumer.occupied_len(), 0);
+
+        // A new FFT size takes effect from the next frame, with a hop of half a frame
+        pipeline.configure(16, Window::default());
+        assert_eq!(pipeline.hop_size(), 8);
+        producer.push_slice(&[0.5; 8]);
+        assert_eq!(pipeline.analyse_pending(&mut consumer, &mut input), 1);
+        assert!(output.update());
+        assert_eq!(output.output_buffer().fft_size, 16);
+        assert_eq!(output.output_buffer().frame, 3);
+
+        // Hops dropped unanalysed still count
+        pipeline.skip_frames(2);
+        producer.push_slice(&[0.5; 8]);
+        assert_eq!(pipeline.analyse_pending(&mut consumer, &mut input), 1);
+        assert!(output.update());
+        assert_eq!(output.output_buffer().frame, 6);
+    }
+
+    #[test]
+    fn a_frame_tap_sees_every_frame_the_triple_buffer_skips() {
+        let mut pipeline = AnalysisPipeline::new(8, 48_000);
+        let (tap, mut queue) = frame_queue(8, 8);
+        pipeline.set_frame_tap(tap);
+        let (mut producer, mut consumer) = HeapRb::<f32>::new(64).split();
+        let (mut input, mut output) = triple_buffer(Spectrum::new(8));
+
+        producer.push_slice(&[0.5; 12]);
+        assert_eq!(pipeline.analyse_pending(&mut consumer, &mut input), 3);
+        assert!(output.update());
+        assert_eq!(output.output_buffer().frame, 2);
+        let mut frames = Vec::new();
+        queue.drain(|spectrum| frames.push(spectrum.frame));
+        assert_eq!(frames, [0, 1, 2]);
+    }
+
+    #[test]
+    fn a_large_backlog_is_skipped_in_whole_hops() {
+        let (mut producer, mut consumer) = HeapRb::<f32>::new(64).split();
+        let samples: Vec<f32> = (0..30).map(|i| i as f32).collect();
+        producer.push_slice(&samples);
+        assert_eq!(skip_backlog(&mut consumer, 30, 4), 0);
+        assert_eq!(consumer.occupied_len(), 30);
+
+        // 13 samples too many round up to four hops, leaving the newest 14
+        assert_eq!(skip_backlog(&mut consumer, 17, 4), 4);
+        assert_eq!(consumer.occupied_len(), 14);
+        assert_eq!(consumer.try_pop(), Some(16.0));
+    }
+
+    #[test]
+    fn channels_are_mixed_down_by_averaging() {
+        assert_eq!(mix_down(&[i16::MIN, 0]), -0.5);
+        assert_eq!(mix_down(&[u8::MAX, u8::MAX, u8::MAX]), normalize(u8::MAX));
+        assert_eq!(mix_down(&[0.25f32]), 0.25);
+    }
+}
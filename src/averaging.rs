@@ -0,0 +1,197 @@
+/// How successive spectra are combined
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AveragingMode {
+    /// Show each spectrum as it arrives
+    #[default]
+    Off,
+    /// Exponentially weighted average of the power, forgetting older spectra over a time constant
+    Exponential,
+    /// Equally weighted average of the power of every spectrum since the last reset
+    Linear,
+    /// Highest magnitude of each bin since the last reset
+    MaxHold,
+    /// Lowest magnitude of each bin since the last reset
+    MinHold,
+}
+
+impl AveragingMode {
+    /// Display name
+    pub fn name(self) -> &'static str {
+        match self {
+            AveragingMode::Off => "off",
+            AveragingMode::Exponential => "exponential",
+            AveragingMode::Linear => "linear",
+            AveragingMode::MaxHold => "max hold",
+            AveragingMode::MinHold => "min hold",
+        }
+    }
+}
+
+/// Combines successive magnitude spectra according to an [`AveragingMode`]
+#[derive(Debug, Clone, Default)]
+pub struct SpectrumAverager {
+    mode: AveragingMode,
+    /// Time constant of the exponential average in seconds
+    time_constant: f32,
+    /// Averaged power (for the averaging modes) or held magnitude (for the hold modes) per bin
+    accumulator: Vec<f32>,
+    /// Magnitudes of the result
+    magnitudes: Vec<f32>,
+    /// Number of spectra combined since the last reset
+    count: usize,
+}
+
+impl SpectrumAverager {
+    /// Create an averager; `time_constant` (in seconds) only affects exponential averaging
+    pub fn new(mode: AveragingMode, time_constant: f32) -> Self {
+        Self {
+            mode,
+            time_constant,
+            ..Self::default()
+        }
+    }
+
+    /// Current mode
+    pub fn mode(&self) -> AveragingMode {
+        self.mode
+    }
+
+    /// Change the mode, starting the average over
+    pub fn set_mode(&mut self, mode: AveragingMode) {
+        self.mode = mode;
+        self.reset();
+    }
+
+    /// Start the average over from the next spectrum
+    pub fn reset(&mut self) {
+        self.count = 0;
+    }
+
+    /// Number of spectra combined since the last reset
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Combined magnitudes; empty until a spectrum has been added
+    pub fn magnitudes(&self) -> &[f32] {
+        if self.count == 0 {
+            &[]
+        } else {
+            &self.magnitudes
+        }
+    }
+
+    /// Add the spectrum `magnitudes`, arriving `elapsed` seconds after the previous one. A
+    /// spectrum of a different size starts the average over.
+    pub fn update(&mut self, magnitudes: &[f32], elapsed: f32) {
+        if self.mode == AveragingMode::Off {
+            return;
+        }
+        if self.accumulator.len() != magnitudes.len() {
+            self.accumulator.resize(magnitudes.len(), 0.0);
+            self.magnitudes.resize(magnitudes.len(), 0.0);
+            self.count = 0;
+        }
+
+        if self.count == 0 {
+            for (accumulated, magnitude) in self.accumulator.iter_mut().zip(magnitudes) {
+                *accumulated = match self.mode {
+                    AveragingMode::Exponential | AveragingMode::Linear => magnitude * magnitude,
+                    _ => *magnitude,
+                };
+            }
+        } else {
+            // Weight of the new spectrum in the power averages
+            let weight = match self.mode {
+                AveragingMode::Exponential => {
+                    1.0 - (-elapsed / self.time_constant.max(f32::EPSILON)).exp()
+                }
+                _ => 1.0 / (self.count + 1) as f32,
+            };
+            for (accumulated, magnitude) in self.accumulator.iter_mut().zip(magnitudes) {
+                *accumulated = match self.mode {
+                    AveragingMode::Exponential | AveragingMode::Linear => {
+                        *accumulated + weight * (magnitude * magnitude - *accumulated)
+                    }
+                    AveragingMode::MaxHold => accumulated.max(*magnitude),
+                    AveragingMode::MinHold => accumulated.min(*magnitude),
+                    AveragingMode::Off => *magnitude,
+                };
+            }
+        }
+        self.count += 1;
+
+        for (magnitude, accumulated) in self.magnitudes.iter_mut().zip(&self.accumulator) {
+            *magnitude = match self.mode {
+                AveragingMode::Exponential | AveragingMode::Linear => accumulated.sqrt(),
+                _ => *accumulated,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn average(mode: AveragingMode, spectra: &[[f32; 2]], elapsed: f32) -> Vec<f32> {
+        let mut averager = SpectrumAverager::new(mode, 1.0);
+        for spectrum in spectra {
+            averager.update(spectrum, elapsed);
+        }
+        averager.magnitudes().to_vec()
+    }
+
+    #[test]
+    fn linear_average_weights_spectra_equally_by_power() {
+        let averaged = average(
+            AveragingMode::Linear,
+            &[[3.0, 0.0], [4.0, 0.0], [0.0, 5.0]],
+            0.1,
+        );
+        // sqrt((9 + 16 + 0) / 3) and sqrt(25 / 3)
+        assert!((averaged[0] - (25.0f32 / 3.0).sqrt()).abs() < 1e-5);
+        assert!((averaged[1] - (25.0f32 / 3.0).sqrt()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn exponential_average_follows_the_time_constant() {
+        // After one time constant a step has risen to 1 - 1/e of its power
+        let mut averager = SpectrumAverager::new(AveragingMode::Exponential, 0.5);
+        averager.update(&[0.0], 0.0);
+        for _ in 0..50 {
+            averager.update(&[1.0], 0.01);
+        }
+        let power = averager.magnitudes()[0].powi(2);
+        assert!((power - (1.0 - (-1.0f32).exp())).abs() < 1e-3, "{power}");
+    }
+
+    #[test]
+    fn holds_keep_extremes() {
+        let spectra = [[1.0, 5.0], [3.0, 2.0], [2.0, 4.0]];
+        assert_eq!(average(AveragingMode::MaxHold, &spectra, 0.1), [3.0, 5.0]);
+        assert_eq!(average(AveragingMode::MinHold, &spectra, 0.1), [1.0, 2.0]);
+    }
+
+    #[test]
+    fn resets_start_over() {
+        let mut averager = SpectrumAverager::new(AveragingMode::MaxHold, 1.0);
+        assert!(averager.magnitudes().is_empty());
+        averager.update(&[5.0], 0.1);
+        averager.reset();
+        averager.update(&[1.0], 0.1);
+        assert_eq!(averager.magnitudes(), [1.0]);
+
+        // A new spectrum size starts over as well
+        averager.update(&[2.0, 2.0], 0.1);
+        assert_eq!(averager.count(), 1);
+
+        averager.set_mode(AveragingMode::MinHold);
+        assert_eq!(averager.count(), 0);
+
+        // Switched off, nothing is combined
+        averager.set_mode(AveragingMode::Off);
+        averager.update(&[1.0, 1.0], 0.1);
+        assert!(averager.magnitudes().is_empty());
+    }
+}
@@ -0,0 +1,196 @@
+use crate::bars::{MAX_BAR_HEIGHT, bins_per_bar, height_to_db};
+
+/// A label on an axis
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tick {
+    /// Column (x-axis) or row (y-axis) the label starts at
+    pub position: usize,
+    pub label: String,
+}
+
+/// Frequency in Hz at the (fractional) FFT bin `bin`
+pub fn bin_frequency(bin: f32, sample_rate: u32, fft_size: usize) -> f32 {
+    bin * sample_rate as f32 / fft_size as f32
+}
+
+/// Format a frequency compactly, e.g. `50`, `500`, `1k`, `2.5k`, `10k`
+pub fn format_frequency(hz: f32) -> String {
+    if hz < 1000.0 {
+        format!("{hz:.0}")
+    } else if (hz / 1000.0).fract() == 0.0 {
+        format!("{:.0}k", hz / 1000.0)
+    } else {
+        format!("{:.1}k", hz / 1000.0)
+    }
+}
+
+/// Frequency labels for bars `bar_width` columns wide in a chart `width` columns wide, where each
+/// bar averages consecutive bins of a spectrum with `bin_count` bins.
+///
+/// Labels are picked from 1-2-5 steps (decades first), placed at the column their frequency falls
+/// in, and dropped if they would share a bar or touch a label already placed.
+pub fn frequency_ticks(
+    bin_count: usize,
+    bar_count: usize,
+    bar_width: usize,
+    width: usize,
+    sample_rate: u32,
+    fft_size: usize,
+) -> Vec<Tick> {
+    let bins_per_bar = bins_per_bar(bin_count, bar_count);
+    let chart_width = width.min(bar_count * bar_width);
+    let hz_per_column =
+        bin_frequency(bins_per_bar as f32, sample_rate, fft_size) / bar_width.max(1) as f32;
+    if chart_width == 0 || hz_per_column <= 0.0 {
+        return Vec::new();
+    }
+
+    // (first column, end column, bar) of every placed label
+    let mut placed: Vec<(usize, usize, usize)> = Vec::new();
+    let mut ticks = Vec::new();
+    for mantissa in [1.0, 5.0, 2.0] {
+        for exponent in 1..=5 {
+            let hz = mantissa * 10f32.powi(exponent);
+            let column = (hz / hz_per_column) as usize;
+            let bar = column / bar_width.max(1);
+
+            // The first bar covers everything up to its bandwidth, so a label there says nothing
+            if bar == 0 || column >= chart_width {
+                continue;
+            }
+
+            let label = format_frequency(hz);
+            let start = column
+                .saturating_sub(label.len() / 2)
+                .min(chart_width.saturating_sub(label.len()));
+            let end = start + label.len();
+            if end > chart_width
+                || placed
+                    .iter()
+                    .any(|&(s, e, b)| b == bar || (start <= e && s <= end))
+            {
+                continue;
+            }
+
+            placed.push((start, end, bar));
+            ticks.push(Tick {
+                position: start,
+                label,
+            });
+        }
+    }
+
+    ticks.sort_by_key(|tick| tick.position);
+    ticks
+}
+
+/// dB labels for a chart `rows` rows high whose full height is [`MAX_BAR_HEIGHT`], spaced at
+/// least two rows apart
+pub fn db_ticks(rows: usize, sensitivity: f32) -> Vec<Tick> {
+    let top = height_to_db(MAX_BAR_HEIGHT as f32, sensitivity);
+    let span = top - height_to_db(0.0, sensitivity);
+    if rows == 0 || span <= 0.0 {
+        return Vec::new();
+    }
+
+    let Some(step) = [
+        1.0, 2.0, 3.0, 5.0, 10.0, 20.0, 30.0, 50.0, 100.0, 200.0, 500.0,
+    ]
+    .into_iter()
+    .find(|step| step / span * rows as f32 >= 2.0) else {
+        return Vec::new();
+    };
+
+    let mut ticks: Vec<Tick> = Vec::new();
+    let mut db = (top / step).floor() * step;
+    while db > top - span {
+        // Rounded rather than truncated, so float error cannot pull two labels a row closer
+        let row = ((top - db) / span * rows as f32).round() as usize;
+        if row < rows && ticks.last().is_none_or(|tick| tick.position < row) {
+            ticks.push(Tick {
+                position: row,
+                label: format!("{db:.0}"),
+            });
+        }
+        db -= step;
+    }
+    ticks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(ticks: &[Tick]) -> Vec<&str> {
+        ticks.iter().map(|tick| tick.label.as_str()).collect()
+    }
+
+    #[test]
+    fn frequencies_are_formatted_compactly() {
+        assert_eq!(format_frequency(50.0), "50");
+        assert_eq!(format_frequency(500.0), "500");
+        assert_eq!(format_frequency(1000.0), "1k");
+        assert_eq!(format_frequency(2500.0), "2.5k");
+        assert_eq!(format_frequency(20000.0), "20k");
+    }
+
+    #[test]
+    fn ticks_follow_the_bin_to_bar_mapping() {
+        // 48 kHz, 1024-point FFT: 513 bins, 16 per bar at 32 bars = 750 Hz per bar
+        let ticks = frequency_ticks(513, 32, 4, 128, 48_000, 1024);
+        assert_eq!(labels(&ticks), ["1k", "2k", "5k", "10k", "20k"]);
+
+        // 10 kHz is 10000 / 750 * 4 = 53.3 columns in, and its label is centred there
+        let tick = ticks.iter().find(|tick| tick.label == "10k").unwrap();
+        assert_eq!(tick.position, 53 - 1);
+    }
+
+    #[test]
+    fn finer_bars_show_lower_frequencies() {
+        // 16384-point FFT at 128 bars: 64 bins (~188 Hz) per bar
+        let ticks = frequency_ticks(8193, 128, 2, 256, 48_000, 16384);
+        assert_eq!(labels(&ticks)[..3], ["500", "1k", "2k"]);
+    }
+
+    #[test]
+    fn ticks_never_overlap_at_any_width() {
+        for width in 1..300 {
+            for bar_count in [8, 32, 128] {
+                let bar_width = (width / bar_count).max(1);
+                let ticks = frequency_ticks(513, bar_count, bar_width, width, 44_100, 1024);
+                for pair in ticks.windows(2) {
+                    assert!(pair[0].position + pair[0].label.len() < pair[1].position);
+                }
+                if let Some(last) = ticks.last() {
+                    assert!(last.position + last.label.len() <= width);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn db_ticks_are_spaced_out() {
+        let ticks = db_ticks(20, 1.0);
+        assert_eq!(
+            labels(&ticks),
+            ["0", "-10", "-20", "-30", "-40", "-50", "-60", "-70", "-80"]
+        );
+        assert_eq!(ticks[0].position, 0);
+
+        let ticks = db_ticks(5, 1.0);
+        assert_eq!(labels(&ticks), ["0", "-50"]);
+
+        for rows in 1..60 {
+            for sensitivity in [0.5, 1.0, 1.7, 3.0] {
+                let ticks = db_ticks(rows, sensitivity);
+                for pair in ticks.windows(2) {
+                    assert!(
+                        pair[1].position - pair[0].position >= 2,
+                        "{rows} rows at sensitivity {sensitivity}: {ticks:?}"
+                    );
+                }
+                assert!(ticks.iter().all(|tick| tick.position < rows));
+            }
+        }
+    }
+}
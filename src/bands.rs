@@ -0,0 +1,155 @@
+use std::ops::Range;
+
+use crate::analysis::Window;
+
+/// Level reported for bands without any energy
+pub const MIN_LEVEL_DB: f32 = -120.0;
+
+/// A named frequency range summarised as a single level
+#[derive(Debug, Clone, PartialEq)]
+pub struct Band {
+    /// Short label shown in the band meters
+    pub name: String,
+    /// Lowest frequency in Hz, inclusive
+    pub low: f32,
+    /// Highest frequency in Hz, exclusive
+    pub high: f32,
+}
+
+impl Band {
+    /// Create a band covering `low` up to `high` Hz
+    pub fn new(name: impl Into<String>, low: f32, high: f32) -> Self {
+        Self {
+            name: name.into(),
+            low,
+            high,
+        }
+    }
+
+    /// FFT bins whose centre frequency lies in the band
+    pub fn bins(&self, bin_count: usize, sample_rate: u32, fft_size: usize) -> Range<usize> {
+        if fft_size == 0 {
+            return 0..0;
+        }
+        let hz_per_bin = sample_rate as f32 / fft_size as f32;
+        let start = ((self.low / hz_per_bin).ceil().max(0.0) as usize).min(bin_count);
+        let end = ((self.high / hz_per_bin).ceil().max(0.0) as usize).clamp(start, bin_count);
+        start..end
+    }
+}
+
+/// The usual sub-bass to brilliance split of the audible range
+pub fn default_bands() -> Vec<Band> {
+    vec![
+        Band::new("Sub", 20.0, 60.0),
+        Band::new("Bass", 60.0, 250.0),
+        Band::new("Low-mid", 250.0, 500.0),
+        Band::new("Mid", 500.0, 2000.0),
+        Band::new("High-mid", 2000.0, 4000.0),
+        Band::new("Presence", 4000.0, 6000.0),
+        Band::new("Brilliance", 6000.0, 20000.0),
+    ]
+}
+
+/// Level of each band in dB relative to a full-scale sine, from the spectrum `magnitudes` of a
+/// `fft_size`-point FFT of frames weighted with `window`.
+///
+/// The energy of all bins in a band is summed, so a sine reads the same whatever the FFT size,
+/// and broadband signals read higher in wider bands. Empty bands read [`MIN_LEVEL_DB`].
+pub fn band_levels(
+    magnitudes: &[f32],
+    sample_rate: u32,
+    fft_size: usize,
+    window: Window,
+    bands: &[Band],
+) -> Vec<f32> {
+    bands
+        .iter()
+        .map(|band| {
+            let energy: f32 = magnitudes[band.bins(magnitudes.len(), sample_rate, fft_size)]
+                .iter()
+                .map(|magnitude| magnitude * magnitude)
+                .sum();
+            // Mean square of the signal in the band, doubled so a full-scale sine is 1
+            let power = 4.0 * energy / (fft_size as f32 * fft_size as f32 * window.power_gain());
+            if power > 0.0 {
+                (10.0 * power.log10()).max(MIN_LEVEL_DB)
+            } else {
+                MIN_LEVEL_DB
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::{Analyzer, Spectrum};
+
+    const SAMPLE_RATE: u32 = 48_000;
+
+    fn tone_spectrum(frequency: f32, amplitude: f32, fft_size: usize, window: Window) -> Vec<f32> {
+        let mut analyzer = Analyzer::new(fft_size, fft_size);
+        analyzer.set_window(window);
+        analyzer.advance(|samples| {
+            for (i, sample) in samples.iter_mut().enumerate() {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                *sample = amplitude * (2.0 * std::f32::consts::PI * frequency * t).sin();
+            }
+        });
+        let mut spectrum = Spectrum::new(fft_size);
+        analyzer.compute(&mut spectrum);
+        spectrum.magnitudes
+    }
+
+    #[test]
+    fn tone_is_measured_in_its_band() {
+        let bands = default_bands();
+        let sizes_and_windows = [1024, 4096]
+            .into_iter()
+            .flat_map(|size| Window::ALL.map(|window| (size, window)));
+        for (fft_size, window) in sizes_and_windows {
+            let levels = band_levels(
+                &tone_spectrum(1000.0, 0.5, fft_size, window),
+                SAMPLE_RATE,
+                fft_size,
+                window,
+                &bands,
+            );
+            // Half scale is -6 dB
+            assert!((levels[3] + 6.02).abs() < 0.2, "{levels:?}");
+            for (band, level) in levels.iter().enumerate() {
+                if band != 3 {
+                    assert!(*level < -40.0, "{levels:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn silence_reads_the_floor() {
+        let levels = band_levels(
+            &[0.0; 513],
+            SAMPLE_RATE,
+            1024,
+            Window::Hann,
+            &default_bands(),
+        );
+        assert_eq!(levels, [MIN_LEVEL_DB; 7]);
+    }
+
+    #[test]
+    fn bands_map_to_bins() {
+        let band = Band::new("Mid", 500.0, 2000.0);
+        // 46.875 Hz per bin
+        assert_eq!(band.bins(513, SAMPLE_RATE, 1024), 11..43);
+
+        // Bands beyond Nyquist are empty rather than out of range
+        let band = Band::new("Ultrasonic", 30_000.0, 40_000.0);
+        assert!(band.bins(513, SAMPLE_RATE, 1024).is_empty());
+        assert_eq!(
+            band_levels(&[1.0; 513], SAMPLE_RATE, 1024, Window::Hann, &[band]),
+            [MIN_LEVEL_DB]
+        );
+    }
+}
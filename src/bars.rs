@@ -0,0 +1,119 @@
+use std::ops::Range;
+
+/// Highest bar height produced by [`spectrum_to_bars`]
+pub const MAX_BAR_HEIGHT: u64 = 100;
+
+/// Group FFT bin magnitudes into `bar_count` bars with log-scaled heights from 0 to
+/// [`MAX_BAR_HEIGHT`]
+pub fn spectrum_to_bars(magnitudes: &[f32], bar_count: usize, sensitivity: f32) -> Vec<u64> {
+    let mut bars = Vec::with_capacity(bar_count);
+
+    for i in 0..bar_count {
+        let avg_magnitude = bar_magnitude(magnitudes, bar_count, i);
+
+        // Apply logarithmic scaling for better visual representation
+        let log_magnitude = if avg_magnitude > 0.0 {
+            (avg_magnitude.ln() + 10.0).max(0.0)
+        } else {
+            0.0
+        };
+
+        // Scale by sensitivity and convert to bar height
+        bars.push(((log_magnitude * sensitivity * 10.0) as u64).min(MAX_BAR_HEIGHT));
+    }
+
+    bars
+}
+
+/// Number of FFT bins averaged into each of `bar_count` bars
+pub fn bins_per_bar(bin_count: usize, bar_count: usize) -> usize {
+    std::cmp::max(1, bin_count / bar_count.max(1))
+}
+
+/// Range of FFT bins averaged into `bar`; empty if the spectrum has fewer bins than bars
+pub fn bar_bins(bin_count: usize, bar_count: usize, bar: usize) -> Range<usize> {
+    let bins_per_bar = bins_per_bar(bin_count, bar_count);
+    let start = (bar * bins_per_bar).min(bin_count);
+    start..(start + bins_per_bar).min(bin_count)
+}
+
+/// Average magnitude of the bins in `bar`
+pub fn bar_magnitude(magnitudes: &[f32], bar_count: usize, bar: usize) -> f32 {
+    let bins = &magnitudes[bar_bins(magnitudes.len(), bar_count, bar)];
+    if bins.is_empty() {
+        0.0
+    } else {
+        bins.iter().sum::<f32>() / bins.len() as f32
+    }
+}
+
+/// Convert a magnitude to dB relative to a magnitude of 1
+pub fn magnitude_to_db(magnitude: f32) -> f32 {
+    20.0 * magnitude.log10()
+}
+
+/// Level in dB (relative to a magnitude of 1) shown by a bar of `height`; the inverse of the
+/// scaling in [`spectrum_to_bars`]
+pub fn height_to_db(height: f32, sensitivity: f32) -> f32 {
+    20.0 / std::f32::consts::LN_10 * (height / (10.0 * sensitivity) - 10.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn silence_is_flat() {
+        let bars = spectrum_to_bars(&[0.0; 512], 16, 1.0);
+        assert_eq!(bars, [0; 16]);
+    }
+
+    #[test]
+    fn magnitudes_are_mapped_logarithmically() {
+        // ln(e^-5) + 10 = 5, scaled by 10
+        let bars = spectrum_to_bars(&[(-5.0f32).exp(); 64], 8, 1.0);
+        assert_eq!(bars, [50; 8]);
+
+        // Sensitivity scales the log magnitude
+        let bars = spectrum_to_bars(&[(-5.0f32).exp(); 64], 8, 0.5);
+        assert_eq!(bars, [25; 8]);
+
+        // Full scale and beyond is clamped
+        let bars = spectrum_to_bars(&[1.0, 100.0], 2, 1.0);
+        assert_eq!(bars, [MAX_BAR_HEIGHT; 2]);
+    }
+
+    #[test]
+    fn bins_are_averaged_per_bar() {
+        let e = std::f32::consts::E;
+        let spectrum = [e.powi(-8), e.powi(-8), e.powi(-4), e.powi(-4)];
+        assert_eq!(spectrum_to_bars(&spectrum, 2, 1.0), [20, 60]);
+    }
+
+    #[test]
+    fn heights_convert_back_to_db() {
+        assert!(height_to_db(MAX_BAR_HEIGHT as f32, 1.0).abs() < 1e-4);
+        assert!((height_to_db(0.0, 1.0) + 86.858_9).abs() < 1e-3);
+
+        // -20 dB is a magnitude of 0.1; heights are truncated to whole steps of ~0.87 dB
+        let [height] = spectrum_to_bars(&[0.1], 1, 1.0)[..] else {
+            unreachable!()
+        };
+        assert!((height_to_db(height as f32, 1.0) + 20.0).abs() < 0.9);
+    }
+
+    #[test]
+    fn bar_bins_cover_the_spectrum_in_order() {
+        assert_eq!(bar_bins(513, 32, 0), 0..16);
+        assert_eq!(bar_bins(513, 32, 31), 496..512);
+        assert_eq!(bar_bins(4, 8, 3), 3..4);
+        assert!(bar_bins(4, 8, 6).is_empty());
+        assert_eq!(magnitude_to_db(0.1), -20.0);
+    }
+
+    #[test]
+    fn missing_bins_give_empty_bars() {
+        let bars = spectrum_to_bars(&[1.0; 4], 8, 1.0);
+        assert_eq!(bars, [100, 100, 100, 100, 0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,102 @@
+use crate::pitch::midi_note;
+
+/// Number of pitch classes in an octave
+pub const PITCH_CLASSES: usize = 12;
+
+/// Lowest frequency folded into the chroma; below this FFT bins are too wide to tell notes apart
+const MIN_FREQUENCY: f32 = 55.0;
+
+/// Highest frequency folded into the chroma; above this harmonics dominate over notes
+const MAX_FREQUENCY: f32 = 5000.0;
+
+/// Energy of each pitch class from C to B, normalised so the strongest class is 1
+pub type Chroma = [f32; PITCH_CLASSES];
+
+/// Fold the spectrum `magnitudes` of a `fft_size`-point FFT into pitch classes across octaves,
+/// assigning each bin's energy to its nearest note in equal temperament tuned to `a4`.
+///
+/// Returns all zeros if the spectrum has no energy in the folded range.
+pub fn chroma(magnitudes: &[f32], sample_rate: u32, fft_size: usize, a4: f32) -> Chroma {
+    let mut chroma = [0.0; PITCH_CLASSES];
+    if fft_size == 0 {
+        return chroma;
+    }
+
+    let hz_per_bin = sample_rate as f32 / fft_size as f32;
+    let first = (MIN_FREQUENCY / hz_per_bin).ceil() as usize;
+    let last = ((MAX_FREQUENCY / hz_per_bin) as usize).min(magnitudes.len().saturating_sub(1));
+    for (bin, magnitude) in magnitudes.iter().enumerate().take(last + 1).skip(first) {
+        let note = midi_note(bin as f32 * hz_per_bin, a4).round() as i32;
+        chroma[note.rem_euclid(PITCH_CLASSES as i32) as usize] += magnitude * magnitude;
+    }
+
+    let max = chroma.iter().copied().fold(0.0, f32::max);
+    if max > 0.0 {
+        chroma.iter_mut().for_each(|energy| *energy /= max);
+    }
+    chroma
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::{Analyzer, Spectrum};
+    use crate::pitch::NOTE_NAMES;
+
+    const SAMPLE_RATE: u32 = 48_000;
+    const FFT_SIZE: usize = 8192;
+
+    fn spectrum_of(frequencies: &[f32]) -> Vec<f32> {
+        let mut analyzer = Analyzer::new(FFT_SIZE, FFT_SIZE);
+        analyzer.advance(|samples| {
+            for (i, sample) in samples.iter_mut().enumerate() {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                *sample = frequencies
+                    .iter()
+                    .map(|f| 0.2 * (2.0 * std::f32::consts::PI * f * t).sin())
+                    .sum();
+            }
+        });
+        let mut spectrum = Spectrum::new(FFT_SIZE);
+        analyzer.compute(&mut spectrum);
+        spectrum.magnitudes
+    }
+
+    /// Pitch classes sorted from strongest to weakest
+    fn ranking(chroma: &Chroma) -> Vec<&'static str> {
+        let mut classes: Vec<usize> = (0..PITCH_CLASSES).collect();
+        classes.sort_by(|&a, &b| chroma[b].total_cmp(&chroma[a]));
+        classes.into_iter().map(|class| NOTE_NAMES[class]).collect()
+    }
+
+    #[test]
+    fn single_notes_fold_across_octaves() {
+        for frequency in [110.0, 220.0, 440.0, 880.0] {
+            let chroma = chroma(&spectrum_of(&[frequency]), SAMPLE_RATE, FFT_SIZE, 440.0);
+            assert_eq!(ranking(&chroma)[0], "A");
+            assert_eq!(chroma[9], 1.0);
+        }
+    }
+
+    #[test]
+    fn chords_light_up_their_notes() {
+        // C major: C4, E4, G4
+        let chroma = chroma(
+            &spectrum_of(&[261.63, 329.63, 392.0]),
+            SAMPLE_RATE,
+            FFT_SIZE,
+            440.0,
+        );
+        let mut top = ranking(&chroma)[..3].to_vec();
+        top.sort();
+        assert_eq!(top, ["C", "E", "G"]);
+    }
+
+    #[test]
+    fn silence_is_empty() {
+        assert_eq!(
+            chroma(&[0.0; 4097], SAMPLE_RATE, FFT_SIZE, 440.0),
+            [0.0; 12]
+        );
+    }
+}
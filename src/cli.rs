@@ -0,0 +1,477 @@
+use clap::{ArgGroup, Args, Parser, Subcommand};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::bands::{Band, default_bands};
+use crate::config::{
+    ColorScheme, Config, MAX_A4, MAX_BAR_COUNT, MAX_FFT_SIZE, MIN_A4, MIN_BAR_COUNT, MIN_FFT_SIZE,
+    StreamErrorPolicy,
+};
+use crate::export::{ExportData, ExportFormat};
+use crate::plot::ImageFormat;
+use crate::summary::Thresholds;
+
+/// Command line arguments for the audio visualizer
+#[derive(Debug, Parser)]
+#[command(version, about)]
+#[command(group = ArgGroup::new("spectrum_output").args(["export", "no_tui"]).multiple(true))]
+pub struct Cli {
+    /// Audio host backend to capture from (e.g. ALSA, JACK); remembered for later runs
+    #[arg(long, value_name = "NAME")]
+    pub host: Option<String>,
+
+    /// Append log messages to this file in addition to the in-app log
+    #[arg(long, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+
+    /// How to react to audio stream errors such as xruns or backend failures
+    #[arg(long, value_enum, value_name = "POLICY", default_value = "warn")]
+    pub on_stream_error: StreamErrorPolicy,
+
+    /// Tuner reference pitch for A4 in Hz
+    #[arg(long, value_name = "HZ", default_value_t = 440.0, value_parser = parse_a4)]
+    pub a4: f32,
+
+    /// Band for the band meters as NAME=LOW-HIGH in Hz (e.g. Bass=60-250); repeat to replace the
+    /// default sub-bass to brilliance bands
+    #[arg(long = "band", value_name = "NAME=LOW-HIGH", value_parser = parse_band)]
+    pub bands: Vec<Band>,
+
+    /// Write the spectral features of every analysed frame to this CSV file
+    #[arg(long, value_name = "PATH")]
+    pub features_csv: Option<PathBuf>,
+
+    /// Seconds of spectra kept for stepping through while paused
+    #[arg(long, value_name = "SECONDS", default_value = "10", value_parser = parse_seconds)]
+    pub history: Duration,
+
+    /// Seconds of audio from before a recording is started to include in it
+    #[arg(long, value_name = "SECONDS", default_value = "5", value_parser = parse_pre_roll)]
+    pub pre_roll: Duration,
+
+    /// Directory to write recordings and spectrum snapshots to
+    #[arg(long, value_name = "PATH", default_value = ".")]
+    pub record_dir: PathBuf,
+
+    /// File format of the spectrum snapshots saved with `p`
+    #[arg(long, value_enum, value_name = "FORMAT", default_value = "png")]
+    pub snapshot_format: ImageFormat,
+
+    /// Number of samples per FFT frame, a power of two
+    #[arg(long, value_name = "SAMPLES", default_value_t = 1024, value_parser = parse_fft_size)]
+    pub fft_size: usize,
+
+    /// Number of frequency bars
+    #[arg(long, value_name = "COUNT", default_value_t = 32, value_parser = parse_bar_count)]
+    pub bars: usize,
+
+    /// Write every analysed spectrum to this file, or to standard output if it is `-`
+    #[arg(long, value_name = "PATH")]
+    pub export: Option<PathBuf>,
+
+    /// Encoding of the exported spectra
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        default_value = "csv",
+        requires = "spectrum_output"
+    )]
+    pub export_format: ExportFormat,
+
+    /// Export the level of every FFT bin or the bar heights
+    #[arg(
+        long,
+        value_enum,
+        value_name = "DATA",
+        default_value = "bins",
+        requires = "spectrum_output"
+    )]
+    pub export_data: ExportData,
+
+    /// Run without the terminal UI, exporting spectra (to standard output unless --export is
+    /// given) and features (with --features-csv) until interrupted
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "log_file",
+            "on_stream_error",
+            "a4",
+            "bands",
+            "history",
+            "pre_roll",
+            "record_dir",
+            "snapshot_format",
+        ]
+    )]
+    pub no_tui: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Commands run instead of the visualizer
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Print the peak, RMS, loudness, dominant frequency and spectral centroid of WAV files,
+    /// exiting with status 3 if any exceeds a given limit
+    Analyze(AnalyzeArgs),
+    /// Render the average spectrum or the spectrogram of a WAV file to a PNG or SVG image, as
+    /// the spectrum view draws it with the --fft-size and --bars given before the command
+    Render(RenderArgs),
+}
+
+/// Arguments of the `analyze` command
+#[derive(Debug, Args)]
+pub struct AnalyzeArgs {
+    /// WAV files to analyse
+    #[arg(value_name = "FILE", required = true)]
+    pub files: Vec<PathBuf>,
+
+    /// Print a JSON array with one object per file instead of text
+    #[arg(long)]
+    pub json: bool,
+
+    /// Fail if the sample peak is above this level in dBFS
+    #[arg(long, value_name = "DBFS", allow_negative_numbers = true)]
+    pub max_peak: Option<f32>,
+
+    /// Fail if the RMS level is above this level in dBFS
+    #[arg(long, value_name = "DBFS", allow_negative_numbers = true)]
+    pub max_rms: Option<f32>,
+
+    /// Fail if the integrated loudness is above this level in LUFS
+    #[arg(long, value_name = "LUFS", allow_negative_numbers = true)]
+    pub max_lufs: Option<f32>,
+
+    /// Fail if the integrated loudness is below this level in LUFS, or cannot be measured
+    #[arg(long, value_name = "LUFS", allow_negative_numbers = true)]
+    pub min_lufs: Option<f32>,
+}
+
+/// Arguments of the `render` command
+#[derive(Debug, Args)]
+pub struct RenderArgs {
+    /// WAV file to render
+    #[arg(value_name = "FILE")]
+    pub input: PathBuf,
+
+    /// Image to write; its extension (.png or .svg) sets the format unless --format is given
+    #[arg(value_name = "IMAGE")]
+    pub output: PathBuf,
+
+    /// File format of the image
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    pub format: Option<ImageFormat>,
+
+    /// Render how the spectrum changes over the whole file instead of its average
+    #[arg(long)]
+    pub spectrogram: bool,
+
+    /// Color scheme of the bars
+    #[arg(long, value_enum, value_name = "SCHEME", default_value = "rainbow")]
+    pub scheme: ColorScheme,
+
+    /// Draw the spectrum on the fixed dB scale with level labels
+    #[arg(long)]
+    pub db_axis: bool,
+}
+
+impl RenderArgs {
+    /// The image format asked for, or else the one named by the output file's extension
+    pub fn image_format(&self) -> Option<ImageFormat> {
+        self.format.or_else(|| ImageFormat::from_path(&self.output))
+    }
+}
+
+impl AnalyzeArgs {
+    /// The limits the files are checked against
+    pub fn thresholds(&self) -> Thresholds {
+        Thresholds {
+            max_peak_dbfs: self.max_peak,
+            max_rms_dbfs: self.max_rms,
+            max_loudness_lufs: self.max_lufs,
+            min_loudness_lufs: self.min_lufs,
+        }
+    }
+}
+
+impl Cli {
+    /// Build the initial configuration from the command line options
+    pub fn config(&self) -> Config {
+        Config {
+            stream_error_policy: self.on_stream_error,
+            a4: self.a4,
+            bands: if self.bands.is_empty() {
+                default_bands()
+            } else {
+                self.bands.clone()
+            },
+            fft_size: self.fft_size,
+            bar_count: self.bars,
+            history: self.history,
+            pre_roll: self.pre_roll,
+            record_dir: self.record_dir.clone(),
+            snapshot_format: self.snapshot_format,
+            ..Config::default()
+        }
+    }
+}
+
+/// Parse a tuner reference pitch, rejecting values outside the selectable range
+fn parse_a4(value: &str) -> Result<f32, String> {
+    let a4: f32 = value.parse().map_err(|e| format!("{e}"))?;
+    if (MIN_A4..=MAX_A4).contains(&a4) {
+        Ok(a4)
+    } else {
+        Err(format!("must be between {MIN_A4} and {MAX_A4} Hz"))
+    }
+}
+
+/// Parse an FFT size, which must be a power of two in the selectable range
+fn parse_fft_size(value: &str) -> Result<usize, String> {
+    let size: usize = value.parse().map_err(|e| format!("{e}"))?;
+    if size.is_power_of_two() && (MIN_FFT_SIZE..=MAX_FFT_SIZE).contains(&size) {
+        Ok(size)
+    } else {
+        Err(format!(
+            "must be a power of two from {MIN_FFT_SIZE} to {MAX_FFT_SIZE}"
+        ))
+    }
+}
+
+/// Parse a bar count in the selectable range
+fn parse_bar_count(value: &str) -> Result<usize, String> {
+    let count: usize = value.parse().map_err(|e| format!("{e}"))?;
+    if (MIN_BAR_COUNT..=MAX_BAR_COUNT).contains(&count) {
+        Ok(count)
+    } else {
+        Err(format!(
+            "must be between {MIN_BAR_COUNT} and {MAX_BAR_COUNT}"
+        ))
+    }
+}
+
+/// Parse a positive number of seconds
+fn parse_seconds(value: &str) -> Result<Duration, String> {
+    let seconds: f32 = value.parse().map_err(|e| format!("{e}"))?;
+    if seconds > 0.0 && seconds.is_finite() {
+        Ok(Duration::from_secs_f32(seconds))
+    } else {
+        Err("must be a positive number of seconds".to_string())
+    }
+}
+
+/// Parse a pre-roll length in seconds, which may be zero
+fn parse_pre_roll(value: &str) -> Result<Duration, String> {
+    if value.trim().parse::<f32>() == Ok(0.0) {
+        Ok(Duration::ZERO)
+    } else {
+        parse_seconds(value)
+    }
+}
+
+/// Parse a band meter definition of the form `NAME=LOW-HIGH`
+fn parse_band(value: &str) -> Result<Band, String> {
+    let (name, range) = value
+        .split_once('=')
+        .ok_or("expected NAME=LOW-HIGH, e.g. Bass=60-250")?;
+    let (low, high) = range
+        .split_once('-')
+        .ok_or("expected a range LOW-HIGH in Hz, e.g. 60-250")?;
+    let low: f32 = low.trim().parse().map_err(|e| format!("{e}"))?;
+    let high: f32 = high.trim().parse().map_err(|e| format!("{e}"))?;
+    if name.trim().is_empty() {
+        Err("band name must not be empty".to_string())
+    } else if !(0.0..high).contains(&low) {
+        Err(format!(
+            "band must start below its end and at 0 Hz or above, got {low}-{high}"
+        ))
+    } else {
+        Ok(Band::new(name.trim(), low, high))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bands_replace_the_defaults() {
+        let cli = Cli::parse_from(["audio-visualizer"]);
+        assert_eq!(cli.config().bands, default_bands());
+
+        let cli = Cli::parse_from([
+            "audio-visualizer",
+            "--band",
+            "Kick=40-100",
+            "--band",
+            "Air=10000-20000",
+        ]);
+        assert_eq!(
+            cli.config().bands,
+            [
+                Band::new("Kick", 40.0, 100.0),
+                Band::new("Air", 10000.0, 20000.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn history_length_is_positive_seconds() {
+        let cli = Cli::parse_from(["audio-visualizer", "--history", "2.5"]);
+        assert_eq!(cli.config().history, Duration::from_millis(2500));
+        for seconds in ["0", "-1", "soon"] {
+            assert!(parse_seconds(seconds).is_err(), "{seconds} was accepted");
+        }
+    }
+
+    #[test]
+    fn pre_roll_may_be_zero() {
+        let cli = Cli::parse_from(["audio-visualizer", "--pre-roll", "0"]);
+        assert_eq!(cli.config().pre_roll, Duration::ZERO);
+        assert_eq!(parse_pre_roll("1.5"), Ok(Duration::from_millis(1500)));
+        assert!(parse_pre_roll("-1").is_err());
+    }
+
+    #[test]
+    fn analysis_sizes_are_validated() {
+        let cli = Cli::parse_from(["audio-visualizer", "--fft-size", "4096", "--bars", "64"]);
+        let config = cli.config();
+        assert_eq!((config.fft_size, config.bar_count), (4096, 64));
+        for size in ["1000", "128", "32768"] {
+            assert!(parse_fft_size(size).is_err(), "{size} was accepted");
+        }
+        for count in ["4", "129"] {
+            assert!(parse_bar_count(count).is_err(), "{count} was accepted");
+        }
+    }
+
+    #[test]
+    fn analyze_takes_files_and_limits() {
+        let cli = Cli::parse_from([
+            "audio-visualizer",
+            "analyze",
+            "a.wav",
+            "b.wav",
+            "--json",
+            "--max-peak",
+            "-1",
+            "--min-lufs",
+            "-30.5",
+        ]);
+        let Some(Command::Analyze(args)) = cli.command else {
+            panic!("expected the analyze command");
+        };
+        assert_eq!(args.files, [PathBuf::from("a.wav"), PathBuf::from("b.wav")]);
+        assert!(args.json);
+        assert_eq!(
+            args.thresholds(),
+            Thresholds {
+                max_peak_dbfs: Some(-1.0),
+                min_loudness_lufs: Some(-30.5),
+                ..Thresholds::default()
+            }
+        );
+        assert!(Cli::try_parse_from(["audio-visualizer", "analyze"]).is_err());
+        assert!(Cli::parse_from(["audio-visualizer"]).command.is_none());
+    }
+
+    #[test]
+    fn render_format_follows_the_extension() {
+        let cli = Cli::parse_from([
+            "audio-visualizer",
+            "--bars",
+            "64",
+            "render",
+            "in.wav",
+            "out.SVG",
+            "--spectrogram",
+            "--scheme",
+            "green",
+        ]);
+        assert_eq!(cli.config().bar_count, 64);
+        let Some(Command::Render(args)) = cli.command else {
+            panic!("expected the render command");
+        };
+        assert_eq!(args.image_format(), Some(ImageFormat::Svg));
+        assert!(args.spectrogram);
+        assert_eq!(args.scheme, ColorScheme::Green);
+
+        let cli = Cli::parse_from(["audio-visualizer", "render", "in.wav", "out"]);
+        let Some(Command::Render(args)) = cli.command else {
+            panic!("expected the render command");
+        };
+        assert_eq!(args.image_format(), None);
+        let cli = Cli::parse_from([
+            "audio-visualizer",
+            "render",
+            "in.wav",
+            "out",
+            "--format",
+            "png",
+        ]);
+        let Some(Command::Render(args)) = cli.command else {
+            panic!("expected the render command");
+        };
+        assert_eq!(args.image_format(), Some(ImageFormat::Png));
+    }
+
+    #[test]
+    fn headless_export_takes_only_the_options_it_uses() {
+        let cli = Cli::parse_from([
+            "audio-visualizer",
+            "--no-tui",
+            "--export-format",
+            "ndjson",
+            "--features-csv",
+            "features.csv",
+            "--fft-size",
+            "4096",
+        ]);
+        assert!(cli.no_tui);
+        assert_eq!(cli.export_format, ExportFormat::Ndjson);
+        assert_eq!(cli.features_csv, Some(PathBuf::from("features.csv")));
+
+        // Options of the terminal UI would be silently ignored
+        for option in [
+            ["--record-dir", "recordings"],
+            ["--pre-roll", "2"],
+            ["--history", "5"],
+            ["--log-file", "log.txt"],
+            ["--band", "Bass=60-250"],
+        ] {
+            let args = ["audio-visualizer", "--no-tui", option[0], option[1]];
+            assert!(
+                Cli::try_parse_from(args).is_err(),
+                "{option:?} was accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn export_options_need_an_export() {
+        for option in [["--export-format", "ndjson"], ["--export-data", "bars"]] {
+            let args = ["audio-visualizer", option[0], option[1]];
+            assert!(
+                Cli::try_parse_from(args).is_err(),
+                "{option:?} was accepted"
+            );
+            let args = [
+                "audio-visualizer",
+                "--export",
+                "out.csv",
+                option[0],
+                option[1],
+            ];
+            assert!(Cli::try_parse_from(args).is_ok(), "{option:?} was rejected");
+        }
+    }
+
+    #[test]
+    fn malformed_bands_are_rejected() {
+        for band in ["Bass", "Bass=60", "=60-250", "Bass=250-60", "Bass=low-250"] {
+            assert!(parse_band(band).is_err(), "{band} was accepted");
+        }
+    }
+}
@@ -1,3 +1,28 @@
+use crate::analysis::Window;
+use crate::averaging::AveragingMode;
+use crate::bands::{Band, default_bands};
+use crate::plot::ImageFormat;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Fewest bars shown
+pub const MIN_BAR_COUNT: usize = 8;
+
+/// Most bars shown
+pub const MAX_BAR_COUNT: usize = 128;
+
+/// Smallest selectable FFT size
+pub const MIN_FFT_SIZE: usize = 256;
+
+/// Largest selectable FFT size
+pub const MAX_FFT_SIZE: usize = 16384;
+
+/// Lowest selectable tuner reference pitch for A4 in Hz
+pub const MIN_A4: f32 = 400.0;
+
+/// Highest selectable tuner reference pitch for A4 in Hz
+pub const MAX_A4: f32 = 480.0;
+
 /// Configuration for the audio visualizer
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -9,6 +34,40 @@ pub struct Config {
     pub refresh_rate: u64,
     /// Sensitivity/gain for the visualizer
     pub sensitivity: f32,
+    /// How to react to non-fatal audio stream errors
+    pub stream_error_policy: StreamErrorPolicy,
+    /// Number of samples per FFT frame (a power of two)
+    pub fft_size: usize,
+    /// Show a dB scale next to the bars, fixing bar heights to it instead of auto-scaling
+    pub show_db_axis: bool,
+    /// Show the tuner panel with the detected pitch
+    pub show_tuner: bool,
+    /// Tuner reference pitch for A4 in Hz
+    pub a4: f32,
+    /// What the main area shows
+    pub view: ViewMode,
+    /// Flash the view border on detected beats
+    pub beat_pulse: bool,
+    /// Frequency bands summarised in the band meters
+    pub bands: Vec<Band>,
+    /// Show the band meters below the main view
+    pub show_bands: bool,
+    /// Show the spectral features panel below the main view
+    pub show_features: bool,
+    /// Measure distortion and noise of a test tone, analysing with a flat-top window
+    pub measure: bool,
+    /// How successive spectra are combined for display
+    pub averaging: AveragingMode,
+    /// Time constant of exponential averaging in seconds
+    pub averaging_time: f32,
+    /// How far back the spectrum history can be stepped through while paused
+    pub history: Duration,
+    /// Audio from before a recording is started that is included in it
+    pub pre_roll: Duration,
+    /// Directory recordings and spectrum snapshots are written to
+    pub record_dir: PathBuf,
+    /// File format of spectrum snapshots
+    pub snapshot_format: ImageFormat,
 }
 
 impl Default for Config {
@@ -18,6 +77,23 @@ impl Default for Config {
             color_scheme: ColorScheme::Rainbow,
             refresh_rate: 16, // ~60 FPS
             sensitivity: 1.0,
+            stream_error_policy: StreamErrorPolicy::Warn,
+            fft_size: 1024,
+            show_db_axis: false,
+            show_tuner: false,
+            a4: 440.0,
+            view: ViewMode::Spectrum,
+            beat_pulse: false,
+            bands: default_bands(),
+            show_bands: false,
+            show_features: false,
+            measure: false,
+            averaging: AveragingMode::Off,
+            averaging_time: 1.0,
+            history: Duration::from_secs(10),
+            pre_roll: Duration::from_secs(5),
+            record_dir: PathBuf::from("."),
+            snapshot_format: ImageFormat::Png,
         }
     }
 }
@@ -25,15 +101,15 @@ impl Default for Config {
 impl Config {
     /// Increase bar count
     pub fn increase_bar_count(&mut self) {
-        if self.bar_count < 128 {
-            self.bar_count = (self.bar_count + 8).min(128);
+        if self.bar_count < MAX_BAR_COUNT {
+            self.bar_count = (self.bar_count + 8).min(MAX_BAR_COUNT);
         }
     }
 
     /// Decrease bar count
     pub fn decrease_bar_count(&mut self) {
-        if self.bar_count > 8 {
-            self.bar_count = (self.bar_count - 8).max(8);
+        if self.bar_count > MIN_BAR_COUNT {
+            self.bar_count = (self.bar_count - 8).max(MIN_BAR_COUNT);
         }
     }
 
@@ -51,6 +127,26 @@ impl Config {
         }
     }
 
+    /// Double the FFT size for finer frequency resolution
+    pub fn increase_fft_size(&mut self) {
+        self.fft_size = (self.fft_size * 2).min(MAX_FFT_SIZE);
+    }
+
+    /// Halve the FFT size for finer time resolution
+    pub fn decrease_fft_size(&mut self) {
+        self.fft_size = (self.fft_size / 2).max(MIN_FFT_SIZE);
+    }
+
+    /// Raise the tuner reference pitch by 1 Hz
+    pub fn increase_a4(&mut self) {
+        self.a4 = (self.a4.round() + 1.0).min(MAX_A4);
+    }
+
+    /// Lower the tuner reference pitch by 1 Hz
+    pub fn decrease_a4(&mut self) {
+        self.a4 = (self.a4.round() - 1.0).max(MIN_A4);
+    }
+
     /// Cycle to next color scheme
     pub fn next_color_scheme(&mut self) {
         self.color_scheme = match self.color_scheme {
@@ -64,6 +160,35 @@ impl Config {
         };
     }
 
+    /// Cycle to the next view
+    pub fn next_view(&mut self) {
+        self.view = match self.view {
+            ViewMode::Spectrum => ViewMode::ChromaBars,
+            ViewMode::ChromaBars => ViewMode::ChromaHeatmap,
+            ViewMode::ChromaHeatmap => ViewMode::Spectrum,
+        };
+    }
+
+    /// Cycle to the next averaging mode
+    pub fn next_averaging(&mut self) {
+        self.averaging = match self.averaging {
+            AveragingMode::Off => AveragingMode::Exponential,
+            AveragingMode::Exponential => AveragingMode::Linear,
+            AveragingMode::Linear => AveragingMode::MaxHold,
+            AveragingMode::MaxHold => AveragingMode::MinHold,
+            AveragingMode::MinHold => AveragingMode::Off,
+        };
+    }
+
+    /// Window the analysis should apply: flat-top for accurate amplitudes when measuring
+    pub fn window(&self) -> Window {
+        if self.measure {
+            Window::FlatTop
+        } else {
+            Window::Hann
+        }
+    }
+
     /// Increase sensitivity
     pub fn increase_sensitivity(&mut self) {
         self.sensitivity = (self.sensitivity * 1.2).min(10.0);
@@ -77,6 +202,7 @@ impl Config {
 
 /// Available color schemes for the visualizer
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "tui", derive(clap::ValueEnum))]
 pub enum ColorScheme {
     Rainbow,
     Blue,
@@ -100,4 +226,41 @@ impl ColorScheme {
             ColorScheme::Yellow => "Yellow",
         }
     }
+
+    /// Colour of the bars in images, the common default of the terminal colour the visualizer
+    /// draws them in
+    pub fn rgb(self) -> [u8; 3] {
+        match self {
+            ColorScheme::Rainbow | ColorScheme::Purple => [205, 0, 205],
+            ColorScheme::Blue => [0, 0, 238],
+            ColorScheme::Green => [0, 205, 0],
+            ColorScheme::Red => [205, 0, 0],
+            ColorScheme::Cyan => [0, 205, 205],
+            ColorScheme::Yellow => [205, 205, 0],
+        }
+    }
+}
+
+/// Main views of the visualizer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViewMode {
+    /// Frequency spectrum bars
+    Spectrum,
+    /// Energy per pitch class (C to B) as bars
+    ChromaBars,
+    /// Energy per pitch class over time as a scrolling heatmap
+    ChromaHeatmap,
+}
+
+/// Reaction to non-fatal audio stream errors (xruns, overflows, stalls, backend errors)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "tui", derive(clap::ValueEnum))]
+pub enum StreamErrorPolicy {
+    /// Only count the error
+    Ignore,
+    /// Count the error and show a warning
+    Warn,
+    /// Count the error, show a warning and restart the stream after backend errors, waiting
+    /// longer between restarts while the errors keep coming
+    Restart,
 }
@@ -0,0 +1,244 @@
+use anyhow::Result;
+use cpal::Device;
+use std::sync::mpsc::{self, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::analysis::Window;
+use crate::audio::{AudioProcessor, get_input_devices};
+use crate::settings::Settings;
+
+/// Outcome of a request to a [`DeviceWorker`]
+pub enum DeviceUpdate {
+    /// The input devices currently present on the host, with their names
+    Devices(Vec<(String, Device)>),
+    /// The result of open request `id`, for the named device or the default device if `None`
+    Opened {
+        id: u64,
+        device: Option<String>,
+        result: Result<Box<AudioProcessor>>,
+    },
+    /// The result of saving the worker's host as the one to use on later runs
+    HostRemembered(Result<()>),
+    /// The worker thread exited, e.g. after a panic, and handles no more requests
+    Stopped,
+}
+
+/// Work handed to the worker thread
+enum Request {
+    Scan,
+    Open {
+        id: u64,
+        device: Option<(String, Device)>,
+        fft_size: usize,
+        window: Window,
+    },
+    RememberHost,
+}
+
+/// Enumerates devices and opens streams on a background thread, since some backends (ALSA, JACK)
+/// can block long enough to stall the UI. Requests are handled in order; their results are
+/// collected with [`DeviceWorker::poll`].
+pub struct DeviceWorker {
+    requests: mpsc::Sender<Request>,
+    updates: mpsc::Receiver<DeviceUpdate>,
+    /// Whether [`DeviceUpdate::Stopped`] was reported
+    stopped: bool,
+}
+
+impl DeviceWorker {
+    /// Start a worker for the host `host_id`. The thread exits once the worker is dropped.
+    pub fn new(host_id: cpal::HostId) -> Result<Self> {
+        let host = cpal::host_from_id(host_id)?;
+        let (requests, requests_rx) = mpsc::channel();
+        let (updates_tx, updates) = mpsc::channel();
+        thread::Builder::new()
+            .name("audio-devices".to_string())
+            .spawn(move || {
+                for request in requests_rx {
+                    let update = match request {
+                        Request::Scan => {
+                            DeviceUpdate::Devices(get_input_devices(&host).unwrap_or_default())
+                        }
+                        Request::Open {
+                            id,
+                            device,
+                            fft_size,
+                            window,
+                        } => {
+                            let (name, device) = device.unzip();
+                            let result = AudioProcessor::new(&host, device, fft_size).map(Box::new);
+                            if let Ok(processor) = &result {
+                                processor.set_window(window);
+                            }
+                            DeviceUpdate::Opened {
+                                id,
+                                device: name,
+                                result,
+                            }
+                        }
+                        Request::RememberHost => {
+                            let mut settings = Settings::load();
+                            settings.host = Some(host.id().name().to_string());
+                            DeviceUpdate::HostRemembered(settings.save())
+                        }
+                    };
+                    // The app went away or switched hosts; nobody wants the result
+                    if updates_tx.send(update).is_err() {
+                        break;
+                    }
+                }
+            })?;
+        Ok(Self {
+            requests,
+            updates,
+            stopped: false,
+        })
+    }
+
+    /// Enumerate the host's input devices
+    pub fn scan(&self) {
+        let _ = self.requests.send(Request::Scan);
+    }
+
+    /// Open `device`, or the host's default input device, analysing with `fft_size` and `window`.
+    /// The result is reported with `id`, so callers can tell apart requests they no longer want.
+    pub fn open(&self, id: u64, device: Option<(String, Device)>, fft_size: usize, window: Window) {
+        let _ = self.requests.send(Request::Open {
+            id,
+            device,
+            fft_size,
+            window,
+        });
+    }
+
+    /// Save the worker's host in the settings as the one to use on later runs
+    pub fn remember_host(&self) {
+        let _ = self.requests.send(Request::RememberHost);
+    }
+
+    /// Collect the results of the requests finished since the last call, followed once by
+    /// [`DeviceUpdate::Stopped`] if the thread has exited
+    pub fn poll(&mut self) -> Vec<DeviceUpdate> {
+        let mut updates = Vec::new();
+        loop {
+            match self.updates.try_recv() {
+                Ok(update) => updates.push(update),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    if !self.stopped {
+                        self.stopped = true;
+                        updates.push(DeviceUpdate::Stopped);
+                    }
+                    break;
+                }
+            }
+        }
+        updates
+    }
+}
+
+/// Exponentially growing delay between retries of an operation that keeps failing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    delay: Duration,
+    next_attempt: Option<Instant>,
+}
+
+impl Backoff {
+    /// Retry after `initial` at first, doubling the delay after every failure up to `max`
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            initial,
+            max,
+            delay: initial,
+            next_attempt: None,
+        }
+    }
+
+    /// Whether an attempt may be made at `now`
+    pub fn ready(&self, now: Instant) -> bool {
+        self.next_attempt.is_none_or(|next| now >= next)
+    }
+
+    /// Record a failed attempt at `now`, returning how long until the next one is allowed
+    pub fn failed(&mut self, now: Instant) -> Duration {
+        let delay = self.delay;
+        self.next_attempt = Some(now + delay);
+        self.delay = (delay * 2).min(self.max);
+        delay
+    }
+
+    /// Allow attempts again straight away, e.g. after a success
+    pub fn reset(&mut self) {
+        self.delay = self.initial;
+        self.next_attempt = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        let mut backoff = Backoff::new(Duration::from_secs(2), Duration::from_secs(5));
+        let start = Instant::now();
+        assert!(backoff.ready(start));
+
+        assert_eq!(backoff.failed(start), Duration::from_secs(2));
+        assert!(!backoff.ready(start + Duration::from_secs(1)));
+        assert!(backoff.ready(start + Duration::from_secs(2)));
+
+        assert_eq!(backoff.failed(start), Duration::from_secs(4));
+        assert!(!backoff.ready(start + Duration::from_secs(3)));
+        assert_eq!(backoff.failed(start), Duration::from_secs(5));
+        assert_eq!(backoff.failed(start), Duration::from_secs(5));
+
+        backoff.reset();
+        assert!(backoff.ready(start));
+        assert_eq!(backoff.failed(start), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn worker_reports_scans_in_order() {
+        let mut worker = DeviceWorker::new(cpal::default_host().id()).unwrap();
+        worker.scan();
+        worker.scan();
+        let mut scans = 0;
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while scans < 2 && Instant::now() < deadline {
+            scans += worker
+                .poll()
+                .into_iter()
+                .filter(|update| matches!(update, DeviceUpdate::Devices(_)))
+                .count();
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(scans, 2);
+    }
+
+    #[test]
+    fn a_dead_worker_is_reported_once() {
+        let (requests, _) = mpsc::channel();
+        let (updates_tx, updates) = mpsc::channel();
+        let mut worker = DeviceWorker {
+            requests,
+            updates,
+            stopped: false,
+        };
+        updates_tx
+            .send(DeviceUpdate::HostRemembered(Ok(())))
+            .unwrap();
+        drop(updates_tx);
+
+        let updates = worker.poll();
+        assert!(matches!(
+            updates[..],
+            [DeviceUpdate::HostRemembered(Ok(())), DeviceUpdate::Stopped]
+        ));
+        assert!(worker.poll().is_empty());
+    }
+}
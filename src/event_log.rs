@@ -0,0 +1,241 @@
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Maximum number of entries kept in memory for the log overlay
+const MAX_ENTRIES: usize = 500;
+
+/// Severity of a logged event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    /// Get the name of the level for display
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Info => "INFO",
+            Level::Warn => "WARN",
+            Level::Error => "ERROR",
+        }
+    }
+}
+
+/// A single logged event
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub level: Level,
+    pub timestamp: SystemTime,
+    pub message: String,
+}
+
+impl LogEntry {
+    /// Time elapsed since the entry was logged
+    pub fn age(&self) -> Duration {
+        self.timestamp.elapsed().unwrap_or_default()
+    }
+}
+
+/// Thread-safe event log shared between the UI, audio callbacks and the panic hook.
+///
+/// Entries are kept in memory for display and optionally appended to a log file.
+#[derive(Clone, Default)]
+pub struct EventLog {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: VecDeque<LogEntry>,
+    file: Option<File>,
+}
+
+impl EventLog {
+    /// Create an in-memory event log
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an event log that also appends every entry to the file at `path`
+    pub fn with_file(path: &Path) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let log = Self::new();
+        log.lock().file = Some(file);
+        Ok(log)
+    }
+
+    /// Log an informational message
+    pub fn info(&self, message: impl Into<String>) {
+        self.push(Level::Info, message.into());
+    }
+
+    /// Log a warning
+    pub fn warn(&self, message: impl Into<String>) {
+        self.push(Level::Warn, message.into());
+    }
+
+    /// Log an error
+    pub fn error(&self, message: impl Into<String>) {
+        self.push(Level::Error, message.into());
+    }
+
+    /// Append an entry to the log and the log file, if any
+    pub fn push(&self, level: Level, message: String) {
+        let entry = LogEntry {
+            level,
+            timestamp: SystemTime::now(),
+            message,
+        };
+
+        let mut inner = self.lock();
+        if let Some(file) = inner.file.as_mut() {
+            // A failing log file must never take the application down
+            let _ = writeln!(
+                file,
+                "{} {:<5} {}",
+                format_datetime(entry.timestamp),
+                entry.level.name(),
+                entry.message
+            );
+        }
+        if inner.entries.len() == MAX_ENTRIES {
+            inner.entries.pop_front();
+        }
+        inner.entries.push_back(entry);
+    }
+
+    /// Get a copy of all retained entries, oldest first
+    pub fn entries(&self) -> Vec<LogEntry> {
+        self.lock().entries.iter().cloned().collect()
+    }
+
+    /// Get the most recent entry
+    pub fn latest(&self) -> Option<LogEntry> {
+        self.lock().entries.back().cloned()
+    }
+
+    /// Number of retained entries
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    /// Whether nothing has been logged yet
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        // Keep logging even if a panicking thread poisoned the lock
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Format a timestamp as `HH:MM:SS` (UTC)
+pub fn format_time(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let day_secs = secs % 86_400;
+    format!(
+        "{:02}:{:02}:{:02}",
+        day_secs / 3600,
+        (day_secs / 60) % 60,
+        day_secs % 60
+    )
+}
+
+/// Format a timestamp as `YYYY-MM-DDTHH:MM:SS.mmmZ` (UTC)
+pub fn format_datetime(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let (year, month, day) = civil_from_days((since_epoch.as_secs() / 86_400) as i64);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{}.{:03}Z",
+        format_time(time),
+        since_epoch.subsec_millis()
+    )
+}
+
+/// Format a timestamp as `YYYYMMDD-HHMMSS` (UTC), for use in file names
+pub fn format_file_timestamp(time: SystemTime) -> String {
+    let (year, month, day) = civil_from_days(
+        (time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+            / 86_400) as i64,
+    );
+    format!(
+        "{year:04}{month:02}{day:02}-{}",
+        format_time(time).replace(':', "")
+    )
+}
+
+/// Convert days since the Unix epoch to a (year, month, day) civil date
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // Howard Hinnant's days-to-civil algorithm
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_are_kept_in_order_up_to_the_limit() {
+        let log = EventLog::new();
+        assert!(log.is_empty());
+        log.info("first");
+        log.warn("second");
+        log.error("third");
+        let levels: Vec<Level> = log.entries().iter().map(|entry| entry.level).collect();
+        assert_eq!(levels, [Level::Info, Level::Warn, Level::Error]);
+        assert_eq!(log.latest().unwrap().message, "third");
+
+        for i in 0..MAX_ENTRIES {
+            log.info(format!("entry {i}"));
+        }
+        assert_eq!(log.len(), MAX_ENTRIES);
+        assert_eq!(log.entries()[0].message, "entry 0");
+    }
+
+    #[test]
+    fn clones_share_the_log_and_file() {
+        let path = std::env::temp_dir().join(format!(
+            "audio-visualizer-test-{}-events.log",
+            std::process::id()
+        ));
+        let log = EventLog::with_file(&path).unwrap();
+        log.clone().warn("from a clone");
+        assert_eq!(log.len(), 1);
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(contents.ends_with("Z WARN  from a clone\n"), "{contents:?}");
+    }
+
+    #[test]
+    fn timestamps_are_formatted_in_utc() {
+        // 2024-02-29 13:04:05.678 UTC
+        let time = UNIX_EPOCH + Duration::from_millis(1_709_211_845_678);
+        assert_eq!(format_time(time), "13:04:05");
+        assert_eq!(format_datetime(time), "2024-02-29T13:04:05.678Z");
+        assert_eq!(format_file_timestamp(time), "20240229-130405");
+        assert_eq!(format_datetime(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+    }
+}
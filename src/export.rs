@@ -0,0 +1,349 @@
+use anyhow::Result;
+use serde_json::json;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::analysis::Spectrum;
+use crate::axis::bin_frequency;
+use crate::bands::MIN_LEVEL_DB;
+use crate::bars::{magnitude_to_db, spectrum_to_bars};
+
+/// Encoding of exported spectra
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "tui", derive(clap::ValueEnum))]
+pub enum ExportFormat {
+    /// Comma-separated values with a header row
+    #[default]
+    Csv,
+    /// One JSON object per line
+    Ndjson,
+}
+
+/// What is exported of each spectrum
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "tui", derive(clap::ValueEnum))]
+pub enum ExportData {
+    /// The level of every FFT bin in dBFS, where a full-scale sine peaks at 0 dB
+    #[default]
+    Bins,
+    /// The bar heights shown by the spectrum view, from 0 to
+    /// [`MAX_BAR_HEIGHT`](crate::bars::MAX_BAR_HEIGHT)
+    Bars,
+}
+
+/// Audio time of analysed frames in seconds since the first one seen, for timestamps that follow
+/// the audio rather than when the frames happened to be written out.
+///
+/// Each frame comes a hop (half an FFT frame) after the previous one, plus a hop for every frame
+/// number skipped in between. A frame number that goes back, as when the stream is reopened,
+/// continues one hop after the last time rather than starting over.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameClock {
+    /// Number and time of the last frame seen
+    last: Option<(u64, f64)>,
+}
+
+impl FrameClock {
+    /// Time of frame `frame` of `fft_size` samples of audio at `sample_rate`
+    pub fn time(&mut self, frame: u64, fft_size: usize, sample_rate: u32) -> f64 {
+        let hop = (fft_size / 2) as f64 / sample_rate.max(1) as f64;
+        let time = match self.last {
+            None => 0.0,
+            Some((last_frame, last_time)) if frame > last_frame => {
+                last_time + (frame - last_frame) as f64 * hop
+            }
+            Some((_, last_time)) => last_time + hop,
+        };
+        self.last = Some((frame, time));
+        time
+    }
+}
+
+/// Writes every spectrum as a CSV row or NDJSON line, with the seconds of audio since the export
+/// started (see [`FrameClock`]).
+///
+/// CSV output starts with a header naming the columns (bin frequencies in Hz with the unit of the
+/// levels, or bar numbers), and repeats it whenever the FFT size, sample rate or bar count
+/// changes.
+pub struct SpectrumExport {
+    writer: Box<dyn Write>,
+    format: ExportFormat,
+    data: ExportData,
+    clock: FrameClock,
+    /// Sample rate, column count and FFT size the last CSV header was written for
+    header: Option<(u32, usize, usize)>,
+}
+
+impl SpectrumExport {
+    /// Create (or truncate) the file at `path`, or write to standard output if `path` is `-`
+    pub fn create(path: &Path, format: ExportFormat, data: ExportData) -> Result<Self> {
+        if path == Path::new("-") {
+            // Standard output is line buffered, so each spectrum is passed on as it is written
+            return Ok(Self::new(std::io::stdout(), format, data));
+        }
+        let file = File::create(path)
+            .map_err(|e| anyhow::anyhow!("Failed to create {}: {e}", path.display()))?;
+        Ok(Self::new(BufWriter::new(file), format, data))
+    }
+
+    /// Start exporting to `writer`
+    pub fn new(writer: impl Write + 'static, format: ExportFormat, data: ExportData) -> Self {
+        Self {
+            writer: Box::new(writer),
+            format,
+            data,
+            clock: FrameClock::default(),
+            header: None,
+        }
+    }
+
+    /// Append `spectrum` of audio at `sample_rate`, as bins or as `bar_count` bars scaled by
+    /// `sensitivity`
+    pub fn write(
+        &mut self,
+        spectrum: &Spectrum,
+        sample_rate: u32,
+        bar_count: usize,
+        sensitivity: f32,
+    ) -> Result<()> {
+        let magnitudes = spectrum.magnitudes.as_slice();
+        let fft_size = spectrum.fft_size;
+        let time = self.clock.time(spectrum.frame, fft_size, sample_rate);
+        let full_scale = fft_size as f32 * spectrum.window.coherent_gain() / 2.0;
+        match (self.format, self.data) {
+            (ExportFormat::Csv, ExportData::Bins) => {
+                let layout = (sample_rate, magnitudes.len(), fft_size);
+                if self.header != Some(layout) {
+                    let frequencies = (0..magnitudes.len()).map(|bin| {
+                        format!(
+                            "{:.1}Hz_dBFS",
+                            bin_frequency(bin as f32, sample_rate, fft_size)
+                        )
+                    });
+                    write_csv_row(&mut self.writer, "time_s", frequencies)?;
+                    self.header = Some(layout);
+                }
+                let levels = magnitudes
+                    .iter()
+                    .map(|&magnitude| format!("{:.2}", level(magnitude / full_scale)));
+                write_csv_row(&mut self.writer, &format!("{time:.3}"), levels)?;
+            }
+            (ExportFormat::Csv, ExportData::Bars) => {
+                let layout = (sample_rate, bar_count, fft_size);
+                if self.header != Some(layout) {
+                    let bars = (1..=bar_count).map(|bar| format!("bar_{bar}"));
+                    write_csv_row(&mut self.writer, "time_s", bars)?;
+                    self.header = Some(layout);
+                }
+                let bars = spectrum_to_bars(magnitudes, bar_count, sensitivity);
+                write_csv_row(
+                    &mut self.writer,
+                    &format!("{time:.3}"),
+                    bars.iter().map(u64::to_string),
+                )?;
+            }
+            (ExportFormat::Ndjson, ExportData::Bins) => {
+                let levels: Vec<f32> = magnitudes
+                    .iter()
+                    .map(|&magnitude| (level(magnitude / full_scale) * 100.0).round() / 100.0)
+                    .collect();
+                let line = json!({
+                    "time_s": round_time(time),
+                    "sample_rate": sample_rate,
+                    "fft_size": fft_size,
+                    "hz_per_bin": bin_frequency(1.0, sample_rate, fft_size),
+                    "levels_dbfs": levels,
+                });
+                writeln!(self.writer, "{line}")?;
+            }
+            (ExportFormat::Ndjson, ExportData::Bars) => {
+                let line = json!({
+                    "time_s": round_time(time),
+                    "bars": spectrum_to_bars(magnitudes, bar_count, sensitivity),
+                });
+                writeln!(self.writer, "{line}")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write out any buffered spectra
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Level in dB of a bin magnitude relative to that of a full-scale sine, floored so silence stays
+/// a number
+fn level(magnitude: f32) -> f32 {
+    magnitude_to_db(magnitude).max(MIN_LEVEL_DB)
+}
+
+/// Time rounded to milliseconds, as in the CSV output
+fn round_time(time: f64) -> f64 {
+    (time * 1000.0).round() / 1000.0
+}
+
+/// Write `first` and then `values` as one comma-separated line
+fn write_csv_row(
+    writer: &mut dyn Write,
+    first: &str,
+    values: impl Iterator<Item = String>,
+) -> Result<()> {
+    write!(writer, "{first}")?;
+    for value in values {
+        write!(writer, ",{value}")?;
+    }
+    writeln!(writer)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::{Analyzer, Window};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Writer whose contents stay readable after it is boxed
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(data)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedBuffer {
+        fn lines(&self) -> Vec<String> {
+            String::from_utf8(self.0.borrow().clone())
+                .unwrap()
+                .lines()
+                .map(str::to_string)
+                .collect()
+        }
+    }
+
+    /// Spectrum numbered `frame` with the bin magnitudes `magnitudes`
+    fn spectrum(frame: u64, magnitudes: &[f32]) -> Spectrum {
+        let mut spectrum = Spectrum::new((magnitudes.len() - 1) * 2);
+        spectrum.magnitudes.copy_from_slice(magnitudes);
+        spectrum.frame = frame;
+        spectrum
+    }
+
+    fn export(format: ExportFormat, data: ExportData, spectra: &[&[f32]]) -> Vec<String> {
+        let buffer = SharedBuffer::default();
+        let mut export = SpectrumExport::new(buffer.clone(), format, data);
+        for (frame, magnitudes) in spectra.iter().enumerate() {
+            export
+                .write(&spectrum(frame as u64, magnitudes), 8000, 2, 1.0)
+                .unwrap();
+        }
+        export.flush().unwrap();
+        buffer.lines()
+    }
+
+    #[test]
+    fn csv_bins_have_a_frequency_header() {
+        let lines = export(
+            ExportFormat::Csv,
+            ExportData::Bins,
+            &[&[1.0, 0.1, 0.0], &[0.01, 1.0, 10.0]],
+        );
+        // A full-scale sine in a Hann-windowed 4-sample frame has a magnitude of 1
+        assert_eq!(lines[0], "time_s,0.0Hz_dBFS,2000.0Hz_dBFS,4000.0Hz_dBFS");
+        assert_eq!(lines[1], "0.000,0.00,-20.00,-120.00");
+        // Frames of 4 samples at 8 kHz come every 0.25 ms
+        assert_eq!(lines[2], "0.000,-40.00,0.00,20.00");
+        assert_eq!(lines.len(), 3);
+    }
+
+    #[test]
+    fn csv_header_is_repeated_when_the_layout_changes() {
+        let lines = export(
+            ExportFormat::Csv,
+            ExportData::Bins,
+            &[&[1.0, 1.0, 1.0], &[1.0; 5], &[1.0; 5]],
+        );
+        assert_eq!(lines.len(), 5);
+        assert_eq!(
+            lines[2],
+            "time_s,0.0Hz_dBFS,1000.0Hz_dBFS,2000.0Hz_dBFS,3000.0Hz_dBFS,4000.0Hz_dBFS"
+        );
+    }
+
+    #[test]
+    fn bin_levels_are_relative_to_full_scale() {
+        // A full-scale sine centred on bin 64
+        let samples: Vec<f32> = (0..1024)
+            .map(|i| (2.0 * std::f32::consts::PI * 64.0 * i as f32 / 1024.0).sin())
+            .collect();
+        for window in [Window::Hann, Window::FlatTop] {
+            let mut analyzer = Analyzer::new(1024, 1024);
+            analyzer.set_window(window);
+            analyzer.advance(|frame| frame.copy_from_slice(&samples));
+            let mut spectrum = Spectrum::new(1024);
+            analyzer.compute(&mut spectrum);
+
+            let buffer = SharedBuffer::default();
+            let mut export =
+                SpectrumExport::new(buffer.clone(), ExportFormat::Ndjson, ExportData::Bins);
+            export.write(&spectrum, 48_000, 2, 1.0).unwrap();
+            export.flush().unwrap();
+            let line: serde_json::Value = serde_json::from_str(&buffer.lines()[0]).unwrap();
+            let peak = line["levels_dbfs"][64].as_f64().unwrap();
+            assert!(peak.abs() < 0.1, "{window:?}: {peak}");
+        }
+    }
+
+    #[test]
+    fn csv_bars_match_the_spectrum_view() {
+        let spectrum = [1.0, 1.0, 0.0, 0.0];
+        let lines = export(ExportFormat::Csv, ExportData::Bars, &[&spectrum]);
+        assert_eq!(lines[0], "time_s,bar_1,bar_2");
+        let bars = spectrum_to_bars(&spectrum, 2, 1.0);
+        assert!(
+            lines[1].ends_with(&format!(",{},{}", bars[0], bars[1])),
+            "{}",
+            lines[1]
+        );
+    }
+
+    #[test]
+    fn ndjson_lines_are_self_describing() {
+        let lines = export(ExportFormat::Ndjson, ExportData::Bins, &[&[1.0, 0.1, 0.0]]);
+        let line: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+        assert_eq!(line["sample_rate"], 8000);
+        assert_eq!(line["fft_size"], 4);
+        assert_eq!(line["hz_per_bin"], 2000.0);
+        assert_eq!(line["levels_dbfs"], json!([0.0, -20.0, -120.0]));
+
+        let lines = export(ExportFormat::Ndjson, ExportData::Bars, &[&[1.0; 4]]);
+        let line: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+        assert_eq!(line["bars"], json!([100, 100]));
+        assert_eq!(line["time_s"], 0.0);
+    }
+
+    #[test]
+    fn timestamps_follow_the_audio() {
+        // Hops of 1024 samples at 48 kHz
+        let mut clock = FrameClock::default();
+        let hop = 1024.0 / 48_000.0;
+        assert_eq!(clock.time(7, 2048, 48_000), 0.0);
+        assert_eq!(clock.time(8, 2048, 48_000), hop);
+        // Skipped frames still take up time
+        assert_eq!(clock.time(11, 2048, 48_000), 4.0 * hop);
+        // A reopened stream numbers its frames from zero again
+        assert_eq!(clock.time(0, 2048, 48_000), 5.0 * hop);
+        // Smaller frames come more often
+        assert_eq!(clock.time(1, 1024, 48_000), 5.0 * hop + hop / 2.0);
+    }
+}
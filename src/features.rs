@@ -0,0 +1,280 @@
+use anyhow::Result;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::analysis::Spectrum;
+use crate::export::FrameClock;
+
+/// Fraction of the spectral energy below the rolloff frequency
+pub const ROLLOFF_FRACTION: f32 = 0.85;
+
+/// Power floor for the flatness, so empty bins do not make the geometric mean zero
+const FLATNESS_FLOOR: f32 = 1e-20;
+
+/// Standard descriptors of the shape of one magnitude spectrum
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SpectralFeatures {
+    /// Magnitude-weighted mean frequency in Hz ("brightness")
+    pub centroid: f32,
+    /// Magnitude-weighted standard deviation of frequency around the centroid in Hz
+    pub bandwidth: f32,
+    /// Frequency in Hz below which [`ROLLOFF_FRACTION`] of the energy lies
+    pub rolloff: f32,
+    /// Geometric over arithmetic mean of the power spectrum, from 0 (tonal) to 1 (white noise)
+    pub flatness: f32,
+    /// Peak over mean magnitude; high for a few strong partials, low for noise
+    pub crest: f32,
+    /// Spectral flux from the previous frame, see [`Rhythm::flux`](crate::rhythm::Rhythm::flux)
+    pub flux: f32,
+}
+
+impl SpectralFeatures {
+    /// Column names matching [`SpectralFeatures::to_csv`]
+    pub const CSV_HEADER: &str = "centroid_hz,bandwidth_hz,rolloff_hz,flatness,crest,flux";
+
+    /// The features as a line of comma-separated values, without a line break
+    pub fn to_csv(&self) -> String {
+        format!(
+            "{:.1},{:.1},{:.1},{:.6},{:.3},{:.6}",
+            self.centroid, self.bandwidth, self.rolloff, self.flatness, self.crest, self.flux
+        )
+    }
+}
+
+/// Writes spectral features over time as CSV, one row per frame with the seconds of audio since
+/// the export started (see [`FrameClock`])
+pub struct FeaturesCsv {
+    writer: Box<dyn Write>,
+    clock: FrameClock,
+}
+
+impl FeaturesCsv {
+    /// Create (or truncate) the CSV file at `path`
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path)
+            .map_err(|e| anyhow::anyhow!("Failed to create {}: {e}", path.display()))?;
+        Self::new(BufWriter::new(file))
+    }
+
+    /// Start writing CSV to `writer`, beginning with the header row
+    pub fn new(writer: impl Write + 'static) -> Result<Self> {
+        let mut writer: Box<dyn Write> = Box::new(writer);
+        writeln!(writer, "time_s,{}", SpectralFeatures::CSV_HEADER)?;
+        Ok(Self {
+            writer,
+            clock: FrameClock::default(),
+        })
+    }
+
+    /// Append a row for the features of `spectrum`, analysed from audio at `sample_rate`
+    pub fn write(&mut self, spectrum: &Spectrum, sample_rate: u32) -> Result<()> {
+        let time = self
+            .clock
+            .time(spectrum.frame, spectrum.fft_size, sample_rate);
+        writeln!(self.writer, "{time:.3},{}", spectrum.features.to_csv())?;
+        Ok(())
+    }
+
+    /// Write out any buffered rows
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Compute the features of the spectrum `magnitudes` of a `fft_size`-point FFT, ignoring the DC
+/// bin. Silence gives all zeros.
+///
+/// The flux needs the previous frame and is left at zero; the analysis thread fills it in from
+/// its beat tracker.
+pub fn spectral_features(
+    magnitudes: &[f32],
+    sample_rate: u32,
+    fft_size: usize,
+) -> SpectralFeatures {
+    let bins = magnitudes.get(1..).unwrap_or_default();
+    if bins.is_empty() || fft_size == 0 {
+        return SpectralFeatures::default();
+    }
+    let hz_per_bin = sample_rate as f32 / fft_size as f32;
+    let frequency = |index: usize| (index + 1) as f32 * hz_per_bin;
+
+    let mut magnitude_sum = 0.0;
+    let mut weighted_sum = 0.0;
+    let mut energy = 0.0;
+    let mut peak = 0.0f32;
+    let mut log_power_sum = 0.0;
+    // Normalise so the flatness floor is relative to full scale whatever the FFT size
+    let scale = 2.0 / fft_size as f32;
+    for (index, &magnitude) in bins.iter().enumerate() {
+        magnitude_sum += magnitude;
+        weighted_sum += frequency(index) * magnitude;
+        energy += magnitude * magnitude;
+        peak = peak.max(magnitude);
+        log_power_sum += ((magnitude * scale).powi(2) + FLATNESS_FLOOR).ln();
+    }
+    if magnitude_sum <= 0.0 {
+        return SpectralFeatures::default();
+    }
+
+    let centroid = weighted_sum / magnitude_sum;
+    let variance = bins
+        .iter()
+        .enumerate()
+        .map(|(index, magnitude)| (frequency(index) - centroid).powi(2) * magnitude)
+        .sum::<f32>()
+        / magnitude_sum;
+
+    let mut cumulative = 0.0;
+    let rolloff_bin = bins
+        .iter()
+        .position(|magnitude| {
+            cumulative += magnitude * magnitude;
+            cumulative >= ROLLOFF_FRACTION * energy
+        })
+        .unwrap_or(bins.len() - 1);
+
+    let count = bins.len() as f32;
+    let mean_power = energy * scale * scale / count + FLATNESS_FLOOR;
+    SpectralFeatures {
+        centroid,
+        bandwidth: variance.sqrt(),
+        rolloff: frequency(rolloff_bin),
+        flatness: ((log_power_sum / count).exp() / mean_power).min(1.0),
+        crest: peak / (magnitude_sum / count),
+        flux: 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::{Analyzer, Spectrum};
+
+    const SAMPLE_RATE: u32 = 48_000;
+    const FFT_SIZE: usize = 4096;
+
+    fn spectrum_of(signal: impl Fn(usize) -> f32) -> Vec<f32> {
+        let mut analyzer = Analyzer::new(FFT_SIZE, FFT_SIZE);
+        analyzer.advance(|samples| {
+            for (i, sample) in samples.iter_mut().enumerate() {
+                *sample = signal(i);
+            }
+        });
+        let mut spectrum = Spectrum::new(FFT_SIZE);
+        analyzer.compute(&mut spectrum);
+        spectrum.magnitudes
+    }
+
+    fn sine(frequency: f32) -> impl Fn(usize) -> f32 {
+        move |i| {
+            0.5 * (2.0 * std::f32::consts::PI * frequency * i as f32 / SAMPLE_RATE as f32).sin()
+        }
+    }
+
+    fn noise() -> Vec<f32> {
+        let mut state = 1u32;
+        (0..FFT_SIZE)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 8) as f32 / (1 << 24) as f32 - 0.5
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sine_is_tonal_and_centred_on_its_frequency() {
+        let features = spectral_features(&spectrum_of(sine(1000.0)), SAMPLE_RATE, FFT_SIZE);
+        assert!((features.centroid - 1000.0).abs() < 20.0, "{features:?}");
+        // Window leakage spreads the tone a little
+        assert!(features.bandwidth < 250.0, "{features:?}");
+        assert!((features.rolloff - 1000.0).abs() < 20.0, "{features:?}");
+        assert!(features.flatness < 0.01, "{features:?}");
+        assert!(features.crest > 100.0, "{features:?}");
+    }
+
+    #[test]
+    fn noise_is_flat_and_broadband() {
+        let noise = noise();
+        let features = spectral_features(&spectrum_of(|i| noise[i]), SAMPLE_RATE, FFT_SIZE);
+        // Half the Nyquist frequency, 85% of it, and e^-γ for exponentially distributed power
+        assert!(
+            (features.centroid - 12_000.0).abs() < 1000.0,
+            "{features:?}"
+        );
+        assert!((features.rolloff - 20_400.0).abs() < 1000.0, "{features:?}");
+        assert!((features.flatness - 0.56).abs() < 0.1, "{features:?}");
+        assert!(features.crest < 5.0, "{features:?}");
+    }
+
+    #[test]
+    fn brighter_sounds_have_higher_centroids() {
+        let low = spectral_features(&spectrum_of(sine(200.0)), SAMPLE_RATE, FFT_SIZE);
+        let high = spectral_features(&spectrum_of(sine(5000.0)), SAMPLE_RATE, FFT_SIZE);
+        assert!(high.centroid > low.centroid);
+        assert!(high.rolloff > low.rolloff);
+    }
+
+    #[test]
+    fn silence_has_no_features() {
+        assert_eq!(
+            spectral_features(&[0.0; 2049], SAMPLE_RATE, FFT_SIZE),
+            SpectralFeatures::default()
+        );
+        assert_eq!(
+            spectral_features(&[], SAMPLE_RATE, FFT_SIZE),
+            SpectralFeatures::default()
+        );
+    }
+
+    /// Writer whose contents stay readable after it is boxed
+    #[derive(Clone, Default)]
+    struct SharedBuffer(std::rc::Rc<std::cell::RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(data)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn csv_export_writes_a_header_and_a_row_per_frame() {
+        let buffer = SharedBuffer::default();
+        let mut csv = FeaturesCsv::new(buffer.clone()).unwrap();
+        let mut spectrum = Spectrum::new(1024);
+        csv.write(&spectrum, 8000).unwrap();
+        spectrum.frame = 16;
+        csv.write(&spectrum, 8000).unwrap();
+        csv.flush().unwrap();
+
+        let output = String::from_utf8(buffer.0.borrow().clone()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines[0],
+            "time_s,centroid_hz,bandwidth_hz,rolloff_hz,flatness,crest,flux"
+        );
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1], "0.000,0.0,0.0,0.0,0.000000,0.000,0.000000");
+        // 16 hops of 512 samples at 8 kHz later
+        assert!(lines[2].starts_with("1.024,"), "{}", lines[2]);
+    }
+
+    #[test]
+    fn csv_row_matches_header() {
+        let row = SpectralFeatures {
+            centroid: 1234.56,
+            ..SpectralFeatures::default()
+        }
+        .to_csv();
+        assert_eq!(row, "1234.6,0.0,0.0,0.000000,0.000,0.000000");
+        assert_eq!(
+            row.split(',').count(),
+            SpectralFeatures::CSV_HEADER.split(',').count()
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use ringbuf::{
+    HeapRb,
+    traits::{Consumer, Producer, Split},
+};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::analysis::Spectrum;
+
+/// Create a queue holding up to `length` copies of spectra of `fft_size`-sample frames.
+///
+/// Unlike the triple buffer, which only ever holds the latest spectrum, the queue passes on every
+/// frame, for consumers such as exports that must not miss any. Its buffers are allocated here
+/// and recycled, so the analysis side never allocates unless the FFT size grows.
+pub fn frame_queue(length: usize, fft_size: usize) -> (FrameTap, FrameQueue) {
+    let (filled_tx, filled_rx) = HeapRb::new(length).split();
+    let (mut free_tx, free_rx) = HeapRb::new(length).split();
+    for _ in 0..length {
+        let _ = free_tx.try_push(Spectrum::new(fft_size));
+    }
+    let dropped = Arc::new(AtomicU64::new(0));
+
+    (
+        FrameTap {
+            filled: filled_tx,
+            free: free_rx,
+            dropped: dropped.clone(),
+        },
+        FrameQueue {
+            filled: filled_rx,
+            free: free_tx,
+            dropped,
+            reported_dropped: 0,
+        },
+    )
+}
+
+/// Analysis side of a frame queue
+pub struct FrameTap {
+    filled: ringbuf::HeapProd<Spectrum>,
+    free: ringbuf::HeapCons<Spectrum>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl FrameTap {
+    /// Queue a copy of `spectrum`, or count it as dropped if the consumer fell too far behind
+    pub fn push(&mut self, spectrum: &Spectrum) {
+        match self.free.try_pop() {
+            Some(mut copy) => {
+                copy.copy_from(spectrum);
+                // Every buffer taken from `free` has room waiting in `filled`
+                let _ = self.filled.try_push(copy);
+            }
+            None => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Consuming side of a frame queue
+pub struct FrameQueue {
+    filled: ringbuf::HeapCons<Spectrum>,
+    free: ringbuf::HeapProd<Spectrum>,
+    dropped: Arc<AtomicU64>,
+    reported_dropped: u64,
+}
+
+impl FrameQueue {
+    /// Pass every queued spectrum to `f`, oldest first
+    pub fn drain(&mut self, mut f: impl FnMut(&Spectrum)) {
+        while let Some(spectrum) = self.filled.try_pop() {
+            f(&spectrum);
+            let _ = self.free.try_push(spectrum);
+        }
+    }
+
+    /// Number of spectra dropped since the last call because the queue was full
+    pub fn take_dropped(&mut self) -> u64 {
+        let dropped = self.dropped.load(Ordering::Relaxed);
+        let new = dropped - self.reported_dropped;
+        self.reported_dropped = dropped;
+        new
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spectrum(frame: u64) -> Spectrum {
+        let mut spectrum = Spectrum::new(4);
+        spectrum.frame = frame;
+        spectrum.magnitudes.fill(frame as f32);
+        spectrum
+    }
+
+    #[test]
+    fn every_frame_is_passed_on_in_order() {
+        let (mut tap, mut queue) = frame_queue(4, 4);
+        for frame in 0..3 {
+            tap.push(&spectrum(frame));
+        }
+        let mut frames = Vec::new();
+        queue.drain(|spectrum| frames.push((spectrum.frame, spectrum.magnitudes[0])));
+        assert_eq!(frames, [(0, 0.0), (1, 1.0), (2, 2.0)]);
+
+        // Buffers are recycled once drained
+        for frame in 3..7 {
+            tap.push(&spectrum(frame));
+        }
+        frames.clear();
+        queue.drain(|spectrum| frames.push((spectrum.frame, spectrum.magnitudes[0])));
+        assert_eq!(frames.len(), 4);
+        assert_eq!(queue.take_dropped(), 0);
+    }
+
+    #[test]
+    fn frames_beyond_the_length_are_counted_as_dropped() {
+        let (mut tap, mut queue) = frame_queue(2, 4);
+        for frame in 0..5 {
+            tap.push(&spectrum(frame));
+        }
+        let mut frames = Vec::new();
+        queue.drain(|spectrum| frames.push(spectrum.frame));
+        assert_eq!(frames, [0, 1]);
+        assert_eq!(queue.take_dropped(), 3);
+        assert_eq!(queue.take_dropped(), 0);
+    }
+
+    #[test]
+    fn spectra_of_another_size_are_copied_whole() {
+        let (mut tap, mut queue) = frame_queue(1, 4);
+        let mut large = Spectrum::new(16);
+        large.magnitudes[8] = 1.0;
+        tap.push(&large);
+        queue.drain(|spectrum| {
+            assert_eq!(spectrum.fft_size, 16);
+            assert_eq!(spectrum.magnitudes, large.magnitudes);
+        });
+    }
+}
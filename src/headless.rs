@@ -0,0 +1,93 @@
+use color_eyre::{Result, eyre::eyre};
+use std::time::Duration;
+use tokio::time;
+
+use crate::audio::{AudioProcessor, StreamEvent};
+use crate::config::Config;
+use crate::export::SpectrumExport;
+use crate::features::FeaturesCsv;
+
+/// How often queued spectra are written out; the frame queue holds far longer than this
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Capture from the host's default input device and export every analysed spectrum, and its
+/// features if `features_csv` is given, until interrupted with Ctrl+C, without the terminal UI.
+/// Problems are reported on standard error.
+pub async fn run(
+    config: Config,
+    host: cpal::Host,
+    mut export: SpectrumExport,
+    mut features_csv: Option<FeaturesCsv>,
+) -> Result<()> {
+    let mut processor =
+        AudioProcessor::new(&host, None, config.fft_size).map_err(|e| eyre!("{e}"))?;
+    processor.set_window(config.window());
+    processor.queue_frames();
+    eprintln!(
+        "Exporting spectra from {} ({} Hz); press Ctrl+C to stop",
+        processor.device_name(),
+        processor.sample_rate()
+    );
+
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    let mut interval = time::interval(POLL_INTERVAL);
+    let mut skipped_frames = 0;
+    loop {
+        tokio::select! {
+            _ = &mut ctrl_c => break,
+            _ = interval.tick() => {}
+        }
+
+        for event in processor.poll_events() {
+            if matches!(
+                event,
+                StreamEvent::DeviceLost | StreamEvent::AnalysisStopped
+            ) {
+                return Err(eyre!("{event}"));
+            }
+            eprintln!("Warning: {event}");
+        }
+
+        let sample_rate = processor.sample_rate();
+        let mut written = Ok(());
+        processor.drain_frames(|spectrum| {
+            if written.is_ok() {
+                written = export
+                    .write(spectrum, sample_rate, config.bar_count, config.sensitivity)
+                    .and_then(|()| match &mut features_csv {
+                        Some(csv) => csv.write(spectrum, sample_rate),
+                        None => Ok(()),
+                    });
+            }
+        });
+        match written {
+            Ok(()) => {}
+            // The reading end of a pipe went away, e.g. `| head`
+            Err(e) if is_broken_pipe(&e) => return Ok(()),
+            Err(e) => return Err(eyre!("Failed to export spectra: {e}")),
+        }
+        skipped_frames += processor.take_dropped_frames();
+    }
+
+    if skipped_frames > 0 {
+        eprintln!(
+            "Warning: {skipped_frames} spectra were analysed faster than they could be exported and were skipped"
+        );
+    }
+    if let Some(csv) = &mut features_csv {
+        csv.flush()
+            .map_err(|e| eyre!("Failed to export features: {e}"))?;
+    }
+    match export.flush() {
+        Err(e) if !is_broken_pipe(&e) => Err(eyre!("Failed to export spectra: {e}")),
+        _ => Ok(()),
+    }
+}
+
+/// Whether `error` was caused by writing to a closed pipe
+fn is_broken_pipe(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<std::io::Error>()
+        .is_some_and(|e| e.kind() == std::io::ErrorKind::BrokenPipe)
+}
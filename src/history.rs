@@ -0,0 +1,191 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::features::SpectralFeatures;
+
+/// A spectrum kept in the history, its features and when it arrived
+#[derive(Debug, Clone)]
+struct Frame {
+    time: Instant,
+    magnitudes: Vec<f32>,
+    features: SpectralFeatures,
+}
+
+/// The spectra of the last few seconds, each identified by a sequence number that counts up from
+/// the first one pushed and stays the same while the frame is kept
+#[derive(Debug, Clone)]
+pub struct SpectrumHistory {
+    frames: VecDeque<Frame>,
+    /// How long frames are kept after they arrive
+    duration: Duration,
+    /// Sequence number of the oldest frame kept
+    first: u64,
+}
+
+impl SpectrumHistory {
+    /// Create an empty history keeping frames for `duration`
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            frames: VecDeque::new(),
+            duration,
+            first: 0,
+        }
+    }
+
+    /// Add the spectrum `magnitudes` with its `features` that arrived at `time`, dropping frames
+    /// older than the history's duration. The newest frame is always kept.
+    pub fn push(&mut self, magnitudes: &[f32], features: SpectralFeatures, time: Instant) {
+        // Reuse the buffer of the frame falling off the end rather than allocating a new one
+        let mut buffer = None;
+        while self
+            .frames
+            .front()
+            .is_some_and(|frame| time.saturating_duration_since(frame.time) >= self.duration)
+        {
+            buffer = self.frames.pop_front().map(|frame| frame.magnitudes);
+            self.first += 1;
+        }
+
+        let mut magnitudes_buffer = buffer.unwrap_or_default();
+        magnitudes_buffer.clear();
+        magnitudes_buffer.extend_from_slice(magnitudes);
+        self.frames.push_back(Frame {
+            time,
+            magnitudes: magnitudes_buffer,
+            features,
+        });
+    }
+
+    /// Number of frames kept
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Whether no frame has been pushed yet
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Sequence number of the oldest frame kept
+    pub fn oldest(&self) -> u64 {
+        self.first
+    }
+
+    /// Sequence number of the newest frame, or of the next one if the history is empty
+    pub fn newest(&self) -> u64 {
+        (self.first + self.frames.len() as u64).saturating_sub(1)
+    }
+
+    /// Magnitudes of the newest frame; empty if the history is empty
+    pub fn latest(&self) -> &[f32] {
+        self.frames
+            .back()
+            .map_or(&[], |frame| frame.magnitudes.as_slice())
+    }
+
+    /// Spectral features of the newest frame; all zero if the history is empty
+    pub fn latest_features(&self) -> SpectralFeatures {
+        self.frames
+            .back()
+            .map_or_else(SpectralFeatures::default, |frame| frame.features)
+    }
+
+    /// Spectral features of frame `sequence`, if it is still kept
+    pub fn features(&self, sequence: u64) -> Option<SpectralFeatures> {
+        let index = usize::try_from(sequence.checked_sub(self.first)?).ok()?;
+        self.frames.get(index).map(|frame| frame.features)
+    }
+
+    /// Magnitudes of frame `sequence`, if it is still kept
+    pub fn get(&self, sequence: u64) -> Option<&[f32]> {
+        let index = usize::try_from(sequence.checked_sub(self.first)?).ok()?;
+        self.frames
+            .get(index)
+            .map(|frame| frame.magnitudes.as_slice())
+    }
+
+    /// How long before the newest frame frame `sequence` arrived, if it is still kept
+    pub fn age(&self, sequence: u64) -> Option<Duration> {
+        let index = usize::try_from(sequence.checked_sub(self.first)?).ok()?;
+        let frame = self.frames.get(index)?;
+        let newest = self.frames.back()?;
+        Some(newest.time.saturating_duration_since(frame.time))
+    }
+
+    /// The sequence number `steps` frames after `sequence` (before it if negative), limited to
+    /// the frames kept
+    pub fn step(&self, sequence: u64, steps: i64) -> u64 {
+        sequence
+            .saturating_add_signed(steps)
+            .clamp(self.oldest(), self.newest())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME_INTERVAL: Duration = Duration::from_millis(100);
+
+    /// History of one second holding frames `[0.0]`, `[1.0]`, ... pushed every 100 ms
+    fn history_of(count: usize) -> (SpectrumHistory, Instant) {
+        let mut history = SpectrumHistory::new(Duration::from_secs(1));
+        let start = Instant::now();
+        for i in 0..count {
+            let features = SpectralFeatures {
+                centroid: i as f32,
+                ..SpectralFeatures::default()
+            };
+            history.push(&[i as f32], features, start + FRAME_INTERVAL * i as u32);
+        }
+        (history, start)
+    }
+
+    #[test]
+    fn keeps_the_last_duration_of_frames() {
+        let (history, _) = history_of(25);
+        assert_eq!(history.len(), 10);
+        assert_eq!((history.oldest(), history.newest()), (15, 24));
+        assert_eq!(history.latest(), [24.0]);
+        assert_eq!(history.get(15), Some([15.0].as_slice()));
+        assert_eq!(history.get(14), None);
+        assert_eq!(history.get(25), None);
+        assert_eq!(history.latest_features().centroid, 24.0);
+        assert_eq!(history.features(15).map(|f| f.centroid), Some(15.0));
+        assert_eq!(history.features(14), None);
+        assert_eq!(history.age(15), Some(FRAME_INTERVAL * 9));
+        assert_eq!(history.age(24), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn steps_are_limited_to_the_frames_kept() {
+        let (history, _) = history_of(25);
+        assert_eq!(history.step(24, -3), 21);
+        assert_eq!(history.step(21, 2), 23);
+        assert_eq!(history.step(24, -100), 15);
+        assert_eq!(history.step(15, 100), 24);
+    }
+
+    #[test]
+    fn newest_frame_is_kept_after_a_gap() {
+        let (mut history, start) = history_of(3);
+        history.push(
+            &[9.0],
+            SpectralFeatures::default(),
+            start + Duration::from_secs(60),
+        );
+        assert_eq!(history.len(), 1);
+        assert_eq!(history.latest(), [9.0]);
+        assert_eq!(history.newest(), 3);
+        assert_eq!(history.oldest(), 3);
+    }
+
+    #[test]
+    fn empty_history() {
+        let history = SpectrumHistory::new(Duration::from_secs(1));
+        assert!(history.is_empty());
+        assert!(history.latest().is_empty());
+        assert_eq!(history.latest_features(), SpectralFeatures::default());
+        assert_eq!(history.get(0), None);
+    }
+}
@@ -0,0 +1,57 @@
+//! Audio capture and spectrum analysis for visualizers, plus the ratatui front end of the
+//! `audio-visualizer` binary (behind the default `tui` feature).
+//!
+//! ```no_run
+//! use audio_visualizer::{AudioProcessor, Config, get_best_audio_host, spectrum_to_bars};
+//!
+//! let config = Config::default();
+//! let mut processor = AudioProcessor::new(&get_best_audio_host(), None, config.fft_size)?;
+//! loop {
+//!     if let Some(spectrum) = processor.latest_spectrum() {
+//!         let bars = spectrum_to_bars(&spectrum.magnitudes, config.bar_count, config.sensitivity);
+//!         println!("{bars:?}");
+//!     }
+//!     std::thread::sleep(std::time::Duration::from_millis(50));
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+pub mod analysis;
+#[cfg(feature = "tui")]
+pub mod app;
+pub mod audio;
+pub mod averaging;
+pub mod axis;
+pub mod bands;
+pub mod bars;
+pub mod chroma;
+#[cfg(feature = "tui")]
+pub mod cli;
+pub mod config;
+pub mod devices;
+pub mod event_log;
+pub mod export;
+pub mod features;
+pub mod frame_queue;
+#[cfg(feature = "tui")]
+pub mod headless;
+pub mod history;
+pub mod loudness;
+pub mod measure;
+pub mod pitch;
+pub mod plot;
+pub mod recording;
+pub mod reference;
+pub mod rhythm;
+pub mod settings;
+pub mod snapshot;
+pub mod summary;
+pub mod triple_buffer;
+
+pub use analysis::{Analyzer, Spectrum, Window};
+pub use audio::{
+    AudioProcessor, StreamEvent, get_best_audio_host, get_host_by_name, get_input_devices,
+};
+pub use bands::{Band, band_levels};
+pub use bars::spectrum_to_bars;
+pub use config::Config;
@@ -0,0 +1,211 @@
+/// Length of the gating blocks in seconds
+const BLOCK_SECONDS: f64 = 0.4;
+
+/// Step between gating blocks in seconds (75% overlap)
+const STEP_SECONDS: f64 = 0.1;
+
+/// Blocks quieter than this are silence and ignored, in LUFS
+const ABSOLUTE_GATE: f64 = -70.0;
+
+/// Blocks more than this far below the ungated loudness are ignored, in LU
+const RELATIVE_GATE: f64 = -10.0;
+
+/// Offset that makes a 0 dBFS 1 kHz sine in one channel read -3.01 LUFS
+const LOUDNESS_OFFSET: f64 = -0.691;
+
+/// A second-order IIR filter section in direct form I
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self {
+            b,
+            a,
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
+
+/// The K-weighting of ITU-R BS.1770: a high shelf modelling the head followed by a high pass,
+/// designed for `sample_rate`
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+    let rate = sample_rate as f64;
+
+    let (f0, gain, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+    let k = (std::f64::consts::PI * f0 / rate).tan();
+    let vh = 10f64.powf(gain / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad::new(
+        [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    let (f0, q) = (38.13547087602444, 0.5003270373238773);
+    let k = (std::f64::consts::PI * f0 / rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad::new(
+        [1.0, -2.0, 1.0],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    [shelf, high_pass]
+}
+
+/// Weight of each channel in the loudness sum: the surround channels of a 5.1 layout count
+/// +1.5 dB and its LFE channel is left out; every other layout weighs all channels equally
+fn channel_weights(channels: usize) -> Vec<f64> {
+    if channels == 6 {
+        vec![1.0, 1.0, 1.0, 0.0, 1.41, 1.41]
+    } else {
+        vec![1.0; channels]
+    }
+}
+
+/// Integrated loudness in LUFS of `samples`, interleaved frames of `channels` channels at
+/// `sample_rate` Hz, measured with the gating of ITU-R BS.1770-4 / EBU R 128.
+///
+/// Returns `None` if the audio is shorter than one 400 ms block or every block is silent.
+pub fn integrated_loudness(samples: &[f32], channels: u16, sample_rate: u32) -> Option<f32> {
+    let channels = usize::from(channels);
+    if channels == 0 || sample_rate == 0 {
+        return None;
+    }
+    let frames = samples.len() / channels;
+    let block = (BLOCK_SECONDS * sample_rate as f64).round() as usize;
+    let step = (STEP_SECONDS * sample_rate as f64).round() as usize;
+    if frames < block || step == 0 {
+        return None;
+    }
+
+    // Squared K-weighted samples, summed per step so blocks of four steps can be added up
+    let steps = frames / step;
+    let mut step_energy = vec![vec![0.0f64; steps]; channels];
+    for (channel, energy) in step_energy.iter_mut().enumerate() {
+        let mut filters = k_weighting(sample_rate);
+        for (frame, &sample) in samples
+            .iter()
+            .skip(channel)
+            .step_by(channels)
+            .take(steps * step)
+            .enumerate()
+        {
+            let filtered = filters
+                .iter_mut()
+                .fold(f64::from(sample), |x, filter| filter.process(x));
+            energy[frame / step] += filtered * filtered;
+        }
+    }
+
+    // Mean square of each channel in each block
+    let steps_per_block = block / step;
+    let blocks: Vec<Vec<f64>> = (0..=steps - steps_per_block)
+        .map(|first| {
+            step_energy
+                .iter()
+                .map(|energy| {
+                    energy[first..first + steps_per_block].iter().sum::<f64>()
+                        / (steps_per_block * step) as f64
+                })
+                .collect()
+        })
+        .collect();
+
+    let weights = channel_weights(channels);
+    let loudness = |mean_squares: &[f64]| {
+        let sum: f64 = mean_squares.iter().zip(&weights).map(|(z, g)| z * g).sum();
+        LOUDNESS_OFFSET + 10.0 * sum.log10()
+    };
+    let gated_loudness = |gate: f64| {
+        let kept: Vec<&Vec<f64>> = blocks.iter().filter(|z| loudness(z) > gate).collect();
+        if kept.is_empty() {
+            return None;
+        }
+        let means: Vec<f64> = (0..channels)
+            .map(|channel| kept.iter().map(|z| z[channel]).sum::<f64>() / kept.len() as f64)
+            .collect();
+        Some(loudness(&means))
+    };
+
+    let ungated = gated_loudness(ABSOLUTE_GATE)?;
+    gated_loudness(ungated + RELATIVE_GATE)
+        .or(Some(ungated))
+        .map(|lufs| lufs as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48_000;
+
+    /// `seconds` of a 1 kHz sine at `level` dBFS in every one of `channels` channels
+    fn sine(level: f32, seconds: f32, channels: u16) -> Vec<f32> {
+        let amplitude = 10f32.powf(level / 20.0);
+        let frames = (seconds * SAMPLE_RATE as f32) as usize;
+        (0..frames)
+            .flat_map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                let sample = amplitude * (2.0 * std::f32::consts::PI * 1000.0 * t).sin();
+                std::iter::repeat_n(sample, usize::from(channels))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sine_reads_its_reference_loudness() {
+        // A 1 kHz sine reads 3 dB below its peak level per channel, and channels add up
+        let mono = integrated_loudness(&sine(-20.0, 5.0, 1), 1, SAMPLE_RATE).unwrap();
+        assert!((mono + 23.01).abs() < 0.1, "{mono}");
+        let stereo = integrated_loudness(&sine(-20.0, 5.0, 2), 2, SAMPLE_RATE).unwrap();
+        assert!((stereo + 20.0).abs() < 0.1, "{stereo}");
+    }
+
+    #[test]
+    fn quiet_passages_are_gated_out() {
+        // Adding silence and a much quieter passage leaves the loudness unchanged
+        let mut samples = sine(-20.0, 5.0, 1);
+        samples.extend(vec![0.0; 5 * SAMPLE_RATE as usize]);
+        samples.extend(sine(-45.0, 5.0, 1));
+        let lufs = integrated_loudness(&samples, 1, SAMPLE_RATE).unwrap();
+        assert!((lufs + 23.01).abs() < 0.2, "{lufs}");
+    }
+
+    #[test]
+    fn short_or_silent_audio_has_no_loudness() {
+        assert_eq!(
+            integrated_loudness(&sine(-20.0, 0.3, 1), 1, SAMPLE_RATE),
+            None
+        );
+        assert_eq!(integrated_loudness(&[0.0; 96_000], 1, SAMPLE_RATE), None);
+    }
+
+    #[test]
+    fn lfe_is_left_out() {
+        let mut samples = vec![0.0; 6 * SAMPLE_RATE as usize * 2];
+        for (i, sample) in sine(-20.0, 2.0, 1).into_iter().enumerate() {
+            samples[i * 6 + 3] = sample;
+        }
+        assert_eq!(integrated_loudness(&samples, 6, SAMPLE_RATE), None);
+    }
+}
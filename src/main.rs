@@ -1,448 +1,195 @@
-use color_eyre::Result;
-use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
-use ratatui::{
-    DefaultTerminal, Frame,
-    layout::{Alignment, Constraint, Direction, Layout},
-    style::{Color, Modifier, Style},
-    text::{Line, Span},
-    widgets::{BarChart, Block, Borders, Clear, Paragraph},
-};
-use std::time::{Duration, Instant};
-use tokio::time;
-
-mod audio;
-mod config;
-
-use audio::{AudioProcessor, get_input_devices};
-use config::Config;
+use clap::Parser;
+use color_eyre::eyre::eyre;
+use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
+
+use audio_visualizer::app::App;
+use audio_visualizer::audio::{get_best_audio_host, get_host_by_name};
+use audio_visualizer::cli::{AnalyzeArgs, Cli, Command, RenderArgs};
+use audio_visualizer::config::Config;
+use audio_visualizer::event_log::EventLog;
+use audio_visualizer::export::SpectrumExport;
+use audio_visualizer::features::FeaturesCsv;
+use audio_visualizer::headless;
+use audio_visualizer::settings::Settings;
+use audio_visualizer::snapshot::{spectrogram_plot, spectrum_plot};
+use audio_visualizer::summary::{AudioSummary, average_spectrum, read_wav, summarize_file};
+use serde_json::json;
+use std::path::Path;
+
+/// Exit status of `analyze` when a file exceeds one of the given limits
+const LIMIT_EXCEEDED_EXIT_CODE: i32 = 3;
 
 #[tokio::main]
 async fn main() -> color_eyre::Result<()> {
-    // Install a custom panic hook to handle ALSA timestamp panics gracefully
-    // This prevents the application from crashing due to cpal/ALSA issues
-    let original_hook = std::panic::take_hook();
-    std::panic::set_hook(Box::new(move |panic_info| {
-        let message = panic_info.to_string();
-
-        // Check if this is an ALSA timestamp panic
-        if message.contains("htstamp") || message.contains("trigger_htstamp") {
-            eprintln!(
-                "Warning: Audio backend encountered a timestamp issue. This is a known ALSA issue."
-            );
-            eprintln!(
-                "The audio stream may have stopped, but the application will continue running."
-            );
-            eprintln!("Try switching audio sources with 's' key if audio stops working.");
-            // Don't call the original hook to avoid terminating the process
-            return;
-        }
+    let cli = Cli::parse();
+    let log = match &cli.log_file {
+        Some(path) => EventLog::with_file(path)?,
+        None => EventLog::new(),
+    };
 
-        // For other panics, use the original handler
-        original_hook(panic_info);
-    }));
     color_eyre::install()?;
-    let terminal = ratatui::init();
-    let result = App::new()?.run(terminal).await;
-    ratatui::restore();
-    result
-}
 
-/// The main application which holds the state and logic of the application.
-pub struct App {
-    /// Is the application running?
-    running: bool,
-    /// Audio processor for capturing and analyzing audio
-    audio_processor: Option<AudioProcessor>,
-    /// Current configuration
-    config: Config,
-    /// Latest FFT data for visualization
-    fft_data: Vec<f32>,
-    /// Available audio input devices
-    available_devices: Vec<(String, cpal::Device)>,
-    /// Current device index
-    current_device_index: usize,
-    /// Last render time for FPS limiting
-    last_render: Instant,
-    /// Show help overlay
-    show_help: bool,
-}
-
-impl App {
-    /// Construct a new instance of [`App`].
-    pub fn new() -> Result<Self> {
-        let available_devices = get_input_devices().unwrap_or_default();
-        let current_device_index = 0;
-
-        // Try to initialize audio processor with default device
-        let audio_processor = if !available_devices.is_empty() {
-            match AudioProcessor::new(Some(available_devices[current_device_index].1.clone())) {
-                Ok(processor) => Some(processor),
-                Err(e) => {
-                    eprintln!(
-                        "Warning: Failed to initialize audio with selected device: {e}. Trying default device."
-                    );
-                    AudioProcessor::new(None).ok()
-                }
-            }
-        } else {
-            match AudioProcessor::new(None) {
-                Ok(processor) => Some(processor),
-                Err(e) => {
-                    eprintln!(
-                        "Warning: Failed to initialize audio: {e}. Visualizer will run without audio input."
-                    );
-                    None
-                }
-            }
-        };
-
-        Ok(Self {
-            running: false,
-            audio_processor,
-            config: Config::default(),
-            fft_data: vec![0.0; 512], // Initialize with zeros
-            available_devices,
-            current_device_index,
-            last_render: Instant::now(),
-            show_help: false,
-        })
+    match &cli.command {
+        Some(Command::Analyze(args)) => return analyze(args),
+        Some(Command::Render(args)) => return render(cli.config(), args),
+        None => {}
     }
 
-    /// Run the application's main loop.
-    pub async fn run(mut self, mut terminal: DefaultTerminal) -> Result<()> {
-        self.running = true;
-        let mut interval = time::interval(Duration::from_millis(self.config.refresh_rate));
-
-        while self.running {
-            interval.tick().await;
-
-            // Update FFT data if audio processor is available
-            if let Some(ref mut processor) = self.audio_processor {
-                if let Some(data) = processor.get_fft_data().await {
-                    self.fft_data = data;
-                }
+    // An explicitly requested host must exist; a remembered one may have gone away
+    let mut settings = Settings::load();
+    let host = match (&cli.host, &settings.host) {
+        (Some(name), _) => {
+            let host = get_host_by_name(name).map_err(|e| color_eyre::eyre::eyre!("{e}"))?;
+            settings.host = Some(host.id().name().to_string());
+            if let Err(e) = settings.save() {
+                log.warn(format!("Failed to save settings: {e}"));
             }
-
-            // Only render if enough time has passed for the configured refresh rate
-            if self.last_render.elapsed() >= Duration::from_millis(self.config.refresh_rate) {
-                terminal.draw(|frame| self.render(frame))?;
-                self.last_render = Instant::now();
-            }
-
-            // Handle events with timeout to avoid blocking
-            if crossterm::event::poll(Duration::from_millis(1))? {
-                self.handle_crossterm_events()?;
-            }
-        }
-        Ok(())
-    }
-
-    /// Renders the user interface.
-    fn render(&mut self, frame: &mut Frame) {
-        let chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([
-                Constraint::Length(3), // Title bar
-                Constraint::Min(0),    // Main visualization
-                Constraint::Length(3), // Status bar
-            ])
-            .split(frame.area());
-
-        // Render title
-        self.render_title(frame, chunks[0]);
-
-        // Render main visualization
-        self.render_visualizer(frame, chunks[1]);
-
-        // Render status bar
-        self.render_status(frame, chunks[2]);
-
-        // Render help overlay if requested
-        if self.show_help {
-            self.render_help_overlay(frame);
+            host
         }
+        (None, Some(name)) => get_host_by_name(name).unwrap_or_else(|e| {
+            log.warn(format!("{e}. Using the first host with input devices."));
+            get_best_audio_host()
+        }),
+        (None, None) => get_best_audio_host(),
+    };
+
+    // Standard output belongs to the terminal UI
+    if !cli.no_tui && cli.export.as_deref() == Some(Path::new("-")) {
+        return Err(eyre!("Exporting to standard output needs --no-tui"));
     }
 
-    /// Render the title bar
-    fn render_title(&self, frame: &mut Frame, area: ratatui::layout::Rect) {
-        let title = Line::from(vec![
-            Span::styled(
-                "Audio Visualizer ",
-                Style::default()
-                    .fg(Color::Cyan)
-                    .add_modifier(Modifier::BOLD),
-            ),
-            Span::styled("- Press 'h' for help", Style::default().fg(Color::Gray)),
-        ]);
-
-        let title_widget = Paragraph::new(title)
-            .block(Block::default().borders(Borders::ALL))
-            .alignment(Alignment::Center);
-
-        frame.render_widget(title_widget, area);
+    // Fail on an unwritable export file before starting to capture or taking over the terminal
+    let features_csv = cli
+        .features_csv
+        .as_deref()
+        .map(FeaturesCsv::create)
+        .transpose()
+        .map_err(|e| eyre!("{e}"))?;
+    if cli.no_tui {
+        let path = cli.export.as_deref().unwrap_or(Path::new("-"));
+        let export = SpectrumExport::create(path, cli.export_format, cli.export_data)
+            .map_err(|e| eyre!("{e}"))?;
+        return headless::run(cli.config(), host, export, features_csv).await;
     }
 
-    /// Render the main audio visualizer
-    fn render_visualizer(&self, frame: &mut Frame, area: ratatui::layout::Rect) {
-        // Prepare bar data for visualization
-        let bar_data = self.prepare_bar_data();
-
-        // Create bar chart with color based on current scheme
-        let bar_color = self.get_bar_color();
-        let bar_chart = BarChart::default()
-            .block(Block::default().borders(Borders::ALL).title(format!(
-                    "Frequency Spectrum ({}Hz) - {} bars - {} scheme", 
-                    self.audio_processor.as_ref().map(|p| p.sample_rate()).unwrap_or(44100),
-                    self.config.bar_count,
-                    self.config.color_scheme.name()
-                )))
-            .data(&bar_data)
-            .bar_width(std::cmp::max(
-                1u16,
-                ((area.width as usize - 2) / self.config.bar_count) as u16,
-            ))
-            .bar_gap(0)
-            .bar_style(Style::default().fg(bar_color))
-            .value_style(
-                Style::default()
-                    .fg(Color::White)
-                    .add_modifier(Modifier::BOLD),
-            );
+    let export = cli
+        .export
+        .as_deref()
+        .map(|path| SpectrumExport::create(path, cli.export_format, cli.export_data))
+        .transpose()
+        .map_err(|e| eyre!("{e}"))?;
 
-        frame.render_widget(bar_chart, area);
-    }
+    let terminal = ratatui::init();
+    // Mouse hover moves the spectrum cursor
+    crossterm::execute!(std::io::stdout(), EnableMouseCapture)?;
 
-    /// Get the primary color for bars based on the color scheme
-    fn get_bar_color(&self) -> Color {
-        use config::ColorScheme;
-        match self.config.color_scheme {
-            ColorScheme::Rainbow => Color::Magenta, // Use magenta as base for rainbow
-            ColorScheme::Blue => Color::Blue,
-            ColorScheme::Green => Color::Green,
-            ColorScheme::Red => Color::Red,
-            ColorScheme::Purple => Color::Magenta,
-            ColorScheme::Cyan => Color::Cyan,
-            ColorScheme::Yellow => Color::Yellow,
+    // Installed after ratatui's hook so panics can be routed into the event log
+    // rather than printed over the alternate screen
+    let original_hook = std::panic::take_hook();
+    let panic_log = log.clone();
+    std::panic::set_hook(Box::new(move |panic_info| {
+        let thread = std::thread::current();
+        let thread_name = thread.name().unwrap_or("unnamed");
+        panic_log.error(format!("Panic in thread '{thread_name}': {panic_info}"));
+
+        // A panicking background thread only takes down its own work, so keep the UI alive: the
+        // app restarts the stream when its analysis thread dies and the device thread when it
+        // does, and the stream watchdog reports a stalled audio backend thread
+        if thread_name != "main" {
+            return;
         }
-    }
-
-    /// Render the status bar
-    fn render_status(&self, frame: &mut Frame, area: ratatui::layout::Rect) {
-        let device_name = if !self.available_devices.is_empty()
-            && self.current_device_index < self.available_devices.len()
-        {
-            &self.available_devices[self.current_device_index].0
-        } else {
-            "No Device"
-        };
-
-        let status_text = format!(
-            "Device: {} | Bars: {} | FPS: {} | Sensitivity: {:.1} | Press 'q' to quit, 'h' for help",
-            device_name,
-            self.config.bar_count,
-            1000 / self.config.refresh_rate,
-            self.config.sensitivity
-        );
 
-        let status_widget = Paragraph::new(status_text)
-            .block(Block::default().borders(Borders::ALL))
-            .style(Style::default().fg(Color::Green))
-            .alignment(Alignment::Center);
+        let _ = crossterm::execute!(std::io::stdout(), DisableMouseCapture);
+        original_hook(panic_info);
+    }));
 
-        frame.render_widget(status_widget, area);
+    let mut app = App::new(cli.config(), host, log)?;
+    if let Some(csv) = features_csv {
+        app.set_features_csv(csv);
     }
-
-    /// Render help overlay
-    fn render_help_overlay(&self, frame: &mut Frame) {
-        let area = frame.area();
-        let popup_area = ratatui::layout::Rect {
-            x: area.width / 4,
-            y: area.height / 4,
-            width: area.width / 2,
-            height: area.height / 2,
-        };
-
-        let help_text = "\nKeyboard Controls:\n\n\
-            h - Toggle this help\n\
-            q, Esc, Ctrl+C - Quit\n\
-            c - Change color scheme\n\
-            + / = - Increase bars\n\
-            - / _ - Decrease bars\n\
-            r - Increase refresh rate\n\
-            R - Decrease refresh rate\n\
-            s - Switch audio source\n\
-            [ - Decrease sensitivity\n\
-            ] - Increase sensitivity\n\n\
-            Press any key to close help";
-
-        let help_widget = Paragraph::new(help_text)
-            .block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .title(" Help ")
-                    .style(Style::default().bg(Color::Black).fg(Color::White)),
-            )
-            .style(Style::default().bg(Color::Black).fg(Color::White));
-
-        frame.render_widget(Clear, popup_area);
-        frame.render_widget(help_widget, popup_area);
+    if let Some(export) = export {
+        app.set_export(export);
     }
+    let result = app.run(terminal).await;
+    let _ = crossterm::execute!(std::io::stdout(), DisableMouseCapture);
+    ratatui::restore();
+    result
+}
 
-    /// Prepare bar data for the bar chart widget with colored bars
-    fn prepare_bar_data(&self) -> Vec<(&str, u64)> {
-        let mut bar_data = Vec::with_capacity(self.config.bar_count);
-
-        // Calculate how many FFT bins to group per bar
-        let bins_per_bar = std::cmp::max(1, self.fft_data.len() / self.config.bar_count);
-
-        for i in 0..self.config.bar_count {
-            let start_idx = i * bins_per_bar;
-            let end_idx = std::cmp::min(start_idx + bins_per_bar, self.fft_data.len());
-
-            // Average the magnitude values in this frequency range
-            let avg_magnitude = if start_idx < self.fft_data.len() {
-                self.fft_data[start_idx..end_idx].iter().sum::<f32>() / (end_idx - start_idx) as f32
-            } else {
-                0.0
-            };
-
-            // Apply logarithmic scaling for better visual representation
-            let log_magnitude = if avg_magnitude > 0.0 {
-                (avg_magnitude.ln() + 10.0).max(0.0)
-            } else {
-                0.0
-            };
-
-            // Scale by sensitivity and convert to bar height (0-100)
-            let height = ((log_magnitude * self.config.sensitivity * 10.0) as u64).min(100);
-
-            // Use empty string for labels to save space
-            bar_data.push(("", height));
+/// Summarise each file on standard output, exiting with [`LIMIT_EXCEEDED_EXIT_CODE`] if any
+/// exceeds the limits in `args`
+fn analyze(args: &AnalyzeArgs) -> color_eyre::Result<()> {
+    let thresholds = args.thresholds();
+    let mut reports = Vec::new();
+    let mut exceeded = false;
+    for path in &args.files {
+        let summary = summarize_file(path).map_err(|e| eyre!("{e}"))?;
+        let violations = thresholds.violations(&summary);
+        exceeded |= !violations.is_empty();
+        if args.json {
+            let mut report = serde_json::to_value(summary)?;
+            report["path"] = json!(path);
+            report["violations"] = json!(violations);
+            reports.push(report);
+        } else {
+            print_summary(path, &summary, &violations);
         }
-
-        bar_data
     }
-
-    /// Reads the crossterm events and updates the state of [`App`].
-    ///
-    /// If your application needs to perform work in between handling events, you can use the
-    /// [`event::poll`] function to check if there are any events available with a timeout.
-    fn handle_crossterm_events(&mut self) -> Result<()> {
-        match event::read()? {
-            // it's important to check KeyEventKind::Press to avoid handling key release events
-            Event::Key(key) if key.kind == KeyEventKind::Press => self.on_key_event(key),
-            Event::Mouse(_) => {}
-            Event::Resize(_, _) => {}
-            _ => {}
-        }
-        Ok(())
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&reports)?);
     }
-
-    /// Handles the key events and updates the state of [`App`].
-    fn on_key_event(&mut self, key: KeyEvent) {
-        // Close help if it's open
-        if self.show_help {
-            self.show_help = false;
-            return;
-        }
-
-        match (key.modifiers, key.code) {
-            // Quit commands
-            (_, KeyCode::Esc | KeyCode::Char('q'))
-            | (KeyModifiers::CONTROL, KeyCode::Char('c') | KeyCode::Char('C')) => self.quit(),
-
-            // Help
-            (_, KeyCode::Char('h') | KeyCode::Char('H')) => {
-                self.show_help = true;
-            }
-
-            // Color scheme cycling
-            (_, KeyCode::Char('c') | KeyCode::Char('C')) => {
-                self.config.next_color_scheme();
-            }
-
-            // Bar count adjustment
-            (_, KeyCode::Char('+') | KeyCode::Char('=')) => {
-                self.config.increase_bar_count();
-            }
-            (_, KeyCode::Char('-') | KeyCode::Char('_')) => {
-                self.config.decrease_bar_count();
-            }
-
-            // Refresh rate adjustment
-            (_, KeyCode::Char('r')) => {
-                self.config.increase_refresh_rate();
-            }
-            (_, KeyCode::Char('R')) => {
-                self.config.decrease_refresh_rate();
-            }
-
-            // Sensitivity adjustment
-            (_, KeyCode::Char('[')) => {
-                self.config.decrease_sensitivity();
-            }
-            (_, KeyCode::Char(']')) => {
-                self.config.increase_sensitivity();
-            }
-
-            // Audio source switching
-            (_, KeyCode::Char('s') | KeyCode::Char('S')) => {
-                self.switch_audio_source();
-            }
-
-            _ => {}
-        }
+    if exceeded {
+        std::process::exit(LIMIT_EXCEEDED_EXIT_CODE);
     }
+    Ok(())
+}
 
-    /// Switch to the next available audio source
-    fn switch_audio_source(&mut self) {
-        if self.available_devices.is_empty() {
-            eprintln!("No audio devices available to switch to.");
-            return;
-        }
-
-        let old_device_index = self.current_device_index;
-        self.current_device_index = (self.current_device_index + 1) % self.available_devices.len();
-
-        // Try to create new audio processor with selected device
-        let device_clone = self.available_devices[self.current_device_index].1.clone();
-        let device_name = self.available_devices[self.current_device_index].0.clone();
-
-        // Drop the old audio processor first to ensure cleanup
-        self.audio_processor = None;
-
-        match AudioProcessor::new(Some(device_clone)) {
-            Ok(new_processor) => {
-                self.audio_processor = Some(new_processor);
-                eprintln!("Switched to audio device: {device_name}");
-            }
-            Err(e) => {
-                eprintln!(
-                    "Failed to switch to device '{device_name}': {e}. Trying to restart with previous device."
-                );
-                self.current_device_index = old_device_index;
-
-                // Try to recreate the old device
-                if let Some((_, old_device)) = self.available_devices.get(old_device_index).cloned()
-                {
-                    match AudioProcessor::new(Some(old_device)) {
-                        Ok(processor) => {
-                            self.audio_processor = Some(processor);
-                            eprintln!("Restored previous audio device.");
-                        }
-                        Err(_) => {
-                            eprintln!(
-                                "Could not restore previous audio device. Audio may not be available."
-                            );
-                        }
-                    }
-                }
-            }
-        }
+/// Print `summary` of the file at `path` as text, followed by the limits it exceeds
+fn print_summary(path: &Path, summary: &AudioSummary, violations: &[String]) {
+    let optional = |value: Option<f32>, unit: &str| {
+        value.map_or("-".to_string(), |value| format!("{value:.1} {unit}"))
+    };
+    println!("{}", path.display());
+    println!(
+        "  Duration:  {:.2} s ({} Hz, {} channel{})",
+        summary.duration_s,
+        summary.sample_rate,
+        summary.channels,
+        if summary.channels == 1 { "" } else { "s" }
+    );
+    println!("  Peak:      {:.1} dBFS", summary.peak_dbfs);
+    println!("  RMS:       {:.1} dBFS", summary.rms_dbfs);
+    println!("  Loudness:  {}", optional(summary.loudness_lufs, "LUFS"));
+    println!(
+        "  Dominant:  {}",
+        optional(summary.dominant_frequency_hz, "Hz")
+    );
+    println!("  Centroid:  {:.1} Hz", summary.spectral_centroid_hz);
+    for violation in violations {
+        println!("  FAIL: {violation}");
     }
+}
 
-    /// Set running to false to quit the application.
-    fn quit(&mut self) {
-        self.running = false;
-    }
+/// Render the average spectrum or the spectrogram of the file in `args` to an image, with the
+/// analysis settings in `config`
+fn render(config: Config, args: &RenderArgs) -> color_eyre::Result<()> {
+    let format = args.image_format().ok_or_else(|| {
+        eyre!(
+            "Cannot tell the image format of {}; name it .png or .svg, or pass --format",
+            args.output.display()
+        )
+    })?;
+    let (samples, channels, sample_rate) = read_wav(&args.input).map_err(|e| eyre!("{e}"))?;
+    let config = Config {
+        color_scheme: args.scheme,
+        show_db_axis: args.db_axis,
+        ..config
+    };
+    let plot = if args.spectrogram {
+        spectrogram_plot(&samples, channels, sample_rate, &config)
+    } else {
+        let magnitudes = average_spectrum(&samples, channels, config.fft_size);
+        spectrum_plot(&magnitudes, sample_rate, &config, None)
+    };
+    plot.save(&args.output, format).map_err(|e| eyre!("{e}"))
 }
@@ -0,0 +1,279 @@
+use crate::analysis::Window;
+
+/// Highest harmonic included in the distortion
+pub const MAX_HARMONIC: usize = 10;
+
+/// Lowest fundamental searched for, keeping clear of DC and rumble
+const MIN_FUNDAMENTAL: f32 = 20.0;
+
+/// Quietest fundamental measured, in dB relative to full scale
+const MIN_FUNDAMENTAL_DB: f32 = -100.0;
+
+/// A harmonic of the measured tone
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Harmonic {
+    /// Harmonic number, 2 for the first overtone
+    pub number: usize,
+    /// Frequency of the harmonic's peak in Hz
+    pub frequency: f32,
+    /// Level relative to the fundamental in dB
+    pub level: f32,
+}
+
+/// Distortion and noise of a test tone
+#[derive(Debug, Clone, PartialEq)]
+pub struct Distortion {
+    /// Frequency of the fundamental in Hz
+    pub frequency: f32,
+    /// Peak level of the fundamental in dB relative to a full-scale sine
+    pub level: f32,
+    /// Harmonics 2 to [`MAX_HARMONIC`] that lie below the Nyquist frequency
+    pub harmonics: Vec<Harmonic>,
+    /// Total harmonic distortion: harmonic over fundamental amplitude, in percent
+    pub thd: f32,
+    /// Everything but the fundamental over the fundamental amplitude, in percent
+    pub thd_n: f32,
+    /// Fundamental over noise (excluding harmonics) in dB
+    pub snr: f32,
+    /// Signal, noise and distortion over noise and distortion in dB
+    pub sinad: f32,
+    /// Whether the main lobes of the fundamental and the harmonics were all clear of each other.
+    /// If not, shared bins count only towards the lower one, so the harmonics and THD read low; a
+    /// larger FFT size separates them.
+    pub resolved: bool,
+}
+
+/// Measure the tone in the spectrum `magnitudes` of a `fft_size`-point FFT of frames weighted with
+/// `window`.
+///
+/// The strongest peak is taken as the fundamental; the energy of each harmonic is summed over the
+/// window's main lobe around the expected frequency, and everything else above DC is noise. Every
+/// bin counts towards one of them only, see [`Distortion::resolved`]. A flat-top window gives
+/// accurate levels wherever the tone falls between bins. Returns `None` if there is no tone, or
+/// it is too low to tell apart from DC at this FFT size.
+pub fn measure_distortion(
+    magnitudes: &[f32],
+    sample_rate: u32,
+    fft_size: usize,
+    window: Window,
+) -> Option<Distortion> {
+    if fft_size == 0 || magnitudes.is_empty() {
+        return None;
+    }
+    let hz_per_bin = sample_rate as f32 / fft_size as f32;
+    let lobe = window.main_lobe_bins();
+    let power = |bin: usize| magnitudes[bin] * magnitudes[bin];
+
+    // Leave out the DC lobe, which holds any offset rather than signal
+    let first = ((MIN_FUNDAMENTAL / hz_per_bin).ceil() as usize).max(lobe + 1);
+    let peak =
+        (first..magnitudes.len()).max_by(|&a, &b| magnitudes[a].total_cmp(&magnitudes[b]))?;
+    // Still rising into the DC lobe: the tone lies below the lowest bin searched
+    if peak == first && magnitudes[first - 1] > magnitudes[peak] {
+        return None;
+    }
+    let level =
+        20.0 * (magnitudes[peak] * 2.0 / (fft_size as f32 * window.coherent_gain())).log10();
+    if level <= MIN_FUNDAMENTAL_DB {
+        return None;
+    }
+
+    // Bins already counted towards the fundamental or a harmonic
+    let mut assigned = vec![false; magnitudes.len()];
+    // Energy and energy-weighted frequency of the bins of the main lobe around `bin` not counted
+    // yet, which are then marked as counted, and whether the whole lobe was still free
+    let mut take_lobe = |bin: usize| {
+        let bins = bin.saturating_sub(lobe)..(bin + lobe + 1).min(magnitudes.len());
+        let free = bins.clone().all(|bin| !assigned[bin]);
+        let (mut energy, mut weighted) = (0.0, 0.0);
+        for bin in bins {
+            if assigned[bin] {
+                continue;
+            }
+            energy += power(bin);
+            weighted += bin as f32 * power(bin);
+            assigned[bin] = true;
+        }
+        (
+            energy,
+            weighted / energy.max(f32::MIN_POSITIVE) * hz_per_bin,
+            free,
+        )
+    };
+
+    let (fundamental, frequency, _) = take_lobe(peak);
+
+    let mut harmonics = Vec::new();
+    let mut harmonic_energy = 0.0;
+    let mut resolved = true;
+    for number in 2..=MAX_HARMONIC {
+        let expected = (number as f32 * frequency / hz_per_bin).round() as usize;
+        if expected + lobe >= magnitudes.len() {
+            break;
+        }
+        // Track the harmonic's peak, which drifts from the exact multiple by up to a bin
+        let search = expected.saturating_sub(1)..=expected + 1;
+        let bin = search
+            .max_by(|&a, &b| magnitudes[a].total_cmp(&magnitudes[b]))
+            .unwrap_or(expected);
+        let (energy, harmonic_frequency, free) = take_lobe(bin);
+        resolved &= free;
+        harmonic_energy += energy;
+        harmonics.push(Harmonic {
+            number,
+            frequency: harmonic_frequency,
+            level: 10.0 * (energy / fundamental).max(f32::MIN_POSITIVE).log10(),
+        });
+    }
+
+    // Summed directly rather than subtracted from the total, which would cancel catastrophically
+    // for clean tones
+    let noise: f32 = (lobe + 1..magnitudes.len())
+        .filter(|&bin| !assigned[bin])
+        .map(power)
+        .sum();
+    let residual = harmonic_energy + noise;
+    let noise = noise.max(f32::MIN_POSITIVE);
+    Some(Distortion {
+        frequency,
+        level,
+        harmonics,
+        thd: 100.0 * (harmonic_energy / fundamental).sqrt(),
+        thd_n: 100.0 * (residual / fundamental).sqrt(),
+        snr: 10.0 * (fundamental / noise).log10(),
+        sinad: 10.0 * ((fundamental + residual) / residual.max(f32::MIN_POSITIVE)).log10(),
+        resolved,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::{Analyzer, Spectrum};
+
+    const SAMPLE_RATE: u32 = 48_000;
+    const FFT_SIZE: usize = 16384;
+
+    /// Spectrum of a 997 Hz tone at `amplitude` with harmonics at the given amplitudes relative to
+    /// the fundamental, plus uniform noise of `noise` peak amplitude
+    fn test_tone(amplitude: f32, harmonics: &[f32], noise: f32, window: Window) -> Vec<f32> {
+        tone_at(997.0, FFT_SIZE, amplitude, harmonics, noise, window)
+    }
+
+    /// Spectrum of a `fft_size`-point FFT of a tone at `frequency`, as for [`test_tone`]
+    fn tone_at(
+        frequency: f64,
+        fft_size: usize,
+        amplitude: f32,
+        harmonics: &[f32],
+        noise: f32,
+        window: Window,
+    ) -> Vec<f32> {
+        let mut state = 1u32;
+        let mut analyzer = Analyzer::new(fft_size, fft_size);
+        analyzer.set_window(window);
+        analyzer.advance(|samples| {
+            for (i, sample) in samples.iter_mut().enumerate() {
+                let phase = 2.0 * std::f64::consts::PI * frequency * i as f64 / SAMPLE_RATE as f64;
+                let tone = phase.sin()
+                    + harmonics
+                        .iter()
+                        .enumerate()
+                        .map(|(n, relative)| *relative as f64 * ((n + 2) as f64 * phase).sin())
+                        .sum::<f64>();
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                let dither = ((state >> 8) as f32 / (1 << 24) as f32 * 2.0 - 1.0) * noise;
+                *sample = amplitude * tone as f32 + dither;
+            }
+        });
+        let mut spectrum = Spectrum::new(fft_size);
+        analyzer.compute(&mut spectrum);
+        spectrum.magnitudes
+    }
+
+    #[test]
+    fn flat_top_reads_the_true_level() {
+        let spectrum = test_tone(0.5, &[], 0.0, Window::FlatTop);
+        let distortion =
+            measure_distortion(&spectrum, SAMPLE_RATE, FFT_SIZE, Window::FlatTop).unwrap();
+        assert!((distortion.frequency - 997.0).abs() < 0.5, "{distortion:?}");
+        assert!((distortion.level + 6.02).abs() < 0.05, "{distortion:?}");
+
+        // Hann scalloping loses up to 1.4 dB between bins
+        let spectrum = test_tone(0.5, &[], 0.0, Window::Hann);
+        let distortion =
+            measure_distortion(&spectrum, SAMPLE_RATE, FFT_SIZE, Window::Hann).unwrap();
+        assert!(distortion.level < -6.1, "{distortion:?}");
+    }
+
+    #[test]
+    fn measures_harmonic_distortion() {
+        // -40 dB second and -46 dB third harmonic: sqrt(0.01² + 0.005²) = 1.118%
+        let spectrum = test_tone(0.5, &[0.01, 0.005], 0.0, Window::FlatTop);
+        let distortion =
+            measure_distortion(&spectrum, SAMPLE_RATE, FFT_SIZE, Window::FlatTop).unwrap();
+        assert!((distortion.thd - 1.118).abs() < 0.02, "{distortion:?}");
+        assert!(
+            (distortion.thd_n - distortion.thd).abs() < 0.02,
+            "{distortion:?}"
+        );
+        assert!((distortion.sinad - 39.03).abs() < 0.2, "{distortion:?}");
+
+        let [second, third, ..] = distortion.harmonics[..] else {
+            panic!("missing harmonics: {distortion:?}");
+        };
+        assert_eq!(second.number, 2);
+        assert!((second.frequency - 1994.0).abs() < 1.0, "{second:?}");
+        assert!((second.level + 40.0).abs() < 0.2, "{second:?}");
+        assert!((third.level + 46.02).abs() < 0.2, "{third:?}");
+        assert_eq!(distortion.harmonics.len(), MAX_HARMONIC - 1);
+        assert!(distortion.resolved);
+    }
+
+    #[test]
+    fn measures_noise() {
+        // Uniform noise of peak 0.001 has an RMS of 0.001/√3, against 0.5/√2 for the tone
+        let spectrum = test_tone(0.5, &[], 0.001, Window::FlatTop);
+        let distortion =
+            measure_distortion(&spectrum, SAMPLE_RATE, FFT_SIZE, Window::FlatTop).unwrap();
+        assert!((distortion.snr - 55.7).abs() < 1.0, "{distortion:?}");
+        assert!(
+            (distortion.sinad - distortion.snr).abs() < 0.5,
+            "{distortion:?}"
+        );
+        assert!(distortion.thd < distortion.thd_n, "{distortion:?}");
+    }
+
+    #[test]
+    fn low_tones_count_overlapping_lobes_once() {
+        // At 23.4 Hz per bin the flat-top lobes of 250 Hz and 500 Hz share bins
+        let spectrum = tone_at(250.0, 2048, 0.5, &[0.01], 0.0, Window::FlatTop);
+        let distortion = measure_distortion(&spectrum, SAMPLE_RATE, 2048, Window::FlatTop).unwrap();
+        assert!(!distortion.resolved, "{distortion:?}");
+        assert!((distortion.frequency - 250.0).abs() < 1.0, "{distortion:?}");
+        // Shared bins are not counted twice, so THD does not read high and noise stays positive
+        assert!(distortion.thd <= 1.0 + 0.02, "{distortion:?}");
+        assert!(distortion.thd_n >= distortion.thd, "{distortion:?}");
+        assert!(distortion.snr < 200.0, "{distortion:?}");
+
+        // 100 Hz is within the DC lobe at this resolution, and resolved at a larger FFT size
+        let spectrum = tone_at(100.0, 2048, 0.5, &[0.01], 0.0, Window::FlatTop);
+        assert_eq!(
+            measure_distortion(&spectrum, SAMPLE_RATE, 2048, Window::FlatTop),
+            None
+        );
+        let spectrum = tone_at(100.0, FFT_SIZE, 0.5, &[0.01], 0.0, Window::FlatTop);
+        let distortion =
+            measure_distortion(&spectrum, SAMPLE_RATE, FFT_SIZE, Window::FlatTop).unwrap();
+        assert!(distortion.resolved, "{distortion:?}");
+        assert!((distortion.thd - 1.0).abs() < 0.02, "{distortion:?}");
+    }
+
+    #[test]
+    fn silence_has_no_tone() {
+        assert_eq!(
+            measure_distortion(&[0.0; 8193], SAMPLE_RATE, FFT_SIZE, Window::FlatTop),
+            None
+        );
+    }
+}
@@ -0,0 +1,204 @@
+/// Lowest fundamental the estimator looks for (a little below A0)
+pub const MIN_PITCH: f32 = 25.0;
+
+/// Highest fundamental the estimator looks for (a little above C8)
+pub const MAX_PITCH: f32 = 4500.0;
+
+/// Highest harmonic the strongest peak is assumed to be
+const HARMONICS: usize = 4;
+
+/// Magnitude relative to the strongest peak a subharmonic needs to count as the fundamental
+const SUBHARMONIC_RATIO: f32 = 0.1;
+
+/// Quietest peak, as a sine amplitude relative to full scale, considered a pitch (-60 dBFS)
+const MIN_AMPLITUDE: f32 = 0.001;
+
+/// Note names within an octave, starting from C
+pub const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// The equal-tempered note nearest to a frequency
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Note {
+    /// Note name, e.g. `"A"` or `"C#"`
+    pub name: &'static str,
+    /// Scientific pitch notation octave (A4 is the A above middle C)
+    pub octave: i32,
+    /// Deviation from the note in cents, from -50 to +50
+    pub cents: f32,
+}
+
+impl Note {
+    /// Find the note nearest to `frequency` in twelve-tone equal temperament tuned to `a4`
+    pub fn from_frequency(frequency: f32, a4: f32) -> Self {
+        let midi = midi_note(frequency, a4);
+        let nearest = midi.round();
+        let number = nearest as i32;
+        Self {
+            name: NOTE_NAMES[number.rem_euclid(12) as usize],
+            octave: number.div_euclid(12) - 1,
+            cents: (midi - nearest) * 100.0,
+        }
+    }
+}
+
+/// Fractional MIDI note number of `frequency` in equal temperament tuned to `a4` (A4 = 69)
+pub fn midi_note(frequency: f32, a4: f32) -> f32 {
+    69.0 + 12.0 * (frequency / a4).log2()
+}
+
+/// Estimate the fundamental frequency in Hz of the spectrum `magnitudes` (bins from DC up to
+/// Nyquist of a Hann-windowed `fft_size`-point FFT), or `None` if there is no clear pitch.
+///
+/// Finds the strongest spectral peak, then takes the lowest subharmonic of it (down to
+/// 1/[`HARMONICS`]) that also has a clear peak, so a fundamental that is weaker than its
+/// harmonics is still found. The peak is refined by interpolating between bins.
+pub fn detect_pitch(magnitudes: &[f32], sample_rate: u32, fft_size: usize) -> Option<f32> {
+    if magnitudes.len() < 3 || fft_size == 0 {
+        return None;
+    }
+    let hz_per_bin = sample_rate as f32 / fft_size as f32;
+    let min_bin = ((MIN_PITCH / hz_per_bin).ceil() as usize).max(1);
+    let max_bin = (MAX_PITCH / hz_per_bin) as usize;
+    let last_bin = magnitudes.len() - 2;
+    if min_bin > max_bin.min(last_bin) {
+        return None;
+    }
+
+    // The strongest peak may be any of the first few harmonics
+    let peak = (min_bin..=(max_bin * HARMONICS).min(last_bin))
+        .max_by(|&a, &b| magnitudes[a].total_cmp(&magnitudes[b]))?;
+
+    // A Hann-windowed sine of amplitude A peaks at about A * fft_size / 4
+    if magnitudes[peak] < MIN_AMPLITUDE * fft_size as f32 / 4.0 {
+        return None;
+    }
+
+    let fundamental = (1..=HARMONICS).rev().find_map(|harmonic| {
+        let candidate = (peak as f32 / harmonic as f32).round() as usize;
+        if candidate < min_bin || candidate > max_bin {
+            return None;
+        }
+        let bin = (candidate - 1..=candidate + 1)
+            .max_by(|&a, &b| magnitudes[a].total_cmp(&magnitudes[b]))?;
+        let is_peak =
+            magnitudes[bin] >= magnitudes[bin - 1] && magnitudes[bin] >= magnitudes[bin + 1];
+        (is_peak && magnitudes[bin] >= SUBHARMONIC_RATIO * magnitudes[peak]).then_some(bin)
+    })?;
+
+    Some((fundamental as f32 + peak_offset(magnitudes, fundamental)) * hz_per_bin)
+}
+
+/// Fractional offset (-0.5 to 0.5) of the true peak from `bin`, fitting a parabola through the
+/// log magnitudes of the bin and its neighbours
+pub(crate) fn peak_offset(magnitudes: &[f32], bin: usize) -> f32 {
+    let (Some(&left), Some(&right)) = (magnitudes.get(bin - 1), magnitudes.get(bin + 1)) else {
+        return 0.0;
+    };
+    let [left, centre, right] =
+        [left, magnitudes[bin], right].map(|m| m.max(f32::MIN_POSITIVE).ln());
+    let curvature = left - 2.0 * centre + right;
+    if curvature >= 0.0 {
+        return 0.0;
+    }
+    (0.5 * (left - right) / curvature).clamp(-0.5, 0.5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::{Analyzer, Spectrum};
+
+    const SAMPLE_RATE: u32 = 48_000;
+
+    /// Magnitudes of a tone at `frequency` with the given harmonic amplitudes
+    fn tone_spectrum(frequency: f32, harmonics: &[f32], fft_size: usize) -> Vec<f32> {
+        let signal: Vec<f32> = (0..fft_size)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                harmonics
+                    .iter()
+                    .enumerate()
+                    .map(|(n, amplitude)| {
+                        amplitude
+                            * (2.0 * std::f32::consts::PI * frequency * (n + 1) as f32 * t).sin()
+                    })
+                    .sum()
+            })
+            .collect();
+        let mut analyzer = Analyzer::new(fft_size, fft_size);
+        analyzer.advance(|samples| samples.copy_from_slice(&signal));
+        let mut spectrum = Spectrum::new(fft_size);
+        analyzer.compute(&mut spectrum);
+        spectrum.magnitudes
+    }
+
+    #[test]
+    fn detects_pure_tones() {
+        for frequency in [82.41, 110.0, 261.63, 440.0, 1318.5] {
+            let spectrum = tone_spectrum(frequency, &[0.5], 8192);
+            let detected = detect_pitch(&spectrum, SAMPLE_RATE, 8192).unwrap();
+            assert!(
+                (detected - frequency).abs() < 0.5,
+                "{frequency} Hz detected as {detected} Hz"
+            );
+        }
+    }
+
+    #[test]
+    fn small_ffts_stay_close() {
+        // 47 Hz bins at 1024 points; interpolation keeps the estimate within a few Hz
+        for frequency in [196.0, 440.0, 987.77] {
+            let spectrum = tone_spectrum(frequency, &[0.5], 1024);
+            let detected = detect_pitch(&spectrum, SAMPLE_RATE, 1024).unwrap();
+            assert!(
+                (detected - frequency).abs() < 2.0,
+                "{frequency} Hz detected as {detected} Hz"
+            );
+        }
+    }
+
+    #[test]
+    fn detects_weak_fundamentals() {
+        // A string-like tone whose second and third harmonics are louder than the fundamental
+        let spectrum = tone_spectrum(110.0, &[0.1, 0.4, 0.3, 0.2, 0.1], 8192);
+        let detected = detect_pitch(&spectrum, SAMPLE_RATE, 8192).unwrap();
+        assert!((detected - 110.0).abs() < 0.5, "detected {detected} Hz");
+    }
+
+    #[test]
+    fn ignores_silence() {
+        assert_eq!(detect_pitch(&[0.0; 4097], SAMPLE_RATE, 8192), None);
+        let quiet = tone_spectrum(440.0, &[0.0001], 8192);
+        assert_eq!(detect_pitch(&quiet, SAMPLE_RATE, 8192), None);
+    }
+
+    #[test]
+    fn names_notes() {
+        let note = Note::from_frequency(440.0, 440.0);
+        assert_eq!((note.name, note.octave), ("A", 4));
+        assert!(note.cents.abs() < 1e-3);
+
+        let note = Note::from_frequency(261.63, 440.0);
+        assert_eq!((note.name, note.octave), ("C", 4));
+
+        let note = Note::from_frequency(27.5, 440.0);
+        assert_eq!((note.name, note.octave), ("A", 0));
+
+        // 445 Hz is 19.6 cents sharp of A4
+        let note = Note::from_frequency(445.0, 440.0);
+        assert_eq!(note.name, "A");
+        assert!((note.cents - 19.56).abs() < 0.01);
+    }
+
+    #[test]
+    fn a4_reference_shifts_notes() {
+        let note = Note::from_frequency(432.0, 432.0);
+        assert_eq!((note.name, note.octave), ("A", 4));
+        assert!(note.cents.abs() < 1e-3);
+
+        let note = Note::from_frequency(440.0, 432.0);
+        assert!((note.cents - 31.77).abs() < 0.01);
+    }
+}
@@ -0,0 +1,514 @@
+use anyhow::Result;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::Write as _;
+use std::path::Path;
+
+/// An sRGB colour
+pub type Rgb = [u8; 3];
+
+/// Width and height of a glyph of the built-in font, in font pixels
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+
+/// Image pixels per font pixel
+const TEXT_SCALE: u32 = 2;
+
+/// Horizontal distance between the starts of consecutive characters in image pixels
+pub const CHAR_WIDTH: u32 = (GLYPH_WIDTH + 1) * TEXT_SCALE;
+
+/// Height of a line of text in image pixels
+pub const TEXT_HEIGHT: u32 = GLYPH_HEIGHT * TEXT_SCALE;
+
+/// File format an image is saved in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "tui", derive(clap::ValueEnum))]
+pub enum ImageFormat {
+    /// Lossless bitmap
+    #[default]
+    Png,
+    /// Scalable vector graphics
+    Svg,
+}
+
+impl ImageFormat {
+    /// File name extension, without the dot
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Svg => "svg",
+        }
+    }
+
+    /// The format named by the extension of `path`, if it is a known one
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?;
+        [ImageFormat::Png, ImageFormat::Svg]
+            .into_iter()
+            .find(|format| extension.eq_ignore_ascii_case(format.extension()))
+    }
+}
+
+/// Which point of a text its position refers to horizontally
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anchor {
+    Start,
+    Middle,
+    End,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Shape {
+    Rect {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        color: Rgb,
+    },
+    Text {
+        /// Left edge after anchoring; may lie outside the image
+        x: i64,
+        y: u32,
+        text: String,
+        color: Rgb,
+    },
+}
+
+/// A picture made of filled rectangles and single-line text, which can be written as an SVG
+/// document or rasterised to a PNG with the same layout.
+///
+/// Text is set in a built-in 5×7 pixel font (printable ASCII), so it measures the same in both
+/// formats; see [`text_width`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plot {
+    width: u32,
+    height: u32,
+    background: Rgb,
+    /// Drawn in order, later shapes on top
+    shapes: Vec<Shape>,
+}
+
+impl Plot {
+    /// Create an empty picture of `width` × `height` pixels filled with `background`
+    pub fn new(width: u32, height: u32, background: Rgb) -> Self {
+        Self {
+            width,
+            height,
+            background,
+            shapes: Vec::new(),
+        }
+    }
+
+    /// Width in pixels
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height in pixels
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Fill the rectangle with its top left corner at (`x`, `y`)
+    pub fn fill_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: Rgb) {
+        if width > 0 && height > 0 {
+            self.shapes.push(Shape::Rect {
+                x,
+                y,
+                width,
+                height,
+                color,
+            });
+        }
+    }
+
+    /// Write `text` with its top at `y`, starting, centred or ending at `x` depending on `anchor`
+    pub fn text(&mut self, x: u32, y: u32, text: impl Into<String>, color: Rgb, anchor: Anchor) {
+        let text = text.into();
+        let width = i64::from(text_width(&text));
+        let x = match anchor {
+            Anchor::Start => i64::from(x),
+            Anchor::Middle => i64::from(x) - width / 2,
+            Anchor::End => i64::from(x) - width,
+        };
+        self.shapes.push(Shape::Text { x, y, text, color });
+    }
+
+    /// The picture as an SVG document
+    pub fn to_svg(&self) -> String {
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n",
+            self.width, self.height
+        );
+        let _ = writeln!(
+            svg,
+            "<rect width=\"100%\" height=\"100%\" fill=\"{}\"/>",
+            hex(self.background)
+        );
+        for shape in &self.shapes {
+            let _ = match shape {
+                Shape::Rect {
+                    x,
+                    y,
+                    width,
+                    height,
+                    color,
+                } => writeln!(
+                    svg,
+                    "<rect x=\"{x}\" y=\"{y}\" width=\"{width}\" height=\"{height}\" fill=\"{}\"/>",
+                    hex(*color)
+                ),
+                // Stretched to the width of the built-in font so layouts hold with any font
+                Shape::Text { x, y, text, color } => writeln!(
+                    svg,
+                    "<text x=\"{x}\" y=\"{}\" font-family=\"monospace\" font-size=\"{}\" textLength=\"{}\" lengthAdjust=\"spacingAndGlyphs\" fill=\"{}\">{}</text>",
+                    y + TEXT_HEIGHT,
+                    TEXT_HEIGHT * 4 / 3,
+                    text_width(text),
+                    hex(*color),
+                    escape_xml(text)
+                ),
+            };
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// The picture rasterised as rows of RGB pixels, top to bottom
+    pub fn to_pixels(&self) -> Vec<u8> {
+        let (width, height) = (self.width as usize, self.height as usize);
+        let mut pixels = self.background.repeat(width * height);
+        let mut fill = |x: i64, y: i64, w: u32, h: u32, color: Rgb| {
+            let left = x.clamp(0, width as i64) as usize;
+            let right = (x + i64::from(w)).clamp(0, width as i64) as usize;
+            let top = y.clamp(0, height as i64) as usize;
+            let bottom = (y + i64::from(h)).clamp(0, height as i64) as usize;
+            for row in top..bottom {
+                for pixel in
+                    pixels[(row * width + left) * 3..(row * width + right) * 3].chunks_exact_mut(3)
+                {
+                    pixel.copy_from_slice(&color);
+                }
+            }
+        };
+
+        for shape in &self.shapes {
+            match shape {
+                Shape::Rect {
+                    x,
+                    y,
+                    width,
+                    height,
+                    color,
+                } => fill(i64::from(*x), i64::from(*y), *width, *height, *color),
+                Shape::Text { x, y, text, color } => {
+                    for (index, c) in text.chars().enumerate() {
+                        let left = x + (index as u32 * CHAR_WIDTH) as i64;
+                        for (row, line) in glyph(c).iter().enumerate() {
+                            for (column, _) in line.bytes().enumerate().filter(|&(_, b)| b == b'#')
+                            {
+                                fill(
+                                    left + (column as u32 * TEXT_SCALE) as i64,
+                                    i64::from(y + row as u32 * TEXT_SCALE),
+                                    TEXT_SCALE,
+                                    TEXT_SCALE,
+                                    *color,
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        pixels
+    }
+
+    /// The picture as a PNG file
+    pub fn to_png(&self) -> Vec<u8> {
+        let row_length = self.width as usize * 3;
+        let pixels = self.to_pixels();
+        // Every row starts with its filter type, none
+        let mut raw = Vec::with_capacity((row_length + 1) * self.height as usize);
+        for row in pixels.chunks_exact(row_length.max(1)) {
+            raw.push(0);
+            raw.extend_from_slice(row);
+        }
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&self.width.to_be_bytes());
+        header.extend_from_slice(&self.height.to_be_bytes());
+        // 8 bits per channel, RGB, deflate, adaptive filtering, no interlacing
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        write_png_chunk(&mut png, b"IHDR", &header);
+        write_png_chunk(
+            &mut png,
+            b"IDAT",
+            &miniz_oxide::deflate::compress_to_vec_zlib(&raw, 6),
+        );
+        write_png_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    /// Write the picture to `path` in `format`
+    pub fn save(&self, path: &Path, format: ImageFormat) -> Result<()> {
+        std::fs::write(path, self.encode(format))
+            .map_err(|e| anyhow::anyhow!("Failed to write {}: {e}", path.display()))
+    }
+
+    /// Write the picture to a new file at `path` in `format`, failing if the file exists
+    pub fn save_new(&self, path: &Path, format: ImageFormat) -> Result<()> {
+        File::create_new(path)
+            .and_then(|mut file| file.write_all(&self.encode(format)))
+            .map_err(|e| anyhow::anyhow!("Failed to write {}: {e}", path.display()))
+    }
+
+    /// The picture encoded in `format`
+    fn encode(&self, format: ImageFormat) -> Vec<u8> {
+        match format {
+            ImageFormat::Png => self.to_png(),
+            ImageFormat::Svg => self.to_svg().into_bytes(),
+        }
+    }
+}
+
+/// Width in pixels of `text` set in the built-in font
+pub fn text_width(text: &str) -> u32 {
+    (text.chars().count() as u32 * CHAR_WIDTH).saturating_sub(TEXT_SCALE)
+}
+
+/// `color` as an SVG hex colour, e.g. `#ff00ff`
+fn hex([r, g, b]: Rgb) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+/// `text` with the characters that are special in XML escaped
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Append a PNG chunk of type `kind` holding `data`
+fn write_png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// The CRC-32 (ISO-HDLC) checksum PNG chunks end with
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Rows of the glyph for `c`, `#` for set pixels; characters without a glyph are blank
+fn glyph(c: char) -> &'static [&'static str; GLYPH_HEIGHT as usize] {
+    FONT.iter()
+        .find(|(glyph, _)| *glyph == c)
+        .map_or(&FONT[0].1, |(_, rows)| rows)
+}
+
+/// 5×7 pixel font covering printable ASCII apart from a few rarely needed symbols; the first
+/// glyph, a space, stands in for the rest
+#[rustfmt::skip]
+const FONT: &[(char, [&str; GLYPH_HEIGHT as usize])] = &[
+    (' ', [".....", ".....", ".....", ".....", ".....", ".....", "....."]),
+    ('0', [".###.", "#...#", "#..##", "#.#.#", "##..#", "#...#", ".###."]),
+    ('1', ["..#..", ".##..", "..#..", "..#..", "..#..", "..#..", ".###."]),
+    ('2', [".###.", "#...#", "....#", "...#.", "..#..", ".#...", "#####"]),
+    ('3', ["####.", "....#", "....#", ".###.", "....#", "....#", "####."]),
+    ('4', ["...#.", "..##.", ".#.#.", "#..#.", "#####", "...#.", "...#."]),
+    ('5', ["#####", "#....", "####.", "....#", "....#", "#...#", ".###."]),
+    ('6', ["..##.", ".#...", "#....", "####.", "#...#", "#...#", ".###."]),
+    ('7', ["#####", "....#", "...#.", "..#..", ".#...", ".#...", ".#..."]),
+    ('8', [".###.", "#...#", "#...#", ".###.", "#...#", "#...#", ".###."]),
+    ('9', [".###.", "#...#", "#...#", ".####", "....#", "...#.", ".##.."]),
+    ('A', [".###.", "#...#", "#...#", "#####", "#...#", "#...#", "#...#"]),
+    ('B', ["####.", "#...#", "#...#", "####.", "#...#", "#...#", "####."]),
+    ('C', [".###.", "#...#", "#....", "#....", "#....", "#...#", ".###."]),
+    ('D', ["####.", "#...#", "#...#", "#...#", "#...#", "#...#", "####."]),
+    ('E', ["#####", "#....", "#....", "####.", "#....", "#....", "#####"]),
+    ('F', ["#####", "#....", "#....", "####.", "#....", "#....", "#...."]),
+    ('G', [".###.", "#...#", "#....", "#.###", "#...#", "#...#", ".####"]),
+    ('H', ["#...#", "#...#", "#...#", "#####", "#...#", "#...#", "#...#"]),
+    ('I', [".###.", "..#..", "..#..", "..#..", "..#..", "..#..", ".###."]),
+    ('J', ["..###", "...#.", "...#.", "...#.", "...#.", "#..#.", ".##.."]),
+    ('K', ["#...#", "#..#.", "#.#..", "##...", "#.#..", "#..#.", "#...#"]),
+    ('L', ["#....", "#....", "#....", "#....", "#....", "#....", "#####"]),
+    ('M', ["#...#", "##.##", "#.#.#", "#.#.#", "#...#", "#...#", "#...#"]),
+    ('N', ["#...#", "#...#", "##..#", "#.#.#", "#..##", "#...#", "#...#"]),
+    ('O', [".###.", "#...#", "#...#", "#...#", "#...#", "#...#", ".###."]),
+    ('P', ["####.", "#...#", "#...#", "####.", "#....", "#....", "#...."]),
+    ('Q', [".###.", "#...#", "#...#", "#...#", "#.#.#", "#..#.", ".##.#"]),
+    ('R', ["####.", "#...#", "#...#", "####.", "#.#..", "#..#.", "#...#"]),
+    ('S', [".####", "#....", "#....", ".###.", "....#", "....#", "####."]),
+    ('T', ["#####", "..#..", "..#..", "..#..", "..#..", "..#..", "..#.."]),
+    ('U', ["#...#", "#...#", "#...#", "#...#", "#...#", "#...#", ".###."]),
+    ('V', ["#...#", "#...#", "#...#", "#...#", "#...#", ".#.#.", "..#.."]),
+    ('W', ["#...#", "#...#", "#...#", "#.#.#", "#.#.#", "#.#.#", ".#.#."]),
+    ('X', ["#...#", "#...#", ".#.#.", "..#..", ".#.#.", "#...#", "#...#"]),
+    ('Y', ["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#..", "..#.."]),
+    ('Z', ["#####", "....#", "...#.", "..#..", ".#...", "#....", "#####"]),
+    ('a', [".....", ".....", ".###.", "....#", ".####", "#...#", ".####"]),
+    ('b', ["#....", "#....", "#.##.", "##..#", "#...#", "#...#", "####."]),
+    ('c', [".....", ".....", ".###.", "#....", "#....", "#...#", ".###."]),
+    ('d', ["....#", "....#", ".##.#", "#..##", "#...#", "#...#", ".####"]),
+    ('e', [".....", ".....", ".###.", "#...#", "#####", "#....", ".###."]),
+    ('f', ["..##.", ".#..#", ".#...", "###..", ".#...", ".#...", ".#..."]),
+    ('g', [".....", ".####", "#...#", "#...#", ".####", "....#", ".###."]),
+    ('h', ["#....", "#....", "#.##.", "##..#", "#...#", "#...#", "#...#"]),
+    ('i', ["..#..", ".....", ".##..", "..#..", "..#..", "..#..", ".###."]),
+    ('j', ["...#.", ".....", "..##.", "...#.", "...#.", "#..#.", ".##.."]),
+    ('k', ["#....", "#....", "#..#.", "#.#..", "##...", "#.#..", "#..#."]),
+    ('l', [".##..", "..#..", "..#..", "..#..", "..#..", "..#..", ".###."]),
+    ('m', [".....", ".....", "##.#.", "#.#.#", "#.#.#", "#...#", "#...#"]),
+    ('n', [".....", ".....", "#.##.", "##..#", "#...#", "#...#", "#...#"]),
+    ('o', [".....", ".....", ".###.", "#...#", "#...#", "#...#", ".###."]),
+    ('p', [".....", ".....", "####.", "#...#", "####.", "#....", "#...."]),
+    ('q', [".....", ".....", ".##.#", "#..##", ".####", "....#", "....#"]),
+    ('r', [".....", ".....", "#.##.", "##..#", "#....", "#....", "#...."]),
+    ('s', [".....", ".....", ".###.", "#....", ".###.", "....#", "####."]),
+    ('t', [".#...", ".#...", "###..", ".#...", ".#...", ".#..#", "..##."]),
+    ('u', [".....", ".....", "#...#", "#...#", "#...#", "#..##", ".##.#"]),
+    ('v', [".....", ".....", "#...#", "#...#", "#...#", ".#.#.", "..#.."]),
+    ('w', [".....", ".....", "#...#", "#...#", "#.#.#", "#.#.#", ".#.#."]),
+    ('x', [".....", ".....", "#...#", ".#.#.", "..#..", ".#.#.", "#...#"]),
+    ('y', [".....", ".....", "#...#", "#...#", ".####", "....#", ".###."]),
+    ('z', [".....", ".....", "#####", "...#.", "..#..", ".#...", "#####"]),
+    ('-', [".....", ".....", ".....", "#####", ".....", ".....", "....."]),
+    ('+', [".....", "..#..", "..#..", "#####", "..#..", "..#..", "....."]),
+    ('=', [".....", ".....", "#####", ".....", "#####", ".....", "....."]),
+    ('.', [".....", ".....", ".....", ".....", ".....", ".##..", ".##.."]),
+    (',', [".....", ".....", ".....", ".....", ".##..", "..#..", ".#..."]),
+    (':', [".....", ".##..", ".##..", ".....", ".##..", ".##..", "....."]),
+    ('(', ["...#.", "..#..", ".#...", ".#...", ".#...", "..#..", "...#."]),
+    (')', [".#...", "..#..", "...#.", "...#.", "...#.", "..#..", ".#..."]),
+    ('/', [".....", "....#", "...#.", "..#..", ".#...", "#....", "....."]),
+    ('%', ["##...", "##..#", "...#.", "..#..", ".#...", "#..##", "...##"]),
+    ('_', [".....", ".....", ".....", ".....", ".....", ".....", "#####"]),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Rgb = [255, 0, 0];
+    const WHITE: Rgb = [255, 255, 255];
+
+    fn pixel(plot: &Plot, x: u32, y: u32) -> Rgb {
+        let index = ((y * plot.width() + x) * 3) as usize;
+        plot.to_pixels()[index..index + 3].try_into().unwrap()
+    }
+
+    #[test]
+    fn rectangles_and_text_are_rasterised() {
+        let mut plot = Plot::new(40, 20, [0; 3]);
+        plot.fill_rect(30, 10, 20, 20, RED);
+        plot.text(0, 0, "1", WHITE, Anchor::Start);
+        assert_eq!(pixel(&plot, 35, 15), RED);
+        assert_eq!(pixel(&plot, 29, 15), [0; 3]);
+        // The stem of the 1 is the middle column of its glyph
+        assert_eq!(pixel(&plot, 2 * TEXT_SCALE, 3 * TEXT_SCALE), WHITE);
+        assert_eq!(pixel(&plot, 0, 3 * TEXT_SCALE), [0; 3]);
+    }
+
+    #[test]
+    fn text_is_anchored() {
+        assert_eq!(text_width("10k"), 3 * CHAR_WIDTH - TEXT_SCALE);
+        let mut plot = Plot::new(40, 20, [0; 3]);
+        plot.text(40, 0, "-", WHITE, Anchor::End);
+        assert_eq!(pixel(&plot, 39 - TEXT_SCALE, 3 * TEXT_SCALE), WHITE);
+        assert_eq!(pixel(&plot, 39 - text_width("-"), 3 * TEXT_SCALE), [0; 3]);
+    }
+
+    #[test]
+    fn png_holds_the_pixels() {
+        let mut plot = Plot::new(3, 2, [0, 0, 255]);
+        plot.fill_rect(1, 1, 1, 1, RED);
+        let png = plot.to_png();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+
+        // Walk the chunks, checking each checksum and collecting the image data
+        let mut offset = 8;
+        let mut kinds = Vec::new();
+        let mut data = Vec::new();
+        while offset < png.len() {
+            let length = u32::from_be_bytes(png[offset..offset + 4].try_into().unwrap()) as usize;
+            let chunk = &png[offset + 4..offset + 8 + length];
+            let crc = u32::from_be_bytes(
+                png[offset + 8 + length..offset + 12 + length]
+                    .try_into()
+                    .unwrap(),
+            );
+            assert_eq!(crc32(chunk), crc);
+            kinds.push(String::from_utf8(chunk[..4].to_vec()).unwrap());
+            if &chunk[..4] == b"IDAT" {
+                data.extend_from_slice(&chunk[4..]);
+            }
+            offset += 12 + length;
+        }
+        assert_eq!(kinds, ["IHDR", "IDAT", "IEND"]);
+
+        let raw = miniz_oxide::inflate::decompress_to_vec_zlib(&data).unwrap();
+        assert_eq!(
+            raw,
+            [
+                0, 0, 0, 255, 0, 0, 255, 0, 0, 255, 0, 0, 0, 255, 255, 0, 0, 0, 0, 255
+            ]
+        );
+    }
+
+    #[test]
+    fn crc_matches_the_standard_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn svg_holds_the_shapes() {
+        let mut plot = Plot::new(100, 50, [0; 3]);
+        plot.fill_rect(10, 20, 30, 5, RED);
+        plot.text(50, 0, "a<b", WHITE, Anchor::Middle);
+        let svg = plot.to_svg();
+        assert!(
+            svg.starts_with(
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"100\" height=\"50\""
+            )
+        );
+        assert!(
+            svg.contains("<rect x=\"10\" y=\"20\" width=\"30\" height=\"5\" fill=\"#ff0000\"/>")
+        );
+        assert!(svg.contains(&format!("x=\"{}\"", 50 - text_width("a<b") / 2)));
+        assert!(svg.contains(">a&lt;b</text>"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+    }
+
+    #[test]
+    fn formats_follow_the_extension() {
+        assert_eq!(
+            ImageFormat::from_path(Path::new("a.PNG")),
+            Some(ImageFormat::Png)
+        );
+        assert_eq!(
+            ImageFormat::from_path(Path::new("a.svg")),
+            Some(ImageFormat::Svg)
+        );
+        assert_eq!(ImageFormat::from_path(Path::new("a.jpg")), None);
+        assert_eq!(ImageFormat::from_path(Path::new("a")), None);
+    }
+}
@@ -0,0 +1,230 @@
+use anyhow::Result;
+use hound::{SampleFormat, WavSpec, WavWriter};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::event_log::format_file_timestamp;
+
+/// File name for a recording started at `time`, e.g. `recording-20240131-235959.wav`
+pub fn recording_file_name(time: SystemTime) -> String {
+    format!("recording-{}.wav", format_file_timestamp(time))
+}
+
+/// Path in `dir` for a recording started at `time` that does not exist yet: the
+/// [`recording_file_name`], with `-2`, `-3`, ... appended if recordings started within the same
+/// second already took it
+pub fn recording_path(dir: &Path, time: SystemTime) -> PathBuf {
+    let stem = format!("recording-{}", format_file_timestamp(time));
+    std::iter::once(dir.join(recording_file_name(time)))
+        .chain((2..).map(|n| dir.join(format!("{stem}-{n}.wav"))))
+        .find(|path| !path.exists())
+        .expect("the numbered names never run out")
+}
+
+/// Writes captured audio to WAV files, keeping the last few seconds before each recording starts
+/// so they can be included in it.
+///
+/// Samples are interleaved frames of all captured channels, as delivered by the device.
+pub struct Recorder {
+    sample_rate: u32,
+    channels: u16,
+    /// Most recent samples while not recording, oldest first
+    pre_roll: VecDeque<f32>,
+    /// Number of samples the pre-roll holds, a whole number of frames
+    pre_roll_capacity: usize,
+    /// Recording in progress and where it is written
+    writer: Option<(WavWriter<BufWriter<File>>, PathBuf)>,
+    /// Samples written to the recording in progress
+    samples_written: u64,
+}
+
+impl Recorder {
+    /// Create a recorder for `channels`-channel audio at `sample_rate` Hz, keeping `pre_roll` of
+    /// audio from before each recording
+    pub fn new(sample_rate: u32, channels: u16, pre_roll: Duration) -> Self {
+        let frames = (pre_roll.as_secs_f64() * sample_rate as f64).round() as usize;
+        let pre_roll_capacity = frames * channels as usize;
+        Self {
+            sample_rate,
+            channels,
+            pre_roll: VecDeque::with_capacity(pre_roll_capacity),
+            pre_roll_capacity,
+            writer: None,
+            samples_written: 0,
+        }
+    }
+
+    /// Sample rate in Hz of the audio recorded
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Number of channels recorded
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    /// Whether a recording is in progress
+    pub fn is_recording(&self) -> bool {
+        self.writer.is_some()
+    }
+
+    /// File the recording in progress is written to
+    pub fn path(&self) -> Option<&Path> {
+        self.writer.as_ref().map(|(_, path)| path.as_path())
+    }
+
+    /// Length of the recording in progress, including the pre-roll
+    pub fn duration(&self) -> Duration {
+        let frames = self.samples_written / u64::from(self.channels.max(1));
+        Duration::from_secs_f64(frames as f64 / self.sample_rate.max(1) as f64)
+    }
+
+    /// Add captured samples, writing them to the recording in progress or keeping them as
+    /// pre-roll
+    pub fn push(&mut self, samples: &[f32]) -> Result<()> {
+        if let Some((writer, _)) = &mut self.writer {
+            for &sample in samples {
+                writer.write_sample(sample)?;
+            }
+            self.samples_written += samples.len() as u64;
+            return Ok(());
+        }
+
+        // Only the tail of a long buffer can end up in the pre-roll
+        let samples = &samples[samples.len().saturating_sub(self.pre_roll_capacity)..];
+        let overflow = (self.pre_roll.len() + samples.len()).saturating_sub(self.pre_roll_capacity);
+        self.pre_roll.drain(..overflow);
+        self.pre_roll.extend(samples);
+        Ok(())
+    }
+
+    /// Start recording to a new WAV file at `path`, beginning with the pre-roll. Fails rather
+    /// than overwrite an existing file.
+    pub fn start(&mut self, path: &Path) -> Result<()> {
+        // Finish any recording in progress rather than leave it without a valid header
+        self.stop()?;
+        let spec = WavSpec {
+            channels: self.channels,
+            sample_rate: self.sample_rate,
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Float,
+        };
+        let writer = File::create_new(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|file| Ok(WavWriter::new(BufWriter::new(file), spec)?))
+            .map_err(|e| anyhow::anyhow!("Failed to create {}: {e}", path.display()))?;
+        self.writer = Some((writer, path.to_path_buf()));
+        self.samples_written = 0;
+
+        let pre_roll: Vec<f32> = self.pre_roll.drain(..).collect();
+        self.push(&pre_roll)
+    }
+
+    /// Finish the recording in progress, returning the file it was written to
+    pub fn stop(&mut self) -> Result<Option<PathBuf>> {
+        let Some((writer, path)) = self.writer.take() else {
+            return Ok(None);
+        };
+        writer
+            .finalize()
+            .map_err(|e| anyhow::anyhow!("Failed to finish {}: {e}", path.display()))?;
+        Ok(Some(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "audio-visualizer-test-{}-{name}",
+            std::process::id()
+        ))
+    }
+
+    fn read_samples(path: &Path) -> (WavSpec, Vec<f32>) {
+        let mut reader = hound::WavReader::open(path).unwrap();
+        let samples = reader.samples::<f32>().map(Result::unwrap).collect();
+        (reader.spec(), samples)
+    }
+
+    #[test]
+    fn recordings_start_with_the_pre_roll() {
+        // Half a second of pre-roll at 8 Hz stereo holds 4 frames
+        let mut recorder = Recorder::new(8, 2, Duration::from_millis(500));
+        let frames: Vec<f32> = (0..20).map(|i| i as f32).collect();
+        recorder.push(&frames[..6]).unwrap();
+        recorder.push(&frames[6..12]).unwrap();
+
+        let path = temp_path("pre-roll.wav");
+        recorder.start(&path).unwrap();
+        assert!(recorder.is_recording());
+        assert_eq!(recorder.path(), Some(path.as_path()));
+        recorder.push(&frames[12..]).unwrap();
+        assert_eq!(recorder.duration(), Duration::from_secs(1));
+        assert_eq!(recorder.stop().unwrap(), Some(path.clone()));
+        assert!(!recorder.is_recording());
+
+        let (spec, samples) = read_samples(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((spec.channels, spec.sample_rate), (2, 8));
+        assert_eq!(spec.sample_format, SampleFormat::Float);
+        assert_eq!(samples, frames[4..]);
+    }
+
+    #[test]
+    fn recordings_without_pre_roll() {
+        let mut recorder = Recorder::new(48_000, 1, Duration::ZERO);
+        recorder.push(&[0.5; 100]).unwrap();
+
+        let path = temp_path("no-pre-roll.wav");
+        recorder.start(&path).unwrap();
+        recorder.push(&[0.25, -0.25]).unwrap();
+        recorder.stop().unwrap();
+        assert_eq!(recorder.stop().unwrap(), None);
+
+        let (_, samples) = read_samples(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(samples, [0.25, -0.25]);
+    }
+
+    #[test]
+    fn file_names_are_timestamped() {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_706_745_599);
+        assert_eq!(recording_file_name(time), "recording-20240131-235959.wav");
+    }
+
+    #[test]
+    fn recordings_in_the_same_second_get_their_own_files() {
+        let dir = temp_path("same-second");
+        std::fs::create_dir_all(&dir).unwrap();
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_706_745_599);
+        let mut recorder = Recorder::new(8, 1, Duration::ZERO);
+
+        let mut names = Vec::new();
+        for _ in 0..3 {
+            let path = recording_path(&dir, time);
+            recorder.start(&path).unwrap();
+            recorder.stop().unwrap();
+            names.push(path.file_name().unwrap().to_string_lossy().into_owned());
+        }
+        // An existing file is never overwritten, even when asked to
+        let overwrite = recorder.start(&dir.join(&names[0]));
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            names,
+            [
+                "recording-20240131-235959.wav",
+                "recording-20240131-235959-2.wav",
+                "recording-20240131-235959-3.wav",
+            ]
+        );
+        assert!(overwrite.is_err());
+        assert!(!recorder.is_recording());
+    }
+}
@@ -0,0 +1,92 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::settings::Settings;
+
+/// A frozen spectrum kept for comparison with the live one, stored as JSON next to the settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Reference {
+    /// Sample rate of the audio the spectrum was taken from
+    pub sample_rate: u32,
+    /// Number of samples per FFT frame the spectrum was computed from
+    pub fft_size: usize,
+    /// Magnitude of each FFT bin
+    pub magnitudes: Vec<f32>,
+}
+
+impl Reference {
+    /// Path of the saved reference in the config directory
+    pub fn default_path() -> Option<PathBuf> {
+        Settings::dir().map(|dir| dir.join("reference.json"))
+    }
+
+    /// Load a reference from `path`
+    pub fn load_from(path: &Path) -> Result<Self> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Save the reference to `path`, creating its directory if needed
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Delete the reference saved at `path`, if any
+    pub fn delete_from(path: &Path) -> Result<()> {
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// Magnitudes scaled to a spectrum of `fft_size`-point frames, so a tone reads the same level
+    /// whatever size the reference was taken at
+    pub fn magnitudes_for(&self, fft_size: usize) -> Vec<f32> {
+        let scale = fft_size as f32 / self.fft_size.max(1) as f32;
+        self.magnitudes
+            .iter()
+            .map(|magnitude| magnitude * scale)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_references_load_back() {
+        let path = std::env::temp_dir()
+            .join(format!("audio-visualizer-test-{}", std::process::id()))
+            .join("reference.json");
+        let reference = Reference {
+            sample_rate: 48_000,
+            fft_size: 4,
+            magnitudes: vec![0.0, 1.5, 0.25],
+        };
+        reference.save_to(&path).unwrap();
+        assert_eq!(Reference::load_from(&path).unwrap(), reference);
+        Reference::delete_from(&path).unwrap();
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+        assert!(Reference::load_from(&path).is_err());
+        // Deleting a reference that was never saved is not an error
+        Reference::delete_from(&path).unwrap();
+    }
+
+    #[test]
+    fn magnitudes_scale_with_fft_size() {
+        let reference = Reference {
+            sample_rate: 48_000,
+            fft_size: 1024,
+            magnitudes: vec![2.0, 4.0],
+        };
+        assert_eq!(reference.magnitudes_for(4096), [8.0, 16.0]);
+        assert_eq!(reference.magnitudes_for(1024), [2.0, 4.0]);
+    }
+}
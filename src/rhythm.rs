@@ -0,0 +1,329 @@
+/// Slowest tempo the estimator reports
+pub const MIN_BPM: f32 = 60.0;
+
+/// Fastest tempo the estimator reports
+pub const MAX_BPM: f32 = 200.0;
+
+/// Tempo favoured when several multiples of the beat period fit the onsets equally well
+const PREFERRED_BPM: f32 = 120.0;
+
+/// Spread in octaves of the preference for tempos near [`PREFERRED_BPM`]
+const TEMPO_SPREAD: f32 = 1.0;
+
+/// Seconds of onset strength the tempo is estimated from
+const HISTORY_SECONDS: f32 = 6.0;
+
+/// Seconds of onset strength averaged for the adaptive onset threshold
+const THRESHOLD_SECONDS: f32 = 0.5;
+
+/// Multiple of the recent mean flux a frame must exceed to count as an onset
+const THRESHOLD_RATIO: f32 = 1.5;
+
+/// Flux always needed for an onset, so noise near silence does not trigger onsets
+const MIN_FLUX: f32 = 0.01;
+
+/// Shortest time in seconds between two onsets
+const MIN_ONSET_INTERVAL: f32 = 0.1;
+
+/// How often in seconds the tempo is re-estimated
+const TEMPO_INTERVAL: f32 = 0.5;
+
+/// Fraction of the beat period that must pass after a beat before an onset counts as the next
+const BEAT_WINDOW: f32 = 0.6;
+
+/// Gain applied before log-compressing magnitudes, so quieter partials still contribute flux
+const COMPRESSION: f32 = 100.0;
+
+/// Onset and tempo state after an analysis frame
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Rhythm {
+    /// Spectral flux of the frame: the mean rise in log magnitude across bins
+    pub flux: f32,
+    /// Number of onsets detected since the analysis started
+    pub onsets: u64,
+    /// Number of beats detected since the analysis started
+    pub beats: u64,
+    /// Estimated tempo in beats per minute, once there is enough history
+    pub bpm: Option<f32>,
+}
+
+/// Spectral-flux onset detector and autocorrelation tempo estimator fed one spectrum per frame.
+///
+/// Buffers are allocated up front (and again only when the frame rate or spectrum size changes),
+/// so processing a frame never allocates and can run on the analysis thread.
+pub struct BeatTracker {
+    /// Spectrum frames per second
+    frame_rate: f32,
+    /// Log-compressed magnitudes of the previous frame
+    previous: Vec<f32>,
+    /// Whether `previous` holds a frame to compare against
+    primed: bool,
+    /// Ring buffer of recent flux values
+    history: Vec<f32>,
+    /// Index in `history` the next flux value is written to
+    next: usize,
+    /// Number of valid values in `history`
+    filled: usize,
+    frames_since_onset: usize,
+    frames_since_beat: usize,
+    frames_since_tempo: usize,
+    rhythm: Rhythm,
+}
+
+impl BeatTracker {
+    /// Create a tracker for spectra of `bin_count` bins arriving `frame_rate` times per second
+    pub fn new(frame_rate: f32, bin_count: usize) -> Self {
+        let mut tracker = Self {
+            frame_rate: 0.0,
+            previous: Vec::new(),
+            primed: false,
+            history: Vec::new(),
+            next: 0,
+            filled: 0,
+            frames_since_onset: usize::MAX,
+            frames_since_beat: usize::MAX,
+            frames_since_tempo: 0,
+            rhythm: Rhythm::default(),
+        };
+        tracker.reset(frame_rate, bin_count);
+        tracker
+    }
+
+    /// Change the frame rate and spectrum size, e.g. after the FFT size changed; clears the
+    /// history but keeps the onset and beat counts
+    pub fn reset(&mut self, frame_rate: f32, bin_count: usize) {
+        self.frame_rate = frame_rate;
+        self.previous.clear();
+        self.previous.resize(bin_count, 0.0);
+        self.primed = false;
+        self.history.clear();
+        self.history
+            .resize(((HISTORY_SECONDS * frame_rate).ceil() as usize).max(1), 0.0);
+        self.next = 0;
+        self.filled = 0;
+        self.frames_since_tempo = 0;
+        self.rhythm.bpm = None;
+    }
+
+    /// Spectrum frames per second
+    pub fn frame_rate(&self) -> f32 {
+        self.frame_rate
+    }
+
+    /// Onset and tempo state after the latest frame
+    pub fn rhythm(&self) -> Rhythm {
+        self.rhythm
+    }
+
+    /// Feed the bin magnitudes of the next frame and return the updated rhythm state
+    pub fn process(&mut self, magnitudes: &[f32]) -> Rhythm {
+        let flux = self.flux(magnitudes);
+        let threshold = (self.recent_mean_flux() * THRESHOLD_RATIO).max(MIN_FLUX);
+
+        self.history[self.next] = flux;
+        self.next = (self.next + 1) % self.history.len();
+        self.filled = (self.filled + 1).min(self.history.len());
+        self.frames_since_onset = self.frames_since_onset.saturating_add(1);
+        self.frames_since_beat = self.frames_since_beat.saturating_add(1);
+        self.frames_since_tempo += 1;
+        self.rhythm.flux = flux;
+
+        let min_onset_frames = (MIN_ONSET_INTERVAL * self.frame_rate) as usize;
+        if flux > threshold && self.frames_since_onset > min_onset_frames {
+            self.frames_since_onset = 0;
+            self.rhythm.onsets += 1;
+
+            // Without a tempo every onset is a beat; with one, onsets between beats are not
+            let beat_frames = self
+                .rhythm
+                .bpm
+                .map_or(0.0, |bpm| BEAT_WINDOW * 60.0 * self.frame_rate / bpm);
+            if self.frames_since_beat as f32 >= beat_frames {
+                self.frames_since_beat = 0;
+                self.rhythm.beats += 1;
+            }
+        }
+
+        if self.frames_since_tempo as f32 >= TEMPO_INTERVAL * self.frame_rate {
+            self.frames_since_tempo = 0;
+            self.rhythm.bpm = self.estimate_tempo();
+        }
+
+        self.rhythm
+    }
+
+    /// Mean rise in log-compressed magnitude across bins since the previous frame
+    fn flux(&mut self, magnitudes: &[f32]) -> f32 {
+        // Scale magnitudes so a full-scale sine is about 1 whatever the FFT size
+        let scale = COMPRESSION / magnitudes.len().saturating_sub(1).max(1) as f32;
+
+        // A spectrum of another size has nothing to compare against
+        if self.previous.len() != magnitudes.len() {
+            self.previous.resize(magnitudes.len(), 0.0);
+            self.primed = false;
+        }
+
+        let mut rise = 0.0;
+        for (previous, magnitude) in self.previous.iter_mut().zip(magnitudes) {
+            let level = (magnitude * scale).ln_1p();
+            rise += (level - *previous).max(0.0);
+            *previous = level;
+        }
+
+        let primed = std::mem::replace(&mut self.primed, true);
+        if primed && !magnitudes.is_empty() {
+            rise / magnitudes.len() as f32
+        } else {
+            0.0
+        }
+    }
+
+    /// Mean flux over the last [`THRESHOLD_SECONDS`]
+    fn recent_mean_flux(&self) -> f32 {
+        let count = ((THRESHOLD_SECONDS * self.frame_rate) as usize).clamp(1, self.history.len());
+        let count = count.min(self.filled);
+        if count == 0 {
+            return 0.0;
+        }
+        let sum: f32 = (1..=count).map(|age| self.flux_ago(age)).sum();
+        sum / count as f32
+    }
+
+    /// Flux value `age` frames back, where 1 is the newest
+    fn flux_ago(&self, age: usize) -> f32 {
+        let len = self.history.len();
+        self.history[(self.next + len - age) % len]
+    }
+
+    /// Pick the beat period that best matches the autocorrelation of the recent flux, weighted
+    /// towards [`PREFERRED_BPM`]
+    fn estimate_tempo(&self) -> Option<f32> {
+        let min_lag = (60.0 * self.frame_rate / MAX_BPM).floor().max(1.0) as usize;
+        let max_lag = (60.0 * self.frame_rate / MIN_BPM).ceil() as usize;
+        // Need a couple of the slowest periods before the correlation means anything
+        if self.filled < 2 * max_lag + 1 {
+            return None;
+        }
+
+        let mean =
+            (1..=self.filled).map(|age| self.flux_ago(age)).sum::<f32>() / self.filled as f32;
+        let mut best: Option<(usize, f32)> = None;
+        for lag in min_lag..=max_lag {
+            let bpm = 60.0 * self.frame_rate / lag as f32;
+            let preference = (bpm / PREFERRED_BPM).log2() / TEMPO_SPREAD;
+            let score = self.autocorrelation(lag, mean) * (-0.5 * preference * preference).exp();
+            if score > 0.0 && best.is_none_or(|(_, best_score)| score > best_score) {
+                best = Some((lag, score));
+            }
+        }
+        let (lag, _) = best?;
+
+        // Refine the period between whole frames by fitting a parabola through the neighbours
+        let [before, at, after] =
+            [lag - 1, lag, lag + 1].map(|lag| self.autocorrelation(lag, mean));
+        let curvature = before - 2.0 * at + after;
+        let offset = if curvature < 0.0 {
+            (0.5 * (before - after) / curvature).clamp(-0.5, 0.5)
+        } else {
+            0.0
+        };
+        Some(60.0 * self.frame_rate / (lag as f32 + offset))
+    }
+
+    /// Mean product of the flux (less `mean`) with itself `lag` frames later
+    fn autocorrelation(&self, lag: usize, mean: f32) -> f32 {
+        if lag == 0 || lag >= self.filled {
+            return 0.0;
+        }
+        let sum: f32 = (1..=self.filled - lag)
+            .map(|age| (self.flux_ago(age) - mean) * (self.flux_ago(age + lag) - mean))
+            .sum();
+        sum / (self.filled - lag) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::{Analyzer, Spectrum};
+
+    const SAMPLE_RATE: usize = 44_100;
+    const FFT_SIZE: usize = 1024;
+    const HOP_SIZE: usize = FFT_SIZE / 2;
+
+    /// Run `seconds` of a click track at `bpm` through the analysis and return the final state
+    fn track_clicks(bpm: f32, seconds: f32) -> Rhythm {
+        let period = (60.0 / bpm * SAMPLE_RATE as f32) as usize;
+        let click = SAMPLE_RATE / 100;
+        let mut noise = 1u32;
+        let signal: Vec<f32> = (0..(seconds * SAMPLE_RATE as f32) as usize)
+            .map(|i| {
+                // Short bursts of decaying pseudo-random noise
+                noise = noise.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                // Start half a period in; the first frame has nothing to compare against
+                let offset = (i + period / 2) % period;
+                if offset < click {
+                    let sample = (noise >> 8) as f32 / (1 << 24) as f32 * 2.0 - 1.0;
+                    0.5 * sample * (1.0 - offset as f32 / click as f32)
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+
+        let mut analyzer = Analyzer::new(FFT_SIZE, HOP_SIZE);
+        let mut spectrum = Spectrum::new(FFT_SIZE);
+        let mut tracker = BeatTracker::new(SAMPLE_RATE as f32 / HOP_SIZE as f32, FFT_SIZE / 2 + 1);
+        for hop in signal.chunks_exact(HOP_SIZE) {
+            analyzer.advance(|samples| samples.copy_from_slice(hop));
+            analyzer.compute(&mut spectrum);
+            tracker.process(&spectrum.magnitudes);
+        }
+        tracker.rhythm()
+    }
+
+    #[test]
+    fn detects_each_click_as_an_onset() {
+        let rhythm = track_clicks(120.0, 10.0);
+        assert_eq!(rhythm.onsets, 20);
+        assert_eq!(rhythm.beats, 20);
+    }
+
+    #[test]
+    fn estimates_click_track_tempo() {
+        for bpm in [72.0, 100.0, 120.0, 128.0, 174.0] {
+            let estimate = track_clicks(bpm, 10.0).bpm.unwrap();
+            assert!(
+                (estimate - bpm).abs() < 2.0,
+                "{bpm} BPM estimated as {estimate} BPM"
+            );
+        }
+    }
+
+    #[test]
+    fn silence_has_no_onsets_or_tempo() {
+        let mut tracker = BeatTracker::new(86.0, 513);
+        let silence = [0.0; 513];
+        for _ in 0..1000 {
+            tracker.process(&silence);
+        }
+        assert_eq!(tracker.rhythm(), Rhythm::default());
+    }
+
+    #[test]
+    fn reset_keeps_counts() {
+        let mut tracker = BeatTracker::new(86.0, 513);
+        tracker.process(&[0.0; 513]);
+        tracker.process(&[1000.0; 513]);
+        assert_eq!(tracker.rhythm().onsets, 1);
+
+        tracker.reset(43.0, 1025);
+        assert_eq!(tracker.frame_rate(), 43.0);
+        assert_eq!(tracker.rhythm().onsets, 1);
+        assert_eq!(tracker.rhythm().bpm, None);
+
+        // The first spectrum after a reset only primes the flux
+        assert_eq!(tracker.process(&[2000.0; 1025]).flux, 0.0);
+        assert!(tracker.process(&[4000.0; 1025]).flux > 0.0);
+    }
+}
@@ -0,0 +1,86 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Choices remembered between runs, stored as JSON in the user's config directory
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Name of the audio host backend selected last time
+    pub host: Option<String>,
+}
+
+impl Settings {
+    /// Directory holding all persisted state for the visualizer
+    pub fn dir() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("audio-visualizer"))
+    }
+
+    /// Path of the settings file
+    fn path() -> Option<PathBuf> {
+        Self::dir().map(|dir| dir.join("settings.json"))
+    }
+
+    /// Load the saved settings, falling back to defaults if none exist or they can't be read
+    pub fn load() -> Self {
+        Self::path()
+            .map(|path| Self::load_from(&path))
+            .unwrap_or_default()
+    }
+
+    /// Save the settings, creating the config directory if needed
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path().ok_or_else(|| anyhow::anyhow!("No config directory available"))?;
+        self.save_to(&path)
+    }
+
+    /// Load the settings saved at `path`, falling back to defaults if they can't be read
+    pub fn load_from(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    /// Save the settings to `path`, creating its directory if needed
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_settings_load_back() {
+        let path = std::env::temp_dir()
+            .join(format!(
+                "audio-visualizer-test-{}-settings",
+                std::process::id()
+            ))
+            .join("settings.json");
+        let settings = Settings {
+            host: Some("JACK".to_string()),
+        };
+        settings.save_to(&path).unwrap();
+        assert_eq!(Settings::load_from(&path), settings);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+        // A missing file gives the defaults
+        assert_eq!(Settings::load_from(&path), Settings::default());
+    }
+
+    #[test]
+    fn unknown_and_missing_fields_are_tolerated() {
+        let settings: Settings = serde_json::from_str(r#"{"future": 1}"#).unwrap();
+        assert_eq!(settings, Settings::default());
+        let settings: Settings = serde_json::from_str(r#"{"host": "ALSA"}"#).unwrap();
+        assert_eq!(settings.host.as_deref(), Some("ALSA"));
+    }
+}
@@ -0,0 +1,536 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::averaging::AveragingMode;
+use crate::axis::{bin_frequency, db_ticks, format_frequency, frequency_ticks};
+use crate::bars::{MAX_BAR_HEIGHT, bins_per_bar, spectrum_to_bars};
+use crate::config::Config;
+use crate::event_log::format_file_timestamp;
+use crate::plot::{Anchor, CHAR_WIDTH, ImageFormat, Plot, Rgb, TEXT_HEIGHT};
+use crate::summary::for_each_spectrum;
+
+const BACKGROUND: Rgb = [0, 0, 0];
+
+/// Colour of titles and labels, the terminal's gray
+const LABEL_COLOR: Rgb = [229, 229, 229];
+
+/// Colour of the axis lines, the terminal's dark gray
+const AXIS_COLOR: Rgb = [127, 127, 127];
+
+/// Colour of the reference trace, as in the terminal
+const REFERENCE_COLOR: Rgb = [255, 255, 255];
+
+/// Height of a row of text; the spectrum chart is laid out in rows and columns of text like the
+/// terminal, so it gets the same bars and labels
+const ROW_HEIGHT: u32 = TEXT_HEIGHT + 4;
+
+/// Space around the picture
+const MARGIN: u32 = 16;
+
+/// Height above a chart holding the picture's title and the title of the vertical axis
+const HEADER_HEIGHT: u32 = 2 * ROW_HEIGHT + 4;
+
+/// Columns of the spectrum chart to fill with bars of a whole number of columns each
+const CHART_COLUMNS: usize = 96;
+
+/// Rows of the spectrum chart
+const CHART_ROWS: usize = 24;
+
+/// Columns left of a chart for its level or frequency labels
+const LABEL_COLUMNS: u32 = 5;
+
+/// Range of widths in pixels of the spectrogram chart; short audio is stretched and long audio
+/// condensed to fit
+const SPECTROGRAM_WIDTHS: (usize, usize) = (600, 1200);
+
+/// Height in pixels the spectrogram's frequency bands share
+const SPECTROGRAM_HEIGHT: u32 = CHART_ROWS as u32 * ROW_HEIGHT;
+
+/// File name for a snapshot taken at `time`, e.g. `spectrum-20240131-235959.png`
+pub fn snapshot_file_name(time: SystemTime, format: ImageFormat) -> String {
+    format!(
+        "spectrum-{}.{}",
+        format_file_timestamp(time),
+        format.extension()
+    )
+}
+
+/// Path in `dir` for a snapshot taken at `time` that does not exist yet: the
+/// [`snapshot_file_name`], with `-2`, `-3`, ... appended if snapshots taken within the same
+/// second already took it
+pub fn snapshot_path(dir: &Path, time: SystemTime, format: ImageFormat) -> PathBuf {
+    let stem = format!("spectrum-{}", format_file_timestamp(time));
+    std::iter::once(dir.join(snapshot_file_name(time, format)))
+        .chain((2..).map(|n| dir.join(format!("{stem}-{n}.{}", format.extension()))))
+        .find(|path| !path.exists())
+        .expect("the numbered names never run out")
+}
+
+/// Picture of the spectrum `magnitudes` of audio at `sample_rate` Hz as the spectrum view shows
+/// it with `config`: the same bars, scaling, colour and labels, with the bar heights of a
+/// `reference` trace drawn over them
+pub fn spectrum_plot(
+    magnitudes: &[f32],
+    sample_rate: u32,
+    config: &Config,
+    reference: Option<&[u64]>,
+) -> Plot {
+    let bar_count = config.bar_count.max(1);
+    let bar_width = (CHART_COLUMNS / bar_count).max(1);
+    let columns = bar_count * bar_width;
+    let bars = spectrum_to_bars(magnitudes, bar_count, config.sensitivity);
+
+    // Bars are absolute on the dB scale and otherwise fill the chart, as in the terminal
+    let max = if config.show_db_axis {
+        MAX_BAR_HEIGHT
+    } else {
+        bars.iter()
+            .chain(reference.into_iter().flatten())
+            .copied()
+            .max()
+            .unwrap_or(0)
+            .max(1)
+    };
+
+    let title = format!(
+        "Frequency Spectrum ({sample_rate} Hz) - {bar_count} bars - {} scheme{}",
+        config.color_scheme.name(),
+        match config.averaging {
+            AveragingMode::Off => String::new(),
+            mode => format!(" - {}", mode.name()),
+        }
+    );
+    let y_title = if config.show_db_axis {
+        "dB"
+    } else {
+        "Relative level"
+    };
+    let label_columns = if config.show_db_axis {
+        LABEL_COLUMNS
+    } else {
+        0
+    };
+    let (mut plot, chart) = framed(
+        &title,
+        y_title,
+        "Frequency (Hz)",
+        label_columns,
+        columns as u32 * CHAR_WIDTH,
+        CHART_ROWS as u32 * ROW_HEIGHT,
+    );
+
+    let bar_pixels = bar_width as u32 * CHAR_WIDTH;
+    let scale = |height: u64| (height.min(max) * u64::from(chart.height) / max) as u32;
+    for (bar, &height) in bars.iter().enumerate() {
+        let height = scale(height);
+        plot.fill_rect(
+            chart.x + bar as u32 * bar_pixels,
+            chart.bottom() - height,
+            bar_pixels - 1,
+            height,
+            config.color_scheme.rgb(),
+        );
+    }
+    for (bar, &height) in reference.unwrap_or_default().iter().enumerate() {
+        let height = scale(height);
+        if height > 0 && bar < bar_count {
+            plot.fill_rect(
+                chart.x + bar as u32 * bar_pixels,
+                (chart.bottom() - height).saturating_sub(1),
+                bar_pixels - 1,
+                2,
+                REFERENCE_COLOR,
+            );
+        }
+    }
+
+    let fft_size = magnitudes.len().saturating_sub(1) * 2;
+    for tick in frequency_ticks(
+        magnitudes.len(),
+        bar_count,
+        bar_width,
+        columns,
+        sample_rate,
+        fft_size,
+    ) {
+        plot.text(
+            chart.x + tick.position as u32 * CHAR_WIDTH,
+            chart.bottom() + 4,
+            tick.label,
+            LABEL_COLOR,
+            Anchor::Start,
+        );
+    }
+    if config.show_db_axis {
+        for tick in db_ticks(CHART_ROWS, config.sensitivity) {
+            plot.text(
+                chart.x - CHAR_WIDTH / 2,
+                chart.y + tick.position as u32 * ROW_HEIGHT,
+                tick.label,
+                LABEL_COLOR,
+                Anchor::End,
+            );
+        }
+    }
+    plot
+}
+
+/// Picture of the spectrogram of `samples`, interleaved frames of `channels` channels at
+/// `sample_rate` Hz: time runs to the right and the bars of the spectrum view with `config` run
+/// upwards, their heights setting the brightness of the scheme's colour
+pub fn spectrogram_plot(samples: &[f32], channels: u16, sample_rate: u32, config: &Config) -> Plot {
+    let bar_count = config.bar_count.max(1);
+    let fft_size = config.fft_size;
+    let hop_size = fft_size / 2;
+    let mut bars: Vec<Vec<u64>> = Vec::new();
+    for_each_spectrum(samples, channels, fft_size, hop_size, |magnitudes| {
+        bars.push(spectrum_to_bars(magnitudes, bar_count, config.sensitivity));
+    });
+
+    let row_height = (SPECTROGRAM_HEIGHT / bar_count as u32).max(1);
+    let (min_width, max_width) = SPECTROGRAM_WIDTHS;
+    let width = bars.len().clamp(min_width, max_width);
+    let duration = (bars.len() * hop_size) as f32 / sample_rate.max(1) as f32;
+
+    let title = format!(
+        "Spectrogram ({sample_rate} Hz) - {bar_count} bars - {} scheme - {duration:.1} s",
+        config.color_scheme.name()
+    );
+    let (mut plot, chart) = framed(
+        &title,
+        "Frequency (Hz)",
+        "Time (s)",
+        LABEL_COLUMNS,
+        width as u32,
+        row_height * bar_count as u32,
+    );
+
+    // Each column shows the loudest of the spectra it covers, so short events stay visible
+    let columns: Vec<Vec<u64>> = (0..width)
+        .map(|column| {
+            let start = column * bars.len() / width;
+            let end = ((column + 1) * bars.len() / width).max(start + 1);
+            (0..bar_count)
+                .map(|bar| {
+                    bars.get(start..end.min(bars.len()))
+                        .unwrap_or_default()
+                        .iter()
+                        .map(|heights| heights[bar])
+                        .max()
+                        .unwrap_or(0)
+                })
+                .collect()
+        })
+        .collect();
+
+    // Neighbouring columns of the same shade are drawn as one rectangle
+    let [r, g, b] = config.color_scheme.rgb();
+    let shade =
+        |height: u64| [r, g, b].map(|channel| (u64::from(channel) * height / MAX_BAR_HEIGHT) as u8);
+    for bar in 0..bar_count {
+        let y = chart.bottom() - (bar as u32 + 1) * row_height;
+        let mut column = 0;
+        while column < width {
+            let height = columns[column][bar];
+            let run = columns[column..]
+                .iter()
+                .take_while(|heights| heights[bar] == height)
+                .count();
+            if height > 0 {
+                plot.fill_rect(
+                    chart.x + column as u32,
+                    y,
+                    run as u32,
+                    row_height,
+                    shade(height),
+                );
+            }
+            column += run;
+        }
+    }
+
+    // Frequencies from 1-2-5 steps, at the band they fall in and at least a row apart
+    let bin_count = fft_size / 2 + 1;
+    let hz_per_bar = bin_frequency(
+        bins_per_bar(bin_count, bar_count) as f32,
+        sample_rate,
+        fft_size,
+    );
+    let mut placed: Vec<u32> = Vec::new();
+    for mantissa in [1.0, 5.0, 2.0] {
+        for exponent in 1..=5 {
+            let hz = mantissa * 10f32.powi(exponent);
+            let bar = if hz_per_bar > 0.0 {
+                (hz / hz_per_bar) as usize
+            } else {
+                0
+            };
+            if bar == 0 || bar >= bar_count {
+                continue;
+            }
+            let centre = chart.bottom() - bar as u32 * row_height - row_height / 2;
+            let y = centre.saturating_sub(TEXT_HEIGHT / 2);
+            if placed.iter().any(|&other| other.abs_diff(y) < ROW_HEIGHT) {
+                continue;
+            }
+            placed.push(y);
+            plot.text(
+                chart.x - CHAR_WIDTH / 2,
+                y,
+                format_frequency(hz),
+                LABEL_COLOR,
+                Anchor::End,
+            );
+        }
+    }
+
+    // Seconds in the smallest step that leaves room for the labels
+    let pixels_per_second = width as f32 / duration.max(f32::EPSILON);
+    let step = [
+        0.01, 0.02, 0.05, 0.1, 0.2, 0.5, 1.0, 2.0, 5.0, 10.0, 15.0, 30.0, 60.0, 120.0, 300.0,
+        600.0, 1800.0, 3600.0,
+    ]
+    .into_iter()
+    .find(|step| step * pixels_per_second >= (8 * CHAR_WIDTH) as f32)
+    .unwrap_or(7200.0);
+    let decimals = if step >= 1.0 {
+        0
+    } else if step >= 0.1 {
+        1
+    } else {
+        2
+    };
+    let mut tick = 0;
+    while tick as f32 * step <= duration {
+        let seconds = tick as f32 * step;
+        let x = chart.x + (seconds * pixels_per_second).round() as u32;
+        plot.fill_rect(x.min(chart.right()), chart.bottom(), 1, 4, AXIS_COLOR);
+        plot.text(
+            x,
+            chart.bottom() + 6,
+            format!("{seconds:.decimals$}"),
+            LABEL_COLOR,
+            if tick == 0 {
+                Anchor::Start
+            } else {
+                Anchor::Middle
+            },
+        );
+        tick += 1;
+    }
+    plot
+}
+
+/// Where a chart lies in its picture
+#[derive(Debug, Clone, Copy)]
+struct Chart {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl Chart {
+    fn right(&self) -> u32 {
+        self.x + self.width
+    }
+
+    fn bottom(&self) -> u32 {
+        self.y + self.height
+    }
+}
+
+/// A picture with a `title`, a chart of `width` × `height` pixels with `label_columns` columns
+/// for labels to its left, axis lines, and titles for both axes
+fn framed(
+    title: &str,
+    y_title: &str,
+    x_title: &str,
+    label_columns: u32,
+    width: u32,
+    height: u32,
+) -> (Plot, Chart) {
+    let chart = Chart {
+        x: MARGIN + label_columns * CHAR_WIDTH,
+        y: MARGIN + HEADER_HEIGHT,
+        width,
+        height,
+    };
+    let mut plot = Plot::new(
+        chart.right() + MARGIN,
+        chart.bottom() + 2 * ROW_HEIGHT + MARGIN,
+        BACKGROUND,
+    );
+    plot.text(MARGIN, MARGIN, title, LABEL_COLOR, Anchor::Start);
+    plot.text(
+        MARGIN,
+        MARGIN + ROW_HEIGHT,
+        y_title,
+        LABEL_COLOR,
+        Anchor::Start,
+    );
+    plot.text(
+        chart.x + chart.width / 2,
+        chart.bottom() + ROW_HEIGHT + 4,
+        x_title,
+        LABEL_COLOR,
+        Anchor::Middle,
+    );
+    plot.fill_rect(chart.x - 1, chart.y, 1, chart.height + 1, AXIS_COLOR);
+    plot.fill_rect(chart.x - 1, chart.bottom(), chart.width + 1, 1, AXIS_COLOR);
+    (plot, chart)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ColorScheme;
+    use std::time::Duration;
+
+    /// Spectrum whose bins rise in magnitude, like the one the spectrum view tests use
+    fn rising_spectrum() -> Vec<f32> {
+        (0..513).map(|bin| bin as f32 / 8.0).collect()
+    }
+
+    fn config(bar_count: usize) -> Config {
+        Config {
+            bar_count,
+            color_scheme: ColorScheme::Green,
+            ..Config::default()
+        }
+    }
+
+    /// Colour of the pixel at (`x`, `y`)
+    fn pixel(plot: &Plot, x: u32, y: u32) -> Rgb {
+        let index = ((y * plot.width() + x) * 3) as usize;
+        plot.to_pixels()[index..index + 3].try_into().unwrap()
+    }
+
+    #[test]
+    fn spectrum_bars_are_scaled_like_the_terminal() {
+        let spectrum = rising_spectrum();
+        let config = config(8);
+        let plot = spectrum_plot(&spectrum, 44100, &config, None);
+        let bars = spectrum_to_bars(&spectrum, 8, 1.0);
+        let tallest = *bars.iter().max().unwrap();
+
+        // 96 columns hold 8 bars of 12 columns each
+        let bar_pixels = 12 * CHAR_WIDTH;
+        let chart_x = MARGIN;
+        let chart_bottom = MARGIN + HEADER_HEIGHT + CHART_ROWS as u32 * ROW_HEIGHT;
+        assert_eq!(plot.width(), chart_x + 8 * bar_pixels + MARGIN);
+        for (bar, &height) in bars.iter().enumerate() {
+            let pixels = (height * u64::from(CHART_ROWS as u32 * ROW_HEIGHT) / tallest) as u32;
+            let x = chart_x + bar as u32 * bar_pixels + bar_pixels / 2;
+            if pixels > 0 {
+                assert_eq!(
+                    pixel(&plot, x, chart_bottom - pixels),
+                    ColorScheme::Green.rgb()
+                );
+            }
+            assert_eq!(pixel(&plot, x, chart_bottom - pixels - 1), BACKGROUND);
+        }
+
+        let svg = plot.to_svg();
+        assert!(svg.contains(">Frequency Spectrum (44100 Hz) - 8 bars - Green scheme</text>"));
+        assert!(svg.contains(">10k</text>"), "{svg}");
+        assert!(svg.contains(">Frequency (Hz)</text>"));
+    }
+
+    #[test]
+    fn spectrum_with_db_axis_and_reference() {
+        let mut config = config(8);
+        config.show_db_axis = true;
+        let reference = [50; 8];
+        let plot = spectrum_plot(&[0.0; 513], 44100, &config, Some(&reference));
+
+        // Half of MAX_BAR_HEIGHT is half way up the chart
+        let chart_x = MARGIN + LABEL_COLUMNS * CHAR_WIDTH;
+        let chart_height = CHART_ROWS as u32 * ROW_HEIGHT;
+        let chart_bottom = MARGIN + HEADER_HEIGHT + chart_height;
+        assert_eq!(
+            pixel(&plot, chart_x + 10, chart_bottom - chart_height / 2),
+            REFERENCE_COLOR
+        );
+        assert_eq!(pixel(&plot, chart_x + 10, chart_bottom - 10), BACKGROUND);
+
+        let svg = plot.to_svg();
+        assert!(svg.contains(">0</text>"), "{svg}");
+        assert!(svg.contains(">dB</text>"));
+    }
+
+    #[test]
+    fn spectrogram_shades_each_band_over_time() {
+        // Two seconds of stereo audio: a 20 kHz tone in the top band for the first half, then
+        // silence
+        let samples: Vec<f32> = (0..2 * 44100)
+            .flat_map(|i| {
+                let t = i as f32 / 44100.0;
+                let sample = if i < 44100 {
+                    0.5 * (2.0 * std::f32::consts::PI * 20_000.0 * t).sin()
+                } else {
+                    0.0
+                };
+                [sample, sample]
+            })
+            .collect();
+        let plot = spectrogram_plot(&samples, 2, 44100, &config(8));
+
+        // 173 spectra are stretched to the minimum width
+        let chart_x = MARGIN + LABEL_COLUMNS * CHAR_WIDTH;
+        let chart_y = MARGIN + HEADER_HEIGHT;
+        let chart_bottom = chart_y + SPECTROGRAM_HEIGHT;
+        assert_eq!(plot.width(), chart_x + 600 + MARGIN);
+        assert_eq!(
+            pixel(&plot, chart_x + 10, chart_y + 5),
+            ColorScheme::Green.rgb()
+        );
+        assert_eq!(pixel(&plot, chart_x + 400, chart_y + 5), BACKGROUND);
+        assert!(pixel(&plot, chart_x + 10, chart_bottom - 5)[1] < 100);
+
+        let svg = plot.to_svg();
+        assert!(svg.contains(" - 2.0 s</text>"), "{svg}");
+        assert!(svg.contains(">Time (s)</text>"));
+        assert!(svg.contains(">1.0</text>"), "{svg}");
+        assert!(svg.contains(">10k</text>"), "{svg}");
+    }
+
+    #[test]
+    fn file_names_are_timestamped() {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_706_745_599);
+        assert_eq!(
+            snapshot_file_name(time, ImageFormat::Svg),
+            "spectrum-20240131-235959.svg"
+        );
+    }
+
+    #[test]
+    fn snapshots_in_the_same_second_get_their_own_files() {
+        let dir = std::env::temp_dir().join(format!(
+            "audio-visualizer-test-{}-same-second-snapshots",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_706_745_599);
+        let plot = spectrum_plot(&rising_spectrum(), 44_100, &config(8), None);
+
+        let mut names = Vec::new();
+        for _ in 0..3 {
+            let path = snapshot_path(&dir, time, ImageFormat::Svg);
+            plot.save_new(&path, ImageFormat::Svg).unwrap();
+            names.push(path.file_name().unwrap().to_string_lossy().into_owned());
+        }
+        // An existing file is never overwritten, even when asked to
+        let overwrite = plot.save_new(&dir.join(&names[0]), ImageFormat::Svg);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            names,
+            [
+                "spectrum-20240131-235959.svg",
+                "spectrum-20240131-235959-2.svg",
+                "spectrum-20240131-235959-3.svg",
+            ]
+        );
+        assert!(overwrite.is_err());
+    }
+}
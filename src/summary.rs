@@ -0,0 +1,293 @@
+use anyhow::Result;
+use hound::{SampleFormat, WavReader};
+use serde::Serialize;
+use std::path::Path;
+
+use crate::analysis::{Analyzer, Spectrum};
+use crate::bands::MIN_LEVEL_DB;
+use crate::features::spectral_features;
+use crate::loudness::integrated_loudness;
+use crate::pitch::peak_offset;
+
+/// Samples per FFT frame of the long-term spectrum; fine enough to tell semitones apart in the
+/// bass at common sample rates
+const SUMMARY_FFT_SIZE: usize = 8192;
+
+/// Summary statistics of a whole piece of audio
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct AudioSummary {
+    /// Length in seconds
+    pub duration_s: f32,
+    /// Sample rate in Hz
+    pub sample_rate: u32,
+    /// Number of channels
+    pub channels: u16,
+    /// Highest absolute sample value of any channel in dBFS
+    pub peak_dbfs: f32,
+    /// Root mean square level over all channels in dBFS
+    pub rms_dbfs: f32,
+    /// Integrated loudness in LUFS, if the audio is long and loud enough to measure it
+    pub loudness_lufs: Option<f32>,
+    /// Frequency in Hz of the strongest peak of the long-term spectrum; `None` for silence
+    pub dominant_frequency_hz: Option<f32>,
+    /// Spectral centroid of the long-term spectrum in Hz
+    pub spectral_centroid_hz: f32,
+}
+
+/// Read the WAV file at `path` and summarise it
+pub fn summarize_file(path: &Path) -> Result<AudioSummary> {
+    let (samples, channels, sample_rate) = read_wav(path)?;
+    Ok(summarize(&samples, channels, sample_rate))
+}
+
+/// Read the WAV file at `path` as interleaved samples scaled to ±1, with its channel count and
+/// sample rate
+pub fn read_wav(path: &Path) -> Result<(Vec<f32>, u16, u32)> {
+    let mut reader = WavReader::open(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {e}", path.display()))?;
+    let spec = reader.spec();
+    let samples: Vec<f32> = match spec.sample_format {
+        SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+        SampleFormat::Int => {
+            let scale = 1.0 / (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|sample| sample as f32 * scale))
+                .collect::<Result<_, _>>()?
+        }
+    };
+    Ok((samples, spec.channels, spec.sample_rate))
+}
+
+/// Call `f` with the magnitudes of each spectrum of the mixdown of `samples`, interleaved frames
+/// of `channels` channels, analysed in frames of `fft_size` samples every `hop_size` samples. The
+/// end is padded with silence to a whole hop.
+pub fn for_each_spectrum(
+    samples: &[f32],
+    channels: u16,
+    fft_size: usize,
+    hop_size: usize,
+    mut f: impl FnMut(&[f32]),
+) {
+    let channel_count = usize::from(channels.max(1));
+    let mut analyzer = Analyzer::new(fft_size, hop_size);
+    let mut spectrum = Spectrum::new(fft_size);
+    for hop in samples.chunks(hop_size * channel_count) {
+        analyzer.advance(|buffer| {
+            buffer.fill(0.0);
+            for (sample, frame) in buffer.iter_mut().zip(hop.chunks(channel_count)) {
+                *sample = frame.iter().sum::<f32>() / channel_count as f32;
+            }
+        });
+        analyzer.compute(&mut spectrum);
+        f(&spectrum.magnitudes);
+    }
+}
+
+/// Summarise `samples`, interleaved frames of `channels` channels at `sample_rate` Hz
+pub fn summarize(samples: &[f32], channels: u16, sample_rate: u32) -> AudioSummary {
+    let frames = samples.len() / usize::from(channels.max(1));
+
+    let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+    let mean_square = samples
+        .iter()
+        .map(|&s| f64::from(s) * f64::from(s))
+        .sum::<f64>()
+        / samples.len().max(1) as f64;
+
+    let magnitudes = average_spectrum(samples, channels, SUMMARY_FFT_SIZE);
+
+    AudioSummary {
+        duration_s: frames as f32 / sample_rate.max(1) as f32,
+        sample_rate,
+        channels,
+        peak_dbfs: level_dbfs(peak),
+        rms_dbfs: level_dbfs(mean_square.sqrt() as f32),
+        loudness_lufs: integrated_loudness(samples, channels, sample_rate),
+        dominant_frequency_hz: dominant_frequency(&magnitudes, sample_rate, SUMMARY_FFT_SIZE),
+        spectral_centroid_hz: spectral_features(&magnitudes, sample_rate, SUMMARY_FFT_SIZE)
+            .centroid,
+    }
+}
+
+/// Magnitudes of the power average of the overlapping `fft_size`-point spectra of the mixdown of
+/// `samples`, interleaved frames of `channels` channels
+pub fn average_spectrum(samples: &[f32], channels: u16, fft_size: usize) -> Vec<f32> {
+    let mut power = vec![0.0f64; fft_size / 2 + 1];
+    let mut spectra = 0;
+    for_each_spectrum(samples, channels, fft_size, fft_size / 2, |magnitudes| {
+        for (power, &magnitude) in power.iter_mut().zip(magnitudes) {
+            *power += f64::from(magnitude) * f64::from(magnitude);
+        }
+        spectra += 1;
+    });
+    power
+        .iter()
+        .map(|&power| (power / f64::from(spectra.max(1))).sqrt() as f32)
+        .collect()
+}
+
+/// Level of a linear amplitude in dBFS, floored so silence stays a number
+fn level_dbfs(amplitude: f32) -> f32 {
+    (20.0 * amplitude.log10()).max(MIN_LEVEL_DB)
+}
+
+/// Frequency of the strongest bin above DC, refined between bins
+fn dominant_frequency(magnitudes: &[f32], sample_rate: u32, fft_size: usize) -> Option<f32> {
+    let (bin, &magnitude) = magnitudes
+        .iter()
+        .enumerate()
+        .skip(1)
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))?;
+    if magnitude <= 0.0 {
+        return None;
+    }
+    let hz_per_bin = sample_rate as f32 / fft_size as f32;
+    Some((bin as f32 + peak_offset(magnitudes, bin)) * hz_per_bin)
+}
+
+/// Limits a summary is checked against; unset limits are not checked
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Thresholds {
+    /// Highest acceptable peak in dBFS
+    pub max_peak_dbfs: Option<f32>,
+    /// Highest acceptable RMS level in dBFS
+    pub max_rms_dbfs: Option<f32>,
+    /// Highest acceptable integrated loudness in LUFS
+    pub max_loudness_lufs: Option<f32>,
+    /// Lowest acceptable integrated loudness in LUFS; audio whose loudness cannot be measured
+    /// falls below any minimum
+    pub min_loudness_lufs: Option<f32>,
+}
+
+impl Thresholds {
+    /// A description of every limit `summary` exceeds
+    pub fn violations(&self, summary: &AudioSummary) -> Vec<String> {
+        let mut violations = Vec::new();
+        if let Some(max) = self.max_peak_dbfs
+            && summary.peak_dbfs > max
+        {
+            violations.push(format!(
+                "peak {:.1} dBFS is above {max:.1} dBFS",
+                summary.peak_dbfs
+            ));
+        }
+        if let Some(max) = self.max_rms_dbfs
+            && summary.rms_dbfs > max
+        {
+            violations.push(format!(
+                "RMS {:.1} dBFS is above {max:.1} dBFS",
+                summary.rms_dbfs
+            ));
+        }
+        if let Some(max) = self.max_loudness_lufs
+            && let Some(lufs) = summary.loudness_lufs
+            && lufs > max
+        {
+            violations.push(format!("loudness {lufs:.1} LUFS is above {max:.1} LUFS"));
+        }
+        if let Some(min) = self.min_loudness_lufs {
+            match summary.loudness_lufs {
+                Some(lufs) if lufs < min => {
+                    violations.push(format!("loudness {lufs:.1} LUFS is below {min:.1} LUFS"));
+                }
+                None => violations.push(format!(
+                    "loudness could not be measured, so is below {min:.1} LUFS"
+                )),
+                Some(_) => {}
+            }
+        }
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hound::{WavSpec, WavWriter};
+
+    const SAMPLE_RATE: u32 = 48_000;
+
+    /// Two seconds of stereo audio with a sine at `frequency` and `amplitude` in both channels
+    fn stereo_sine(frequency: f32, amplitude: f32) -> Vec<f32> {
+        (0..2 * SAMPLE_RATE)
+            .flat_map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                let sample = amplitude * (2.0 * std::f32::consts::PI * frequency * t).sin();
+                [sample, sample]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sine_summary() {
+        let summary = summarize(&stereo_sine(1000.0, 0.5), 2, SAMPLE_RATE);
+        assert_eq!(summary.duration_s, 2.0);
+        assert_eq!((summary.sample_rate, summary.channels), (SAMPLE_RATE, 2));
+        assert!((summary.peak_dbfs + 6.02).abs() < 0.01, "{summary:?}");
+        assert!((summary.rms_dbfs + 9.03).abs() < 0.01, "{summary:?}");
+        // At 1 kHz the K-weighting is neutral, so each channel reads 3 dB below its peak
+        let lufs = summary.loudness_lufs.unwrap();
+        assert!((lufs + 6.0).abs() < 0.3, "{summary:?}");
+        let dominant = summary.dominant_frequency_hz.unwrap();
+        assert!((dominant - 1000.0).abs() < 1.0, "{summary:?}");
+        assert!(
+            (summary.spectral_centroid_hz - 1000.0).abs() < 200.0,
+            "{summary:?}"
+        );
+    }
+
+    #[test]
+    fn silence_summary() {
+        let summary = summarize(&[0.0; 1000], 1, SAMPLE_RATE);
+        assert_eq!(summary.peak_dbfs, MIN_LEVEL_DB);
+        assert_eq!(summary.rms_dbfs, MIN_LEVEL_DB);
+        assert_eq!(summary.loudness_lufs, None);
+        assert_eq!(summary.dominant_frequency_hz, None);
+        assert_eq!(summary.spectral_centroid_hz, 0.0);
+    }
+
+    #[test]
+    fn integer_files_are_scaled_to_full_scale() {
+        let path = std::env::temp_dir().join(format!(
+            "audio-visualizer-test-{}-summary.wav",
+            std::process::id()
+        ));
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: SAMPLE_RATE,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let mut writer = WavWriter::create(&path, spec).unwrap();
+        for sample in stereo_sine(1000.0, 0.5) {
+            writer.write_sample((sample * 32768.0) as i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let summary = summarize_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!((summary.peak_dbfs + 6.02).abs() < 0.01, "{summary:?}");
+        assert!(summarize_file(&path).is_err());
+    }
+
+    #[test]
+    fn thresholds_report_each_violation() {
+        let summary = summarize(&stereo_sine(440.0, 0.5), 2, SAMPLE_RATE);
+        assert!(Thresholds::default().violations(&summary).is_empty());
+
+        let thresholds = Thresholds {
+            max_peak_dbfs: Some(-10.0),
+            max_rms_dbfs: Some(-3.0),
+            max_loudness_lufs: Some(-14.0),
+            min_loudness_lufs: Some(-30.0),
+        };
+        let violations = thresholds.violations(&summary);
+        assert_eq!(violations.len(), 2, "{violations:?}");
+        assert!(violations[0].starts_with("peak -6.0 dBFS is above -10.0"));
+        assert!(violations[1].starts_with("loudness -6."));
+
+        let silence = summarize(&[0.0; 1000], 1, SAMPLE_RATE);
+        assert_eq!(thresholds.violations(&silence).len(), 1);
+    }
+}
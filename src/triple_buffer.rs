@@ -0,0 +1,162 @@
+use std::cell::UnsafeCell;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Bit set in the back buffer index when it holds a value the reader has not seen yet
+const NEW_DATA: u8 = 0b100;
+
+/// Mask extracting the buffer index from the back buffer state
+const INDEX_MASK: u8 = 0b011;
+
+/// Three buffers shared between exactly one writer and one reader. At any time each buffer is
+/// owned by either the writer, the reader, or sits in the middle ("back") slot, so both sides can
+/// work without locks and without ever blocking each other.
+struct Shared<T> {
+    buffers: [UnsafeCell<T>; 3],
+    back: AtomicU8,
+}
+
+// SAFETY: a buffer is only accessed by the side that currently owns its index, and ownership is
+// handed over through the atomic `back` swap with acquire/release ordering.
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+/// Create a triple buffer with every slot initialised to `initial`
+pub fn triple_buffer<T: Clone>(initial: T) -> (TripleInput<T>, TripleOutput<T>) {
+    let shared = Arc::new(Shared {
+        buffers: [
+            UnsafeCell::new(initial.clone()),
+            UnsafeCell::new(initial.clone()),
+            UnsafeCell::new(initial),
+        ],
+        back: AtomicU8::new(1),
+    });
+
+    (
+        TripleInput {
+            shared: shared.clone(),
+            write: 0,
+        },
+        TripleOutput { shared, read: 2 },
+    )
+}
+
+/// Writing side of a triple buffer
+pub struct TripleInput<T> {
+    shared: Arc<Shared<T>>,
+    write: u8,
+}
+
+impl<T> TripleInput<T> {
+    /// Get the buffer to write the next value into
+    pub fn input_buffer(&mut self) -> &mut T {
+        // SAFETY: the writer exclusively owns the `write` slot
+        unsafe { &mut *self.shared.buffers[self.write as usize].get() }
+    }
+
+    /// Publish the input buffer as the latest value. Returns `true` if the previously published
+    /// value was replaced before the reader saw it.
+    pub fn publish(&mut self) -> bool {
+        let previous = self
+            .shared
+            .back
+            .swap(self.write | NEW_DATA, Ordering::AcqRel);
+        self.write = previous & INDEX_MASK;
+        previous & NEW_DATA != 0
+    }
+}
+
+/// Reading side of a triple buffer
+pub struct TripleOutput<T> {
+    shared: Arc<Shared<T>>,
+    read: u8,
+}
+
+impl<T> TripleOutput<T> {
+    /// Take ownership of the latest published value, if there is one the reader has not seen.
+    /// Returns `true` if the output buffer changed.
+    pub fn update(&mut self) -> bool {
+        if self.shared.back.load(Ordering::Relaxed) & NEW_DATA == 0 {
+            return false;
+        }
+        let previous = self.shared.back.swap(self.read, Ordering::AcqRel);
+        self.read = previous & INDEX_MASK;
+        true
+    }
+
+    /// Get the most recently taken value
+    pub fn output_buffer(&self) -> &T {
+        // SAFETY: the reader exclusively owns the `read` slot
+        unsafe { &*self.shared.buffers[self.read as usize].get() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reader_sees_only_the_latest_published_value() {
+        let (mut input, mut output) = triple_buffer(0);
+        assert!(!output.update());
+        assert_eq!(*output.output_buffer(), 0);
+
+        *input.input_buffer() = 1;
+        assert!(!input.publish());
+        assert!(output.update());
+        assert_eq!(*output.output_buffer(), 1);
+        // Nothing new: the reader keeps its value
+        assert!(!output.update());
+        assert_eq!(*output.output_buffer(), 1);
+
+        // Publishing twice before a read replaces the unread value
+        *input.input_buffer() = 2;
+        assert!(!input.publish());
+        *input.input_buffer() = 3;
+        assert!(input.publish());
+        assert!(output.update());
+        assert_eq!(*output.output_buffer(), 3);
+        assert!(!output.update());
+    }
+
+    #[test]
+    fn writer_never_gets_the_readers_buffer() {
+        let (mut input, mut output) = triple_buffer(0);
+        for value in 1..10 {
+            *input.input_buffer() = value;
+            input.publish();
+            output.update();
+            // Writing after every publish must leave the value being read untouched
+            *input.input_buffer() = -1;
+            assert_eq!(*output.output_buffer(), value);
+        }
+    }
+
+    #[test]
+    fn concurrent_reads_are_never_torn() {
+        const FRAMES: u64 = if cfg!(miri) { 200 } else { 200_000 };
+        const LEN: usize = 64;
+
+        let (mut input, mut output) = triple_buffer([0u64; LEN]);
+        let writer = std::thread::spawn(move || {
+            for frame in 1..=FRAMES {
+                // Fill the buffer slowly, so a reader sharing it would see a mix of frames
+                for value in input.input_buffer().iter_mut() {
+                    *value = frame;
+                }
+                input.publish();
+            }
+        });
+
+        let mut last = 0;
+        while last < FRAMES {
+            if output.update() {
+                let buffer = output.output_buffer();
+                let frame = buffer[0];
+                assert!(buffer.iter().all(|&value| value == frame), "torn read");
+                assert!(frame > last, "frame {frame} after {last}");
+                last = frame;
+            }
+        }
+        writer.join().unwrap();
+    }
+}